@@ -0,0 +1,21 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+// Hawkeye is only a binary, so its parsers are built in directly
+#[allow(dead_code)]
+#[path = "../../src/parsing/mod.rs"]
+mod parsing;
+
+// Feeds each input to both record parsers, as a `jmanl` record
+//  and as a `jobstat` job, which may fail but never panic
+fuzz_target!(|data: &[u8]| {
+    let Ok(text) = std::str::from_utf8(data) else {
+        return;
+    };
+
+    let _ = parsing::pbs::parse_record_fields(text);
+    let _ = parsing::jobs::jmanl_job_str_to_btree(text);
+    let _ = parsing::pbs::parse_attribute_block(text, &["Nodes: "]);
+    let _ = parsing::jobs::jobstat_job_str_to_btree(text, chrono_tz::America::Chicago);
+});
@@ -0,0 +1,920 @@
+use std::{collections::HashSet, fmt::Display, path::Path, str::FromStr};
+
+use anyhow::{bail, Context, Result};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+
+const DEFAULT_CONFIG_PATH: &str = "hawkeye.toml";
+
+/// The full Hawkeye configuration.
+///
+/// Read from the TOML file at `HAWKEYE_CONFIG` (or `./hawkeye.toml`),
+///  after which any environment variable overrides are applied.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub server: ServerConfig,
+    pub logging: LoggingConfig,
+    pub daemons: DaemonsConfig,
+    pub site: SiteConfig,
+    pub clusters: Vec<ClusterConfig>,
+    /// Rules for judging job efficiencies, which drive the advice
+    ///  in tooltips and the colour of efficiency cells
+    pub efficiency: Vec<EfficiencyRule>,
+    pub accounting: AccountingConfig,
+    pub allocations: AllocationsConfig,
+    pub retention: RetentionConfig,
+    pub backfill: BackfillConfig
+}
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub port: u16,
+    pub url_prefix: String,
+    pub db_path: String,
+    /// How many read-only DB connections pages share
+    pub db_readers: usize
+}
+impl Default for ServerConfig {
+    fn default ( ) -> Self {
+        Self {
+            port: 5777,
+            url_prefix: String::new(),
+            db_path: String::new(),
+            db_readers: 4
+        }
+    }
+}
+/// The format of log lines
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    #[default]
+    Text,
+    /// One JSON object per line, with the fields of every
+    ///  enclosing span (i.e. `request_id` and `run_id`)
+    Json
+}
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str ( st: &str ) -> std::result::Result<Self, Self::Err> {
+        match st {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(String::from("expected `text` or `json`"))
+        }
+    }
+}
+/// Where logs are written, and how
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LoggingConfig {
+    pub format: LogFormat,
+    /// Write logs to stdout, i.e. for `docker logs`
+    pub stdout: bool,
+    /// Write logs to a daily rolling file in `./logs`
+    pub file: bool
+}
+impl Default for LoggingConfig {
+    fn default ( ) -> Self {
+        Self {
+            format: LogFormat::Text,
+            stdout: false,
+            file: true
+        }
+    }
+}
+/// Time between each run of the daemons, in seconds
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DaemonsConfig {
+    pub jobs_period: u64,
+    pub old_jobs_period: u64,
+    pub groups_period: u64,
+    pub maintenance_period: u64,
+    /// How many users' old jobs are pulled at once, per cluster. Each is
+    ///  an SSH channel, so keep this under the server's `MaxSessions`.
+    pub old_jobs_concurrency: usize,
+    /// How many of its periods the jobs daemon may go without
+    ///  succeeding before `/readyz` reports Hawkeye as not ready
+    pub stale_after_periods: u64
+}
+impl Default for DaemonsConfig {
+    fn default ( ) -> Self {
+        Self {
+            jobs_period: 60 * 15,
+            old_jobs_period: 60 * 300,
+            groups_period: 60 * 60,
+            maintenance_period: 60 * 60 * 24,
+            old_jobs_concurrency: 4,
+            stale_after_periods: 3
+        }
+    }
+}
+/// Branding, links, and permissions for the deployment
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SiteConfig {
+    /// Shown in page titles and on the login page
+    pub name: String,
+    /// Documentation on job management, linked from tooltips
+    pub docs_url: String,
+    /// Where new users can request access, linked from the header
+    pub access_url: String,
+    /// Members of this group may view every job
+    pub admin_group: String,
+    /// Times are shown in this timezone to users who haven't chosen their own,
+    ///  and allocation periods start and end at midnight in it
+    pub timezone: Tz
+}
+impl Default for SiteConfig {
+    fn default ( ) -> Self {
+        Self {
+            name: String::from("CRCD Batchmon"),
+            docs_url: String::from("https://www.niu.edu/crcd/current-users/getting-started/queue-commands-job-management.shtml"),
+            access_url: String::from("https://www.niu.edu/crcd/prospective-user/access.shtml"),
+            admin_group: String::from("hpc"),
+            timezone: Tz::UTC
+        }
+    }
+}
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ClusterConfig {
+    pub name: String,
+    pub remote_username: String,
+    pub remote_hostname: String,
+    /// Regex matching the PBS server suffix of job IDs
+    #[serde(default = "default_server_pattern")]
+    pub server_pattern: String,
+    /// Script on the cluster which verifies a username and password
+    #[serde(default = "default_verify_login_script")]
+    pub verify_login_script: String,
+    /// The timezone PBS reports local times (i.e. `stime`) in
+    #[serde(default = "default_timezone")]
+    pub timezone: Tz
+}
+fn default_server_pattern ( ) -> String {
+    String::from("cm-.+-.+")
+}
+fn default_verify_login_script ( ) -> String {
+    String::from("/opt/metis/el8/contrib/admin/batchmon/verify_login.sh")
+}
+fn default_timezone ( ) -> Tz {
+    Tz::UTC
+}
+/// Rates used to charge groups and projects for their usage
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AccountingConfig {
+    /// Shown before each charge, i.e. `$`
+    pub currency: String,
+    pub rates: Vec<QueueRates>
+}
+impl Default for AccountingConfig {
+    fn default ( ) -> Self {
+        Self {
+            currency: String::from("$"),
+            rates: Vec::new()
+        }
+    }
+}
+/// Charges per hour of walltime, either for specific queues
+///  or (with no `queues`) for every other queue
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct QueueRates {
+    pub queues: Vec<String>,
+    /// Per reserved CPU core
+    pub core_hour: f64,
+    /// Per reserved GPU
+    pub gpu_hour: f64,
+    /// Per GB of reserved memory
+    pub mem_gb_hour: f64
+}
+/// Whether usage is attributed to every group of a job's owner, or only
+///  to their project (their primary group), as in the `Project` column
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Grouping {
+    Group,
+    #[default]
+    Project
+}
+impl Grouping {
+    pub fn label ( &self ) -> &'static str {
+        match self {
+            Grouping::Group => "Group",
+            Grouping::Project => "Project"
+        }
+    }
+    /// As written in the configuration and queries
+    pub fn as_str ( &self ) -> &'static str {
+        match self {
+            Grouping::Group => "group",
+            Grouping::Project => "project"
+        }
+    }
+}
+/// Core-hour and GPU-hour allocations, tracked against job records
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AllocationsConfig {
+    /// Percentages of an allocation used at which to warn, in ascending order
+    pub warn_at: Vec<f64>,
+    pub grants: Vec<Allocation>
+}
+impl Default for AllocationsConfig {
+    fn default ( ) -> Self {
+        Self {
+            warn_at: vec!(80.0, 100.0),
+            grants: Vec::new()
+        }
+    }
+}
+/// How long job samples are kept, in days
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RetentionConfig {
+    /// Raw samples older than this are rolled up into hourly aggregates
+    pub raw_days: u64,
+    /// Hourly aggregates older than this are dropped, or never if 0
+    pub hourly_days: u64
+}
+impl Default for RetentionConfig {
+    fn default ( ) -> Self {
+        Self {
+            raw_days: 7,
+            hourly_days: 365 * 2
+        }
+    }
+}
+/// Admin-triggered pulls of job history older than the year
+///  `jmanl` is asked for by default, a month at a time
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BackfillConfig {
+    /// The date (`YYYY-MM-DD`) backfills go back to unless told otherwise
+    pub since: String,
+    /// Arguments to `jmanl` for one user's raw records over one month, where
+    ///  `{user}`, `{start}` and `{end}` (the first and last days of the month,
+    ///  as `YYYY-MM-DD`) are replaced
+    pub jmanl_args: Vec<String>
+}
+impl Default for BackfillConfig {
+    fn default ( ) -> Self {
+        Self {
+            since: String::from("2020-01-01"),
+            jmanl_args: ["{user}", "{start}", "{end}", "raw"].iter()
+                .map(|arg| arg.to_string())
+                .collect()
+        }
+    }
+}
+impl BackfillConfig {
+    /// The `jmanl` arguments for `user`'s records from `start` to `end`
+    pub fn jmanl_args_for (
+        &self,
+        user: &str,
+        start: chrono::NaiveDate,
+        end: chrono::NaiveDate
+    ) -> Vec<String> {
+        self.jmanl_args.iter()
+            .map(|arg| {
+                arg.replace("{user}", user)
+                    .replace("{start}", &start.to_string())
+                    .replace("{end}", &end.to_string())
+            })
+            .collect()
+    }
+}
+/// An allocation for a group or project over a period, from
+///  `start` to `end` (inclusive), formatted as `YYYY-MM-DD`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Allocation {
+    pub group: String,
+    #[serde(default)]
+    pub by: Grouping,
+    pub start: String,
+    pub end: String,
+    #[serde(default)]
+    pub core_hours: f64,
+    #[serde(default)]
+    pub gpu_hours: f64
+}
+impl Allocation {
+    /// The first and last day of the allocation
+    pub fn dates ( &self ) -> Result<(chrono::NaiveDate, chrono::NaiveDate)> {
+        let date = |date: &str| chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d")
+            .with_context(|| format!("Invalid date `{date}`, expected `YYYY-MM-DD`!"));
+
+        Ok((date(&self.start)?, date(&self.end)?))
+    }
+    /// The timestamps of the start of `start` and the end of `end`, in `timezone`
+    pub fn period ( &self, timezone: Tz ) -> Result<(i64, i64)> {
+        let timestamp = |date: chrono::NaiveDate| -> Result<i64> {
+            date.and_hms_opt(0, 0, 0)
+                .and_then(|date_time| date_time.and_local_timezone(timezone).earliest())
+                .map(|date_time| date_time.timestamp())
+                .with_context(|| format!("Date `{date}` is out of range!"))
+        };
+        let (start, end) = self.dates()?;
+        let end = end.succ_opt()
+            .with_context(|| format!("Date `{end}` is out of range!"))?;
+
+        Ok((timestamp(start)?, timestamp(end)?))
+    }
+}
+/// An efficiency measured for each job, in %
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Metric {
+    Cpu,
+    Mem,
+    Walltime
+}
+impl Metric {
+    pub fn label ( &self ) -> &'static str {
+        match self {
+            Metric::Cpu => "CPU",
+            Metric::Mem => "Memory",
+            Metric::Walltime => "Walltime"
+        }
+    }
+}
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Good,
+    Warning,
+    Bad
+}
+impl Severity {
+    /// Background colour of efficiency cells
+    pub fn color ( &self ) -> &'static str {
+        match self {
+            Severity::Good => "rgba(0, 205, 0, 0.5)",
+            Severity::Warning => "rgba(255, 205, 0, 0.5)",
+            Severity::Bad => "rgba(255, 0, 0, 0.5)"
+        }
+    }
+}
+/// How to judge one efficiency metric, either for specific
+///  queues or (with no `queues`) for every other queue
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EfficiencyRule {
+    pub metric: Metric,
+    #[serde(default)]
+    pub queues: Vec<String>,
+    pub bands: Vec<EfficiencyBand>
+}
+/// A range of efficiencies, from `min` (inclusive) to `max` (exclusive)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EfficiencyBand {
+    #[serde(default)]
+    pub min: f32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max: Option<f32>,
+    pub severity: Severity,
+    #[serde(default)]
+    pub advice: String,
+    /// Defaults to the job control section of `site.docs_url`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub docs_url: Option<String>
+}
+impl EfficiencyBand {
+    fn new ( min: f32, max: Option<f32>, severity: Severity, advice: &str ) -> Self {
+        Self {
+            min,
+            max,
+            severity,
+            advice: advice.to_string(),
+            docs_url: None
+        }
+    }
+    pub fn contains ( &self, value: f32 ) -> bool {
+        value >= self.min && self.max.is_none_or(|max| value < max)
+    }
+}
+impl EfficiencyRule {
+    /// The rules used for any metric without a queue-less rule configured
+    fn defaults ( ) -> Vec<Self> {
+        vec!(
+            EfficiencyRule {
+                metric: Metric::Cpu,
+                queues: Vec::new(),
+                bands: vec!(
+                    EfficiencyBand::new(0.0, Some(50.0), Severity::Bad, "The job had a low CPU load, consider reserving fewer CPUs."),
+                    EfficiencyBand::new(50.0, Some(75.0), Severity::Warning, "The job is using the CPU somewhat efficiently."),
+                )
+            },
+            EfficiencyRule {
+                metric: Metric::Mem,
+                queues: Vec::new(),
+                bands: vec!(
+                    EfficiencyBand::new(0.0, Some(50.0), Severity::Bad, "The job had low memory utilization; consider reserving less memory."),
+                    EfficiencyBand::new(50.0, Some(75.0), Severity::Warning, "The job is using the memory somewhat efficiently. If you are using a GPU, this is okay."),
+                )
+            },
+            EfficiencyRule {
+                metric: Metric::Walltime,
+                queues: Vec::new(),
+                bands: vec!(
+                    EfficiencyBand::new(0.0, Some(50.0), Severity::Warning, "The job took significantly less time than requested; please consider decreasing the requirement. The accurate estimation of the walltime needed is essential for the job starting time and will allow a better schedule of maintenance tasks."),
+                    EfficiencyBand::new(80.0, None, Severity::Bad, "The job is potentially using too much walltime, consider allocating more for breathing room to avoid having the job killed."),
+                )
+            }
+        )
+    }
+}
+
+impl Config {
+    /// Loads, overrides, and validates the configuration
+    pub fn load ( ) -> Result<Self> {
+        let mut config = match std::env::var("HAWKEYE_CONFIG") {
+            Ok(path) => Self::from_file(&path)?,
+            Err(_) if Path::new(DEFAULT_CONFIG_PATH).exists() => Self::from_file(DEFAULT_CONFIG_PATH)?,
+            Err(_) => Self::default()
+        };
+
+        config.apply_env_overrides()?;
+        config.fill_default_efficiency_rules();
+        config.validate()?;
+
+        Ok(config)
+    }
+    fn from_file ( path: &str ) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Couldn't read config file `{path}`!"))?;
+
+        toml::from_str(&contents)
+            .with_context(|| format!("Invalid config file `{path}`!"))
+    }
+    fn apply_env_overrides ( &mut self ) -> Result<()> {
+        env_override("PORT", &mut self.server.port)?;
+        env_override("URL_PREFIX", &mut self.server.url_prefix)?;
+        env_override("DB_PATH", &mut self.server.db_path)?;
+        env_override("DB_READERS", &mut self.server.db_readers)?;
+
+        env_override("LOG_FORMAT", &mut self.logging.format)?;
+        env_override("LOG_STDOUT", &mut self.logging.stdout)?;
+        env_override("LOG_FILE", &mut self.logging.file)?;
+
+        env_override("JOBS_DAEMON_PERIOD", &mut self.daemons.jobs_period)?;
+        env_override("OLD_JOBS_DAEMON_PERIOD", &mut self.daemons.old_jobs_period)?;
+        env_override("GROUPS_DAEMON_PERIOD", &mut self.daemons.groups_period)?;
+        env_override("MAINTENANCE_DAEMON_PERIOD", &mut self.daemons.maintenance_period)?;
+        env_override("OLD_JOBS_CONCURRENCY", &mut self.daemons.old_jobs_concurrency)?;
+        env_override("STALE_AFTER_PERIODS", &mut self.daemons.stale_after_periods)?;
+        env_override("RAW_STATS_RETENTION_DAYS", &mut self.retention.raw_days)?;
+        env_override("HOURLY_STATS_RETENTION_DAYS", &mut self.retention.hourly_days)?;
+        env_override("BACKFILL_SINCE", &mut self.backfill.since)?;
+
+        env_override("SITE_NAME", &mut self.site.name)?;
+        env_override("DOCS_URL", &mut self.site.docs_url)?;
+        env_override("ACCESS_URL", &mut self.site.access_url)?;
+        env_override("ADMIN_GROUP", &mut self.site.admin_group)?;
+        env_override("DISPLAY_TIMEZONE", &mut self.site.timezone)?;
+
+        // Deployments configured purely by environment
+        //  variables monitor a single cluster
+        if self.clusters.is_empty() && std::env::var("REMOTE_HOSTNAME").is_ok() {
+            self.clusters.push(ClusterConfig {
+                name: std::env::var("CLUSTER_NAME")
+                    .unwrap_or_else(|_| String::from("Metis")),
+                remote_username: String::new(),
+                remote_hostname: String::new(),
+                server_pattern: default_server_pattern(),
+                verify_login_script: default_verify_login_script(),
+                timezone: default_timezone()
+            });
+        }
+
+        // Unprefixed variables apply to the primary cluster, and
+        //  prefixed ones (i.e. `METIS_REMOTE_HOSTNAME`) to the named cluster
+        for (i, cluster) in self.clusters.iter_mut().enumerate() {
+            let env_prefix = cluster.name.to_uppercase().replace('-', "_");
+
+            for (suffix, field) in [
+                ("REMOTE_USERNAME", &mut cluster.remote_username),
+                ("REMOTE_HOSTNAME", &mut cluster.remote_hostname),
+                ("PBS_SERVER_PATTERN", &mut cluster.server_pattern),
+                ("VERIFY_LOGIN_SCRIPT", &mut cluster.verify_login_script)
+            ] {
+                if i == 0 {
+                    env_override(suffix, field)?;
+                }
+                env_override(&format!("{env_prefix}_{suffix}"), field)?;
+            }
+            if i == 0 {
+                env_override("CLUSTER_TIMEZONE", &mut cluster.timezone)?;
+            }
+            env_override(&format!("{env_prefix}_TIMEZONE"), &mut cluster.timezone)?;
+        }
+
+        Ok(())
+    }
+    fn fill_default_efficiency_rules ( &mut self ) {
+        for default_rule in EfficiencyRule::defaults() {
+            let has_default = self.efficiency.iter()
+                .any(|rule| rule.metric == default_rule.metric && rule.queues.is_empty());
+
+            if !has_default {
+                self.efficiency.push(default_rule);
+            }
+        }
+    }
+    fn validate ( &self ) -> Result<()> {
+        let mut problems = Vec::new();
+
+        if self.server.port == 0 {
+            problems.push(String::from("`server.port` must not be 0"));
+        }
+        if !self.server.url_prefix.is_empty()
+            && (!self.server.url_prefix.starts_with('/') || self.server.url_prefix.ends_with('/'))
+        {
+            problems.push(format!("`server.url_prefix` must start with `/` and not end with one (i.e. `/hawkeye`), got `{}`", self.server.url_prefix));
+        }
+        if self.server.db_path.trim().is_empty() {
+            problems.push(String::from("`server.db_path` (or `DB_PATH`) must be set"));
+        }
+        if !self.logging.stdout && !self.logging.file {
+            problems.push(String::from("At least one of `logging.stdout` and `logging.file` must be enabled"));
+        }
+        if self.server.db_readers == 0 {
+            problems.push(String::from("`server.db_readers` must be at least 1"));
+        }
+
+        for (name, period) in [
+            ("daemons.jobs_period", self.daemons.jobs_period),
+            ("daemons.old_jobs_period", self.daemons.old_jobs_period),
+            ("daemons.groups_period", self.daemons.groups_period),
+            ("daemons.maintenance_period", self.daemons.maintenance_period)
+        ] {
+            if period == 0 {
+                problems.push(format!("`{name}` must be at least 1 second"));
+            }
+        }
+        if self.daemons.old_jobs_concurrency == 0 {
+            problems.push(String::from("`daemons.old_jobs_concurrency` must be at least 1"));
+        }
+        if self.daemons.stale_after_periods == 0 {
+            problems.push(String::from("`daemons.stale_after_periods` must be at least 1"));
+        }
+
+        if self.site.name.trim().is_empty() {
+            problems.push(String::from("`site.name` must not be empty"));
+        }
+        if self.site.admin_group.trim().is_empty() {
+            problems.push(String::from("`site.admin_group` must not be empty"));
+        }
+        for (name, url) in [
+            ("site.docs_url", &self.site.docs_url),
+            ("site.access_url", &self.site.access_url)
+        ] {
+            if !url.starts_with("https://") && !url.starts_with("http://") {
+                problems.push(format!("`{name}` must be an http(s) URL, got `{url}`"));
+            }
+        }
+
+        if self.clusters.is_empty() {
+            problems.push(String::from("At least one `[[clusters]]` entry (or `REMOTE_USERNAME` and `REMOTE_HOSTNAME`) must be configured"));
+        }
+        let mut names = HashSet::new();
+        for cluster in self.clusters.iter() {
+            if cluster.name.is_empty()
+                || !cluster.name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+            {
+                problems.push(format!("Cluster name `{}` must be non-empty and only contain letters, numbers, `-` and `_`", cluster.name));
+            }
+            if !names.insert(cluster.name.as_str()) {
+                problems.push(format!("Cluster `{}` is configured more than once", cluster.name));
+            }
+            if cluster.remote_username.trim().is_empty() {
+                problems.push(format!("Cluster `{}` is missing `remote_username`", cluster.name));
+            }
+            if cluster.remote_hostname.trim().is_empty() {
+                problems.push(format!("Cluster `{}` is missing `remote_hostname`", cluster.name));
+            }
+            if let Err(e) = regex::Regex::new(&cluster.server_pattern) {
+                problems.push(format!("Cluster `{}` has an invalid `server_pattern`: {e}", cluster.name));
+            }
+            if !cluster.verify_login_script.starts_with('/') {
+                problems.push(format!("Cluster `{}` must use an absolute `verify_login_script` path, got `{}`", cluster.name, cluster.verify_login_script));
+            }
+        }
+
+        for rule in self.efficiency.iter() {
+            let metric = rule.metric.label();
+
+            if rule.queues.iter().any(|queue| queue.trim().is_empty()) {
+                problems.push(format!("{metric} efficiency rule has an empty queue name"));
+            }
+            for band in rule.bands.iter() {
+                if band.min < 0.0 || band.max.is_some_and(|max| max <= band.min) {
+                    let max = band.max.map(|max| max.to_string()).unwrap_or(String::from("none"));
+                    problems.push(format!("{metric} efficiency band must have `0 <= min < max`, got `min = {}`, `max = {max}`", band.min));
+                }
+                if let Some(docs_url) = band.docs_url.as_ref()
+                    .filter(|url| !url.starts_with("https://") && !url.starts_with("http://"))
+                {
+                    problems.push(format!("{metric} efficiency band `docs_url` must be an http(s) URL, got `{docs_url}`"));
+                }
+            }
+        }
+
+        for rates in self.accounting.rates.iter() {
+            if rates.queues.iter().any(|queue| queue.trim().is_empty()) {
+                problems.push(String::from("Accounting rates have an empty queue name"));
+            }
+            if rates.core_hour < 0.0 || rates.gpu_hour < 0.0 || rates.mem_gb_hour < 0.0 {
+                problems.push(format!("Accounting rates for queues {:?} must not be negative", rates.queues));
+            }
+        }
+
+        if self.allocations.warn_at.iter().any(|percent| *percent <= 0.0)
+            || !self.allocations.warn_at.is_sorted()
+        {
+            problems.push(String::from("`allocations.warn_at` must be positive percentages, in ascending order"));
+        }
+        for allocation in self.allocations.grants.iter() {
+            let group = &allocation.group;
+
+            if group.trim().is_empty() {
+                problems.push(String::from("Allocations must name a `group`"));
+            }
+            match allocation.period(self.site.timezone) {
+                Ok((start, end)) if start >= end => {
+                    problems.push(format!("Allocation for `{group}` must not end before it starts"));
+                },
+                Ok(_) => {},
+                Err(e) => problems.push(format!("Allocation for `{group}` has an invalid period: {e}"))
+            }
+            if allocation.core_hours < 0.0 || allocation.gpu_hours < 0.0
+                || (allocation.core_hours == 0.0 && allocation.gpu_hours == 0.0)
+            {
+                problems.push(format!("Allocation for `{group}` must grant a positive number of `core_hours` and/or `gpu_hours`"));
+            }
+        }
+
+        if self.retention.raw_days == 0 {
+            problems.push(String::from("`retention.raw_days` must be at least 1 day"));
+        }
+        if self.retention.hourly_days != 0 && self.retention.hourly_days < self.retention.raw_days {
+            problems.push(format!(
+                "`retention.hourly_days` must be 0 (forever) or at least `retention.raw_days` ({}), got {}",
+                self.retention.raw_days,
+                self.retention.hourly_days
+            ));
+        }
+
+        if chrono::NaiveDate::parse_from_str(&self.backfill.since, "%Y-%m-%d").is_err() {
+            problems.push(format!("`backfill.since` must be a `YYYY-MM-DD` date, got `{}`", self.backfill.since));
+        }
+        for placeholder in ["{user}", "{start}", "{end}"] {
+            if !self.backfill.jmanl_args.iter().any(|arg| arg.contains(placeholder)) {
+                problems.push(format!("`backfill.jmanl_args` must use `{placeholder}`"));
+            }
+        }
+
+        if !problems.is_empty() {
+            bail!("Invalid configuration!\n{}", problems.iter()
+                .map(|problem| format!("  - {problem}"))
+                .collect::<Vec<String>>()
+                .join("\n"));
+        }
+
+        Ok(())
+    }
+    /// The primary cluster, which is used for logins and groups
+    pub fn primary_cluster ( &self ) -> &ClusterConfig {
+        self.clusters.first()
+            .expect("Validated configs have at least one cluster!")
+    }
+    /// The efficiency band a job falls into, preferring rules
+    ///  specific to its queue over the queue-less default
+    pub fn efficiency_band (
+        &self,
+        metric: Metric,
+        queue: &str,
+        value: f32
+    ) -> Option<&EfficiencyBand> {
+        let rules = || self.efficiency.iter()
+            .filter(move |rule| rule.metric == metric);
+
+        rules()
+            .find(|rule| rule.queues.iter().any(|rule_queue| rule_queue == queue))
+            .or_else(|| rules().find(|rule| rule.queues.is_empty()))?
+            .bands.iter()
+            .find(|band| band.contains(value))
+    }
+    /// The rates charged for a queue, preferring rates specific
+    ///  to it over the queue-less default
+    pub fn queue_rates ( &self, queue: &str ) -> Option<&QueueRates> {
+        self.accounting.rates.iter()
+            .find(|rates| rates.queues.iter().any(|rates_queue| rates_queue == queue))
+            .or_else(|| self.accounting.rates.iter().find(|rates| rates.queues.is_empty()))
+    }
+    /// The allocations granted to a group or project
+    pub fn group_allocations ( &self, group: &str ) -> Vec<&Allocation> {
+        self.allocations.grants.iter()
+            .filter(|allocation| allocation.group == group)
+            .collect()
+    }
+    /// The effective configuration, for printing at startup
+    pub fn render ( &self ) -> String {
+        toml::to_string_pretty(self)
+            .unwrap_or_else(|e| format!("Couldn't render configuration! {e}"))
+    }
+}
+
+fn env_override<T> ( name: &str, target: &mut T ) -> Result<()>
+where
+    T: FromStr,
+    T::Err: Display
+{
+    if let Ok(value) = std::env::var(name) {
+        *target = value.parse::<T>()
+            .map_err(|e| anyhow::anyhow!("Invalid `{name}` environment variable `{value}`: {e}"))?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    /// Environment variables are shared by every test, so
+    ///  tests which set them take turns
+    static ENV_LOCK: Mutex<()> = Mutex::new(());
+
+    const MINIMAL: &str = r#"
+        [server]
+        db_path = "hawkeye.db"
+
+        [[clusters]]
+        name = "metis"
+        remote_username = "hawkeye"
+        remote_hostname = "metis.example.edu"
+    "#;
+
+    fn parse ( toml: &str ) -> Config {
+        let mut config: Config = toml::from_str(toml).unwrap();
+        config.fill_default_efficiency_rules();
+        config
+    }
+    fn problems ( config: &Config ) -> String {
+        format!("{:#}", config.validate().unwrap_err())
+    }
+
+    #[test]
+    fn minimal_config_is_valid () {
+        let config = parse(MINIMAL);
+        config.validate().unwrap();
+
+        let cluster = config.primary_cluster();
+        assert_eq!(cluster.name, "metis");
+        assert_eq!(cluster.server_pattern, default_server_pattern());
+        assert_eq!(cluster.timezone, Tz::UTC);
+        assert_eq!(config.server.port, 5777);
+        assert_eq!(config.daemons.jobs_period, 60 * 15);
+        assert!(config.logging.file);
+        assert_eq!(config.efficiency.len(), 3);
+    }
+    #[test]
+    fn rejects_unknown_fields () {
+        assert!(toml::from_str::<Config>("[server]\nprot = 80").is_err());
+    }
+    #[test]
+    fn rejects_no_clusters () {
+        let config = parse("[server]\ndb_path = \"hawkeye.db\"");
+        assert!(problems(&config).contains("At least one `[[clusters]]` entry"));
+    }
+    #[test]
+    fn rejects_duplicate_cluster_names () {
+        let config = parse(&format!("{MINIMAL}
+            [[clusters]]
+            name = \"metis\"
+            remote_username = \"hawkeye\"
+            remote_hostname = \"other.example.edu\"
+        "));
+        assert!(problems(&config).contains("Cluster `metis` is configured more than once"));
+    }
+    #[test]
+    fn rejects_bad_timezones () {
+        let site = MINIMAL.replace("[server]", "[site]\ntimezone = \"Mars/Olympus_Mons\"\n[server]");
+        assert!(toml::from_str::<Config>(&site).is_err());
+
+        let cluster = format!("{MINIMAL}\ntimezone = \"Mars/Olympus_Mons\"");
+        assert!(toml::from_str::<Config>(&cluster).is_err());
+    }
+    #[test]
+    fn rejects_bad_url_prefixes () {
+        for url_prefix in ["hawkeye", "/hawkeye/", "/"] {
+            let mut config = parse(MINIMAL);
+            config.server.url_prefix = url_prefix.to_string();
+            assert!(problems(&config).contains("`server.url_prefix` must start with `/`"), "{url_prefix}");
+        }
+
+        for url_prefix in ["", "/hawkeye", "/apps/hawkeye"] {
+            let mut config = parse(MINIMAL);
+            config.server.url_prefix = url_prefix.to_string();
+            config.validate().unwrap();
+        }
+    }
+    #[test]
+    fn lists_every_problem () {
+        let mut config = parse(MINIMAL);
+        config.server.port = 0;
+        config.retention.raw_days = 0;
+
+        let problems = problems(&config);
+        assert!(problems.contains("`server.port` must not be 0"));
+        assert!(problems.contains("`retention.raw_days` must be at least 1 day"));
+    }
+    #[test]
+    fn environment_overrides_the_file () {
+        let _lock = ENV_LOCK.lock().unwrap_or_else(std::sync::PoisonError::into_inner);
+        let vars = [
+            ("PORT", "8080"),
+            ("DB_PATH", "/data/hawkeye.db"),
+            ("REMOTE_HOSTNAME", "unprefixed.example.edu"),
+            ("REMOTE_USERNAME", "unprefixed"),
+            ("METIS_REMOTE_HOSTNAME", "prefixed.example.edu"),
+            ("CLUSTER_TIMEZONE", "America/Chicago"),
+            ("OTHER_REMOTE_USERNAME", "other")
+        ];
+        for (name, value) in vars {
+            std::env::set_var(name, value);
+        }
+
+        let mut config = parse(&format!("{MINIMAL}
+            [[clusters]]
+            name = \"other\"
+            remote_username = \"hawkeye\"
+            remote_hostname = \"other.example.edu\"
+        "));
+        let result = config.apply_env_overrides();
+        for (name, _) in vars {
+            std::env::remove_var(name);
+        }
+        result.unwrap();
+
+        assert_eq!(config.server.port, 8080);
+        assert_eq!(config.server.db_path, "/data/hawkeye.db");
+
+        // Prefixed variables beat unprefixed ones, which only
+        //  apply to the primary cluster
+        let (metis, other) = (&config.clusters[0], &config.clusters[1]);
+        assert_eq!(metis.remote_hostname, "prefixed.example.edu");
+        assert_eq!(metis.remote_username, "unprefixed");
+        assert_eq!(metis.timezone, chrono_tz::America::Chicago);
+        assert_eq!(other.remote_hostname, "other.example.edu");
+        assert_eq!(other.remote_username, "other");
+        assert_eq!(other.timezone, Tz::UTC);
+    }
+    #[test]
+    fn environment_alone_configures_one_cluster () {
+        let _lock = ENV_LOCK.lock().unwrap_or_else(std::sync::PoisonError::into_inner);
+        let vars = [
+            ("DB_PATH", "hawkeye.db"),
+            ("REMOTE_HOSTNAME", "metis.example.edu"),
+            ("REMOTE_USERNAME", "hawkeye")
+        ];
+        for (name, value) in vars {
+            std::env::set_var(name, value);
+        }
+
+        let mut config = Config::default();
+        let result = config.apply_env_overrides();
+        for (name, _) in vars {
+            std::env::remove_var(name);
+        }
+        result.unwrap();
+        config.fill_default_efficiency_rules();
+        config.validate().unwrap();
+
+        assert_eq!(config.clusters.len(), 1);
+        assert_eq!(config.primary_cluster().name, "Metis");
+        assert_eq!(config.primary_cluster().remote_hostname, "metis.example.edu");
+    }
+    #[test]
+    fn rejects_invalid_environment_values () {
+        let _lock = ENV_LOCK.lock().unwrap_or_else(std::sync::PoisonError::into_inner);
+        std::env::set_var("DISPLAY_TIMEZONE", "Mars/Olympus_Mons");
+
+        let mut config = parse(MINIMAL);
+        let result = config.apply_env_overrides();
+        std::env::remove_var("DISPLAY_TIMEZONE");
+
+        let e = result.unwrap_err().to_string();
+        assert!(e.contains("Invalid `DISPLAY_TIMEZONE` environment variable"), "{e}");
+    }
+}
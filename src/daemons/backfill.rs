@@ -0,0 +1,141 @@
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use chrono::{Datelike, Months, NaiveDate};
+use tokio::task::JoinSet;
+use tracing::{error, info, warn, Instrument};
+
+use crate::{
+    daemons::jobs::{insert_jmanl_jobs, pull_jmanl_jobs, render_full_error},
+    db::lib::BackfillRun,
+    routes::AppState
+};
+
+/// How often to check for a backfill to run, in seconds
+pub const BACKFILL_POLL_PERIOD: u64 = 30;
+
+/// The first day of the month `date` is in
+pub fn month_of ( date: NaiveDate ) -> NaiveDate {
+    date.with_day(1)
+        .expect("Every month has a first day!")
+}
+/// How many months a backfill from `first_month` back to `since` pulls
+pub fn months_between ( since: NaiveDate, first_month: NaiveDate ) -> u32 {
+    let since = month_of(since);
+    let months = (first_month.year() - since.year()) * 12
+        + first_month.month() as i32 - since.month() as i32;
+
+    months.max(0) as u32 + 1
+}
+
+/// Pulls one month of a backfill for each of its users, returning
+///  how many jobs were ingested, how many pulls failed, and the
+///  last failure
+#[tracing::instrument(skip(app))]
+async fn backfill_month (
+    app: Arc<AppState>,
+    run: &BackfillRun,
+    month: NaiveDate
+) -> Result<(u64, u64, Option<String>)> {
+    let cluster = app.cluster(&run.cluster)
+        .with_context(|| format!("Cluster `{}` is no longer configured!", run.cluster))?
+        .clone();
+    let users = if run.users.is_empty() {
+        app.db
+            .get_known_users()
+            .await
+            .context("Couldn't get users!")?
+    } else {
+        run.users.clone()
+    };
+    let end = month.checked_add_months(Months::new(1))
+        .and_then(|next_month| next_month.pred_opt())
+        .context("Month is out of range!")?;
+
+    // Pull a bounded number of users at once, like the old jobs daemon
+    let concurrency = app.config.daemons.old_jobs_concurrency;
+    let mut tasks = JoinSet::new();
+    let mut results = Vec::new();
+    for user in users {
+        if tasks.len() >= concurrency {
+            results.extend(tasks.join_next().await);
+        }
+
+        let app = app.clone();
+        let cluster = cluster.clone();
+        tasks.spawn(async move {
+            let args = app.config.backfill.jmanl_args_for(&user, month, end);
+            let result = async {
+                let jobs = pull_jmanl_jobs(&cluster, args.iter().map(|arg| arg.as_str()).collect())
+                    .await?;
+                let counts = insert_jmanl_jobs(&app, &cluster, &jobs)
+                    .await?;
+
+                Ok::<_, anyhow::Error>(counts.total() as u64)
+            }.await;
+
+            result.map_err(|e| {
+                let full_error = render_full_error(&e);
+                error!("Couldn't backfill {user} for {month} on {}! {full_error}", cluster.name);
+                format!("{user} ({month}): {e}")
+            })
+        }.in_current_span());
+    }
+    results.extend(tasks.join_all().await.into_iter().map(Ok));
+
+    let mut jobs_ingested = 0;
+    let mut errors = 0;
+    let mut last_error = None;
+    for result in results {
+        match result.context("Backfill task panicked!")? {
+            Ok(jobs) => jobs_ingested += jobs,
+            Err(e) => {
+                errors += 1;
+                last_error = Some(e);
+            }
+        }
+    }
+
+    Ok((jobs_ingested, errors, last_error))
+}
+/// Pulls the next month of the oldest running backfill, if any,
+///  returning how many jobs were ingested if there was one.
+///
+/// Progress is recorded after each month, so backfills resume
+///  where they left off after a restart.
+#[tracing::instrument(skip(app))]
+pub async fn backfill_helper ( app: Arc<AppState> ) -> Result<Option<u64>> {
+    let Some(run) = app.db
+        .get_running_backfill()
+        .await
+        .context("Couldn't get running backfill!")?
+    else {
+        return Ok(None);
+    };
+    let Some(ref month) = run.next_month else {
+        warn!("Backfill {} is running with no months left!", run.run_id);
+        app.db
+            .record_backfill_month(run.run_id, None, 0, 0, None)
+            .await
+            .context("Couldn't finish backfill!")?;
+        return Ok(Some(0));
+    };
+
+    let month = NaiveDate::parse_from_str(month, "%Y-%m-%d")
+        .with_context(|| format!("Invalid backfill month `{month}`!"))?;
+    let since = NaiveDate::parse_from_str(&run.since, "%Y-%m-%d")
+        .with_context(|| format!("Invalid backfill date `{}`!", run.since))?;
+    info!("[ Backfilling {} for run {} on {} ]", month.format("%Y-%m"), run.run_id, run.cluster);
+
+    let (jobs_ingested, errors, last_error) = backfill_month(app.clone(), &run, month).await?;
+    let next_month = month.checked_sub_months(Months::new(1))
+        .filter(|next_month| *next_month >= month_of(since))
+        .map(|next_month| next_month.to_string());
+    app.db
+        .record_backfill_month(run.run_id, next_month.as_deref(), jobs_ingested, errors, last_error.as_deref())
+        .await
+        .context("Couldn't record backfill progress!")?;
+    info!("[ Backfilled {jobs_ingested} jobs with {errors} errors for run {} ]", run.run_id);
+
+    Ok(Some(jobs_ingested))
+}
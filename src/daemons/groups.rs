@@ -1,97 +1,83 @@
 use std::sync::Arc;
 
-use anyhow::{Context, Result};
-use tracing::{info, error};
+use anyhow::{bail, Context, Result};
+use tracing::info;
 
-use crate::{daemons::jobs::render_full_error, routes::AppState};
+use crate::{parsing::groups::{parse_group_database, parse_primary_gids, user_memberships}, routes::AppState};
 use super::super::remote::command::*;
 
-const GROUPS_PERIOD: u64 = 60 * 60;
-
-#[tracing::instrument]
-pub async fn grab_group_thread (
-    app: Arc<AppState>,
-    user: String
+/// Looks up the groups of `users` from the whole group database
+///  at once, replacing their recorded memberships
+#[tracing::instrument(skip(users), fields(users = users.len()))]
+async fn grab_groups (
+    app: &AppState,
+    users: &[String]
 ) -> Result<()> {
-    let group_output: String = remote_command(
-        &app,
-        "groups",
-        vec![&user],
+    // Group membership is managed centrally, so the primary
+    //  cluster is authoritative for every cluster
+    let cluster = app.primary_cluster();
+    let group_output = remote_command(
+        cluster,
+        "getent",
+        vec!("group"),
         false
     ).await
         .context("Failed to run remote command!")?;
+    let groups = parse_group_database(&group_output)
+        .context("Invalid output from `getent group`!")?;
+    if groups.is_empty() {
+        bail!("`getent group` returned no groups!");
+    }
 
-    let groups: Vec<&str> = group_output
-        .split(" : ")
-        .nth(1)
-        .context("Invalid output from the `groups` command!")?
-        .split_whitespace()
-        .collect();
-    info!("Got groups for `{user}`: {groups:?}");
+    // Primary groups aren't listed as memberships in the group
+    //  database, and `getent` exits with 2 if some users are gone
+    let passwd_output = remote_command_allowing(
+        cluster,
+        "getent",
+        std::iter::once("passwd")
+            .chain(users.iter().map(|user| user.as_str()))
+            .collect(),
+        false,
+        &[0, 2]
+    ).await
+        .context("Failed to run remote command!")?;
+    let primary_gids = parse_primary_gids(&passwd_output)
+        .context("Invalid output from `getent passwd`!")?;
+    info!("Got {} groups and {} of {} users", groups.len(), primary_gids.len(), users.len());
 
-    app.db
-        .insert_user_groups(&user, groups)
+    let memberships = user_memberships(&groups, &primary_gids, users);
+    let (added, removed) = app.db
+        .set_user_groups(&memberships)
         .await
-        .context("Couldn't insert user groups!")?;
-    
-    info!("Inserted groups for `{user}`!");
+        .context("Couldn't update user groups!")?;
+
+    info!("Updated groups, with {added} memberships added and {removed} removed!");
 
     Ok(())
 }
+/// Looks up the groups of a single (i.e. newly logged in) user
+#[tracing::instrument]
+pub async fn grab_group_thread (
+    app: Arc<AppState>,
+    user: String
+) -> Result<()> {
+    grab_groups(&app, &[user]).await
+}
+/// Looks up the groups of every known user, returning how many there were
 #[tracing::instrument]
-async fn grab_groups_helper ( app: Arc<AppState> ) -> Result<()> {
+pub async fn grab_groups_helper ( app: Arc<AppState> ) -> Result<u64> {
     // Get a list of all users from the DB
     let users = app.db
         .get_users()
         .await
         .context("Couldn't get users!")?;
 
-    info!("[ Got Users ]: {users:?}");
-
-    // Spawn a task for each user, but collect the JoinHandles
-    for user in users {
-        let app = app.clone();
-        let user_cloned = user.clone();
-
-        // We deliberately swallow the actual Result here, but you could propagate it
-        if let Err(e) = grab_group_thread(
-            app,
-            user_cloned
-        ).await {
-            let e = render_full_error(&e);
-            error!(%e, "Failed to grab groups for {user}!");
-        }
+    info!("[ Got {} Users ]", users.len());
+    if users.is_empty() {
+        return Ok(0);
     }
 
-    Ok(())
-}
-pub async fn groups_daemon (
-    app: Arc<AppState>
-) -> ! {
-    let groups_period = std::env::var("GROUPS_DAEMON_PERIOD")
-        .unwrap_or(GROUPS_PERIOD.to_string())
-        .parse::<u64>()
-        .expect("Invalid `GROUPS_DAEMON_PERIOD` value!");
-    info!("[ Groups period: {groups_period} ]");
-
-    // Wait for the web server to start up
-    tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
-
-    // Wait even longer for jobs to be populated
-    tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
+    grab_groups(&app, &users).await?;
 
-    loop {
-        info!("[ Pulling groups... ]");
-        if let Err(e) = grab_groups_helper( app.clone() ).await {
-            error!(%e, "Failed to run remote command!");
-
-            tokio::time::sleep(tokio::time::Duration::from_secs(groups_period)).await;
-            continue;
-        };
-        info!("[ Groups pulled! ]");
-
-        tokio::time::sleep(tokio::time::Duration::from_secs(
-            groups_period
-        )).await;
-    }
+    Ok(users.len() as u64)
 }
\ No newline at end of file
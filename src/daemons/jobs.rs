@@ -1,18 +1,16 @@
-use std::{collections::BTreeMap, sync::Arc};
+use std::{collections::{BTreeMap, HashMap}, sync::Arc};
 
 use anyhow::{anyhow, Context, Result};
 use regex::Regex;
-use tracing::{error, info};
+use tokio::task::{self, JoinError, JoinSet};
+use tracing::{error, info, Instrument};
 
-use crate::routes::AppState;
+use crate::{db::lib::IngestCounts, routes::{AppState, Cluster}};
 use super::super::{
     remote::command::*,
     parsing::jobs::*,
 };
 
-const DEFAULT_JOBSTAT_PERIOD: u64 = 60 * 15;
-const DEFAULT_OLD_JOB_PERIOD: u64 = 60 * 300;
-
 pub fn render_full_error (
     e: &anyhow::Error
 ) -> String {
@@ -26,23 +24,49 @@ pub fn render_full_error (
     full_error
 }
 
+/// Records ending this long (in seconds) before a user's last pull
+///  are still ingested, in case they were accounted late
+const JMANL_CHECKPOINT_OVERLAP: i64 = 60 * 60;
+
+/// The smallest `jmanl` period which covers everything since
+///  `since`, or a year (the initial backfill) if there's no checkpoint
+///
+/// `since` comes from the last successful pull, so users
+///  who've had nothing finish lately still get short periods
+fn jmanl_period ( since: Option<i64>, now: i64 ) -> &'static str {
+    let Some(since) = since else {
+        return "year";
+    };
+
+    match now - since {
+        age if age < 60 * 60 * 24 => "day",
+        age if age < 60 * 60 * 24 * 7 => "week",
+        age if age < 60 * 60 * 24 * 28 => "month",
+        _ => "year"
+    }
+}
+/// Runs `jmanl` with `args` (which must ask for `raw` records),
+///  and parses the records it lists
 #[tracing::instrument]
-pub async fn grab_old_jobs_thread (
-    app: Arc<AppState>,
-    user: String
-) -> Result<()> {
+pub async fn pull_jmanl_jobs (
+    cluster: &Cluster,
+    args: Vec<&str>
+) -> Result<Vec<BTreeMap<String, String>>> {
     let old_jobs_raw = remote_command(
-        &app,
+        cluster,
         "jmanl",
-        vec!(&user, "year", "raw"),
+        args,
         true
     ).await
         .context("Couldn't get output from `jmanl` command!")?;
 
-    // Extract the job ID and # of chunks from the following:
+    // Extract the full job ID (including any array index and the
+    //  server, i.e. `12345[7].cm-pbs-01`) and # of chunks from the following:
     //  (and nothing else, the rest is garbage)
-    let formatted_jmantl_re = Regex::new(r"Job (\d+)\.cm-.+-.+ \(\d+ CPUs, \d+ node\(s\), (\d+) chunk\(s\)\)")
-        .context("Couldn't compile regex!")?;
+    let formatted_jmantl_re = Regex::new(&format!(
+        r"Job (\d+(?:\[\d*\])?\.(?:{})) \(\d+ CPUs, \d+ node\(s\), (\d+) chunk\(s\)\)",
+        cluster.server_pattern
+    )).context("Couldn't compile regex!")?;
 
     // Create a BTreeMap from the job line
     let mut chunks_map = BTreeMap::new();
@@ -50,6 +74,10 @@ pub async fn grab_old_jobs_thread (
         .captures_iter(&old_jobs_raw)
         .map(|c| c.extract())
     {
+        let job_id = parse_job_id(job_id)
+            .with_context(|| format!("Couldn't parse job ID `{job_id}` from `jmanl` summary!"))?
+            .key();
+
         chunks_map.insert(job_id, num_chunks);
     }
 
@@ -72,15 +100,7 @@ pub async fn grab_old_jobs_thread (
         input.split("\n") 
     }.filter(|line| !line.is_empty())
         .flat_map(|job_line| {
-            match jmanl_job_str_to_btree(
-                job_line.split(';')
-                        .take(3)
-                        .collect::<Vec<&str>>(),
-                &job_line.split(';')
-                        .skip(3)
-                        .collect::<Vec<&str>>()
-                        .join(";")
-            ) {
+            match jmanl_job_str_to_btree(job_line) {
                 Ok(job) => {
                     let job_id = if let Some(job_id) = job.get("job_id") {
                         job_id
@@ -101,36 +121,97 @@ pub async fn grab_old_jobs_thread (
                     Some(job)
                 },
                 Err(e) => {
-                    error!(%e, "Couldn't parse `jmanl` job line: {job_line}!");
+                    // The full chain includes where in the record it failed
+                    error!("Couldn't parse `jmanl` job line: {job_line}! {e:#}");
                     None
                 }
             }
         })
         .collect::<Vec<BTreeMap<String, String>>>();
 
-    // Because the job comes out as a BTreeMap<String, String>,
-    //  we need to convert it to a BTreeMap<&str, String>
-    let jobs = jobs.iter()
-        .map(|job| {
-            job.iter()
-                .map(|(k, v)| (k.as_str(), v.clone()))
-                .collect::<BTreeMap<&str, String>>()
-        })
-        .collect::<Vec<BTreeMap<&str, String>>>();
-    
-    for job in jobs.iter() {
-        app.db
-            .insert_job(job)
-            .await
-            .with_context(|| format!("Couldn't insert old job {job:?}!"))?;
-    }
+    Ok(jobs)
+
+}
+/// Inserts jobs parsed by `pull_jmanl_jobs`
+pub async fn insert_jmanl_jobs (
+    app: &AppState,
+    cluster: &Cluster,
+    jobs: &[BTreeMap<String, String>]
+) -> Result<IngestCounts> {
+    let counts = app.db
+        .insert_jobs(&cluster.name, jobs)
+        .await
+        .context("Couldn't insert old jobs!")?;
 
-    Ok(())
+    Ok(counts)
 }
 #[tracing::instrument]
-async fn grab_old_jobs_helper (
-    app: Arc<AppState>
-) -> Result<()> {
+pub async fn grab_old_jobs_thread (
+    app: Arc<AppState>,
+    cluster: Arc<Cluster>,
+    user: String
+) -> Result<usize> {
+    // Only fetch what's new since the last run, with some overlap,
+    //  and record this run's start as the next run's checkpoint
+    let pulled_at = chrono::Utc::now().timestamp();
+    let since = app.db
+        .get_jmanl_checkpoint(&cluster.name, &user)
+        .await
+        .context("Couldn't get `jmanl` checkpoint!")?
+        .map(|checkpoint| checkpoint - JMANL_CHECKPOINT_OVERLAP);
+    let period = jmanl_period(since, pulled_at);
+    info!("Pulling old jobs for `{user}` on {} since {since:?} (`{period}`)", cluster.name);
+
+    let mut jobs = pull_jmanl_jobs(&cluster, vec!(&user, period, "raw"))
+        .await?;
+
+    // Skip records which were already ingested
+    jobs.retain(|job| {
+        let end_time = job.get("end_time")
+            .and_then(|st| st.parse::<i64>().ok())
+            .unwrap_or(0);
+        since.is_none_or(|since| end_time >= since)
+    });
+    let counts = insert_jmanl_jobs(&app, &cluster, &jobs)
+        .await?;
+
+    // Only move the checkpoint once every record before it is in
+    app.db
+        .set_jmanl_checkpoint(&cluster.name, &user, pulled_at)
+        .await
+        .context("Couldn't set `jmanl` checkpoint!")?;
+    info!("Ingested {} old jobs for `{user}` on {} ({} new, {} updated)", counts.total(), cluster.name, counts.inserted, counts.updated);
+
+    Ok(counts.total())
+}
+/// How many jobs a finished per-user pull ingested, logging
+///  the user if its task panicked or was cancelled instead
+fn finished_pull (
+    result: Result<(task::Id, u64), JoinError>,
+    users: &mut HashMap<task::Id, String>,
+    cluster: &str
+) -> u64 {
+    match result {
+        Ok((id, ingested)) => {
+            users.remove(&id);
+            ingested
+        },
+        Err(e) => {
+            let user = users.remove(&e.id())
+                .unwrap_or_else(|| String::from("an unknown user"));
+            let what = if e.is_panic() { "panicked" } else { "was cancelled" };
+            error!("Grabbing old jobs for {user} on {cluster} {what}, so their checkpoint wasn't moved! {e}");
+            0
+        }
+    }
+}
+/// Pulls every user's new `jmanl` records, returning
+///  how many jobs were ingested
+#[tracing::instrument]
+pub async fn grab_old_jobs_helper (
+    app: Arc<AppState>,
+    cluster: Arc<Cluster>
+) -> Result<u64> {
     // Get a list of all users from the DB
     let users = app
         .db
@@ -138,56 +219,58 @@ async fn grab_old_jobs_helper (
         .await
         .context("Couldn't get users!")?;
 
+    // Pull a bounded number of users at once, starting
+    //  the next as soon as any finishes
+    let concurrency = app.config.daemons.old_jobs_concurrency;
+    let mut tasks = JoinSet::new();
+    let mut task_users = HashMap::new();
+    let mut ingested = 0;
     for user in users {
-        let app = app.clone();
-        let user_cloned = user.clone();
-        
-        if let Err(e) = grab_old_jobs_thread(
-            app,
-            user_cloned
-        ).await {
-            let full_error = render_full_error(&e);
-            error!("Couldn't grab old jobs for {user}! {full_error}");
+        // Users are pulled independently, so it's safe to stop between them
+        if app.daemons.is_shutting_down() {
+            info!("Shutting down, so skipping the remaining users on {}", cluster.name);
+            break;
+        }
+        if tasks.len() >= concurrency {
+            if let Some(result) = tasks.join_next_with_id().await {
+                ingested += finished_pull(result, &mut task_users, &cluster.name);
+            }
         }
-    }
-
-    Ok(())
-}
-pub async fn old_jobs_daemon (
-    app: Arc<AppState>
-) -> ! {
-    let old_job_period = std::env::var("OLD_JOBS_DAEMON_PERIOD")
-        .unwrap_or_else(|_| DEFAULT_OLD_JOB_PERIOD.to_string())
-        .parse::<u64>()
-        .expect("Invalid `OLD_JOBS_DAEMON_PERIOD` value!");
-    info!("Old job period: {old_job_period}");
-
-    // Wait for the web server to start up
-    tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
-
-    loop {
-        info!("Pulling old jobs...");
-        if let Err(e) = grab_old_jobs_helper( app.clone() ).await {
-            error!(%e, "Failed to grab old jobs!");
-
-            tokio::time::sleep(tokio::time::Duration::from_secs(
-                old_job_period
-            )).await;
-            continue;
-        };
-        info!("Old jobs pulled!");
 
-        tokio::time::sleep(tokio::time::Duration::from_secs(
-            old_job_period
-        )).await;
+        let app = app.clone();
+        let cluster = cluster.clone();
+        let task_user = user.clone();
+        let handle = tasks.spawn(async move {
+            match grab_old_jobs_thread(
+                app,
+                cluster.clone(),
+                user.clone()
+            ).await {
+                Ok(jobs) => jobs as u64,
+                Err(e) => {
+                    let full_error = render_full_error(&e);
+                    error!("Couldn't grab old jobs for {user} on {}! {full_error}", cluster.name);
+                    0
+                }
+            }
+        }.in_current_span());
+        task_users.insert(handle.id(), task_user);
+    }
+    while let Some(result) = tasks.join_next_with_id().await {
+        ingested += finished_pull(result, &mut task_users, &cluster.name);
     }
+
+    Ok(ingested)
 }
+/// Pulls the running jobs and status of a cluster, returning
+///  how many jobs were ingested
 #[tracing::instrument]
-async fn grab_jobs_helper (
-    app: Arc<AppState>
-) -> Result<()> {
+pub async fn grab_jobs_helper (
+    app: Arc<AppState>,
+    cluster: Arc<Cluster>
+) -> Result<u64> {
     let jobstat_output: String = remote_command(
-        &app,
+        &cluster,
         "jobstat",
         vec!("-anL"),
         true
@@ -203,9 +286,7 @@ async fn grab_jobs_helper (
         .replace("in-use + ", "")
         .replace("available + ", "")
         .replace("unavailable = ", "")
-        .replace(" total", "")
-        .replace("Job status: [R]unning", "")
-        .replace("[Q]ueued", "");
+        .replace(" total", "");
     let node_stats = cluster_status_data_raw.split("\n")
         .next()
         .context("Invalid cluster status (nodes) input! Input:\n{cluster_status_data_raw:?}")?
@@ -221,15 +302,15 @@ async fn grab_jobs_helper (
         .context("Invalid cluster status (GPUs) input! Input:\n{cluster_status_data_raw:?}")?
         .split(" ")
         .collect::<Vec<&str>>();
-    
-    *app.status.write().await = Some(crate::routes::ClusterStatus {
-        total_nodes: node_stats.get(node_stats.len() - 1).context("Missing node field 3")?.parse::<u32>()?,
-        used_nodes: node_stats.get(0).context("Missing node field 0")?.parse::<u32>()?,
-        total_cpus: cpu_stats.get(cpu_stats.len() - 1).context("Missing cpu field 3")?.parse::<u32>()?,
-        used_cpus: cpu_stats.get(0).context("Missing cpu field 0")?.parse::<u32>()?,
-        total_gpus: gpu_stats.get(gpu_stats.len() - 1).context("Missing gpu field 3")?.parse::<u32>()?,
-        used_gpus: gpu_stats.get(0).context("Missing gpu field 0")?.parse::<u32>()?,
-    });
+    // The first two numbers on the `Job status: [R]unning [Q]ueued` line
+    let job_stats = cluster_status_data_raw.split("\n")
+        .find(|line| line.starts_with("Job status:"))
+        .map(|line| {
+            line.split(|c: char| !c.is_ascii_digit())
+                .filter_map(|st| st.parse::<u32>().ok())
+                .collect::<Vec<u32>>()
+        })
+        .unwrap_or_default();
 
     let job_strs: Vec<&str> = jobstat_output.split("--------------------\n")
         .nth(1)
@@ -242,10 +323,10 @@ async fn grab_jobs_helper (
             if job.starts_with("Nodes: ") {
                 return None;
             }
-            match jobstat_job_str_to_btree(job) {
+            match jobstat_job_str_to_btree(job, cluster.timezone) {
                 Ok(job) => Some(job),
                 Err(e) => {
-                    error!(%e, "Couldn't parse `jobstat` job line!");
+                    error!("Couldn't parse `jobstat` job line! {e:#}");
                     error!("Job line: {job}");
                     None
                 }
@@ -253,17 +334,39 @@ async fn grab_jobs_helper (
         })
         .collect::<Vec<BTreeMap<&str, String>>>();
 
-    for job in jobs.iter() {
-        app.db
-            .insert_job(job)
-            .await
-            .with_context(|| format!("Couldn't insert new job {job:?}!"))?;
+    // Fall back to counting the jobs themselves if PBS didn't summarize them
+    let count_jobs = |state: &str| jobs.iter()
+        .filter(|job| job.get("job_state").is_some_and(|job_state| job_state == state))
+        .count() as u32;
+    let status = crate::routes::ClusterStatus {
+        total_nodes: node_stats.last().context("Missing node field 3")?.parse::<u32>()?,
+        used_nodes: node_stats.first().context("Missing node field 0")?.parse::<u32>()?,
+        total_cpus: cpu_stats.last().context("Missing cpu field 3")?.parse::<u32>()?,
+        used_cpus: cpu_stats.first().context("Missing cpu field 0")?.parse::<u32>()?,
+        total_gpus: gpu_stats.last().context("Missing gpu field 3")?.parse::<u32>()?,
+        used_gpus: gpu_stats.first().context("Missing gpu field 0")?.parse::<u32>()?,
+        running_jobs: job_stats.first().copied().unwrap_or_else(|| count_jobs("R")),
+        queued_jobs: job_stats.get(1).copied().unwrap_or_else(|| count_jobs("Q")),
+    };
+    *cluster.status.write().await = Some(status);
+    if let Err(e) = app.db
+        .insert_cluster_status(&cluster.name, &status)
+        .await
+    {
+        let full_error = render_full_error(&e);
+        error!("Couldn't record cluster status! {full_error}");
     }
 
+    let counts = app.db
+        .insert_jobs(&cluster.name, &jobs)
+        .await
+        .context("Couldn't insert new jobs!")?;
+    info!("Ingested {} jobs from {} ({} new, {} updated)", counts.total(), cluster.name, counts.inserted, counts.updated);
+
     // Mark jobs that are no longer active as 'S' (stopped)
     info!("Marking completed jobs...");
     if let Err(e) = app.db
-        .mark_completed_jobs(&jobs)
+        .mark_completed_jobs(&cluster.name, &jobs)
         .await
         .context("Couldn't mark complete jobs!")
     {
@@ -273,33 +376,5 @@ async fn grab_jobs_helper (
         info!("Completed jobs marked successfully!");
     }
 
-    Ok(())
-}
-pub async fn jobs_daemon ( app: Arc<AppState> ) -> ! {
-    let jobstat_period = std::env::var("JOBS_DAEMON_PERIOD")
-        .unwrap_or_else(|_| DEFAULT_JOBSTAT_PERIOD.to_string())
-        .parse::<u64>()
-        .expect("Invalid `JOBS_DAEMON_PERIOD` value!");
-    info!("Jobstat period: {jobstat_period}");
-
-    // Wait for the web server to start up
-    tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
-    
-    loop {
-        info!("Pulling jobs...");
-        if let Err(e) = grab_jobs_helper( app.clone() ).await {
-            let e = render_full_error(&e);
-            error!(%e, "Failed to run remote command!");
-
-            tokio::time::sleep(tokio::time::Duration::from_secs(
-                jobstat_period
-            )).await;
-            continue;
-        };
-        info!("Jobs pulled!");
-
-        tokio::time::sleep(tokio::time::Duration::from_secs(
-            jobstat_period
-        )).await;
-    }
+    Ok(counts.total() as u64)
 }
@@ -0,0 +1,32 @@
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use tracing::info;
+
+use crate::routes::AppState;
+
+const SECONDS_PER_DAY: i64 = 60 * 60 * 24;
+
+/// Downsamples old job stats and optimizes the DB, returning how
+///  many samples and aggregates were rolled up or dropped
+#[tracing::instrument]
+pub async fn maintenance_helper ( app: Arc<AppState> ) -> Result<u64> {
+    let retention = &app.config.retention;
+    let now = chrono::Utc::now().timestamp();
+
+    let raw_cutoff = now - retention.raw_days as i64 * SECONDS_PER_DAY;
+    let hourly_cutoff = (retention.hourly_days != 0)
+        .then(|| now - retention.hourly_days as i64 * SECONDS_PER_DAY);
+    let (rolled_up, dropped) = app.db
+        .downsample_past_stats(raw_cutoff, hourly_cutoff)
+        .await
+        .context("Couldn't downsample job stats!")?;
+    info!("[ Rolled up {rolled_up} raw samples and dropped {dropped} hourly aggregates ]");
+
+    app.db
+        .optimize()
+        .await
+        .context("Couldn't optimize DB!")?;
+
+    Ok((rolled_up + dropped) as u64)
+}
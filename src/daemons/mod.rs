@@ -1,2 +1,5 @@
+pub mod backfill;
 pub mod jobs;
-pub mod groups;
\ No newline at end of file
+pub mod groups;
+pub mod maintenance;
+pub mod supervisor;
\ No newline at end of file
@@ -0,0 +1,310 @@
+use std::{sync::{atomic::{AtomicBool, Ordering}, Arc, Mutex, PoisonError}, time::Instant};
+
+use anyhow::Result;
+use tokio::{sync::{watch, Notify}, task::JoinSet};
+use tracing::{error, info, info_span, warn, Instrument};
+
+use crate::{
+    daemons::{backfill::{backfill_helper, BACKFILL_POLL_PERIOD}, groups::grab_groups_helper, jobs::{grab_jobs_helper, grab_old_jobs_helper, render_full_error}, maintenance::maintenance_helper},
+    routes::{trace::new_trace_id, AppState, Cluster}
+};
+
+/// How long to wait before restarting a daemon which panicked, in seconds
+const RESTART_DELAY: u64 = 5;
+
+/// What a daemon does each run
+#[derive(Debug, Clone)]
+pub enum DaemonKind {
+    /// Pulls running jobs with `jobstat`
+    Jobs(Arc<Cluster>),
+    /// Pulls finished jobs with `jmanl`
+    OldJobs(Arc<Cluster>),
+    /// Pulls group memberships with `getent`
+    Groups,
+    /// Downsamples job stats and optimizes the DB
+    Maintenance,
+    /// Pulls the next month of any running backfill
+    Backfill
+}
+impl DaemonKind {
+    fn name ( &self ) -> String {
+        match self {
+            Self::Jobs(cluster) => format!("Jobs ({})", cluster.name),
+            Self::OldJobs(cluster) => format!("Old Jobs ({})", cluster.name),
+            Self::Groups => String::from("Groups"),
+            Self::Maintenance => String::from("Maintenance"),
+            Self::Backfill => String::from("Backfill")
+        }
+    }
+    /// Time between each run, in seconds
+    fn period ( &self, app: &AppState ) -> u64 {
+        let daemons = &app.config.daemons;
+        match self {
+            Self::Jobs(_) => daemons.jobs_period,
+            Self::OldJobs(_) => daemons.old_jobs_period,
+            Self::Groups => daemons.groups_period,
+            Self::Maintenance => daemons.maintenance_period,
+            Self::Backfill => BACKFILL_POLL_PERIOD
+        }
+    }
+    /// Time to wait for the web server to start up, in seconds
+    fn startup_delay ( &self ) -> u64 {
+        match self {
+            // Wait even longer for jobs to be populated
+            Self::Groups => 10,
+            _ => 5
+        }
+    }
+    /// Runs the daemon once, returning how many items (i.e. jobs or
+    ///  users) it processed, and whether it should run again right away
+    async fn run ( &self, app: Arc<AppState> ) -> Result<(u64, bool)> {
+        match self {
+            Self::Jobs(cluster) => Ok((grab_jobs_helper(app, cluster.clone()).await?, false)),
+            Self::OldJobs(cluster) => Ok((grab_old_jobs_helper(app, cluster.clone()).await?, false)),
+            Self::Groups => Ok((grab_groups_helper(app).await?, false)),
+            Self::Maintenance => Ok((maintenance_helper(app).await?, false)),
+            // Go straight on to the next month, if there was one
+            Self::Backfill => Ok(backfill_helper(app).await?
+                .map(|jobs| (jobs, true))
+                .unwrap_or((0, false)))
+        }
+    }
+}
+
+/// What a daemon has been up to, for the daemons page
+#[derive(Debug, Clone, Default)]
+pub struct DaemonStatus {
+    pub running: bool,
+    /// Tags everything logged during the last run
+    pub last_run_id: Option<String>,
+    pub last_start: Option<i64>,
+    pub last_success: Option<i64>,
+    /// How long the last run took, in seconds
+    pub last_duration: Option<f64>,
+    /// How many items the last successful run processed
+    pub last_items: Option<u64>,
+    /// Why the last run failed, until a run succeeds
+    pub last_error: Option<String>,
+    /// How many times the daemon has panicked and been restarted
+    pub restarts: u32
+}
+#[derive(Debug)]
+pub struct Daemon {
+    pub kind: DaemonKind,
+    pub name: String,
+    paused: AtomicBool,
+    trigger: Notify,
+    status: Mutex<DaemonStatus>
+}
+impl Daemon {
+    fn new ( kind: DaemonKind ) -> Self {
+        Self {
+            name: kind.name(),
+            kind,
+            paused: AtomicBool::new(false),
+            trigger: Notify::new(),
+            status: Mutex::new(DaemonStatus::default())
+        }
+    }
+    pub fn status ( &self ) -> DaemonStatus {
+        self.update_status(|status| status.clone())
+    }
+    fn update_status <T> ( &self, update: impl FnOnce(&mut DaemonStatus) -> T ) -> T {
+        update(&mut self.status.lock().unwrap_or_else(PoisonError::into_inner))
+    }
+    pub fn is_paused ( &self ) -> bool {
+        self.paused.load(Ordering::Relaxed)
+    }
+    /// Stops the daemon's scheduled runs, after any current run.
+    ///  It can still be run manually.
+    pub fn pause ( &self ) {
+        self.paused.store(true, Ordering::Relaxed);
+    }
+    pub fn resume ( &self ) {
+        self.paused.store(false, Ordering::Relaxed);
+    }
+    /// Runs the daemon as soon as any current run finishes
+    pub fn run_now ( &self ) {
+        self.trigger.notify_one();
+    }
+
+    /// Runs the daemon once, recording how it went, and returns
+    ///  whether it should run again right away
+    async fn run_once ( &self, app: Arc<AppState> ) -> bool {
+        let run_id = new_trace_id();
+        let span = info_span!("daemon_run", daemon = %self.name, %run_id);
+
+        self.run_once_inner(app, run_id)
+            .instrument(span)
+            .await
+    }
+    async fn run_once_inner ( &self, app: Arc<AppState>, run_id: String ) -> bool {
+        info!("[ Running the {} daemon... ]", self.name);
+        let start = Instant::now();
+        self.update_status(|status| {
+            status.running = true;
+            status.last_run_id = Some(run_id);
+            status.last_start = Some(chrono::Utc::now().timestamp());
+        });
+
+        let result = self.kind.run(app).await;
+
+        let duration = start.elapsed().as_secs_f64();
+        self.update_status(|status| {
+            status.running = false;
+            status.last_duration = Some(duration);
+        });
+        match result {
+            Ok((items, again)) => {
+                info!("[ The {} daemon processed {items} items in {duration:.1}s ]", self.name);
+                // A healthy daemon shouldn't still show its last failure
+                self.update_status(|status| {
+                    status.last_success = Some(chrono::Utc::now().timestamp());
+                    status.last_items = Some(items);
+                    status.last_error = None;
+                });
+
+                again
+            },
+            Err(e) => {
+                let full_error = render_full_error(&e);
+                error!("The {} daemon failed! {full_error}", self.name);
+                self.update_status(|status| status.last_error = Some(full_error));
+
+                false
+            }
+        }
+    }
+}
+
+/// Runs a daemon on its period (or when triggered) until shutdown,
+///  which only stops it between runs
+async fn daemon_loop ( app: Arc<AppState>, daemon: Arc<Daemon> ) {
+    let period = daemon.kind.period(&app);
+    let mut triggered = false;
+
+    loop {
+        if app.daemons.is_shutting_down() {
+            return;
+        }
+
+        if triggered || !daemon.is_paused() {
+            triggered = false;
+            if daemon.run_once(app.clone()).await {
+                continue;
+            }
+        } else {
+            info!("[ The {} daemon is paused, skipping its run ]", daemon.name);
+        }
+
+        triggered = tokio::select! {
+            _ = tokio::time::sleep(tokio::time::Duration::from_secs(period)) => false,
+            _ = daemon.trigger.notified() => true,
+            _ = app.daemons.shutdown_requested() => return
+        };
+    }
+}
+/// Runs a daemon until shutdown, restarting it if it panics
+async fn supervise ( app: Arc<AppState>, daemon: Arc<Daemon> ) {
+    info!("[ {} period: {} ]", daemon.name, daemon.kind.period(&app));
+    tokio::select! {
+        _ = tokio::time::sleep(tokio::time::Duration::from_secs(daemon.kind.startup_delay())) => {},
+        _ = app.daemons.shutdown_requested() => return
+    }
+
+    loop {
+        // Run in its own task to catch panics, held in a set so that
+        //  it's aborted along with this task if shutdown times out
+        let mut run = JoinSet::new();
+        run.spawn(daemon_loop(app.clone(), daemon.clone()));
+        let e = match run.join_next().await {
+            Some(Err(e)) => e,
+            _ => {
+                info!("[ The {} daemon stopped ]", daemon.name);
+                return;
+            }
+        };
+
+        let message = match e.try_into_panic() {
+            Ok(panic) => panic.downcast_ref::<&str>()
+                .map(|message| message.to_string())
+                .or_else(|| panic.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| String::from("Unknown panic")),
+            Err(e) => e.to_string()
+        };
+        warn!("[ The {} daemon panicked, restarting it in {RESTART_DELAY}s! {message} ]", daemon.name);
+        daemon.update_status(|status| {
+            status.running = false;
+            status.last_error = Some(format!("Panicked: {message}"));
+            status.restarts += 1;
+        });
+        if app.daemons.is_shutting_down() {
+            return;
+        }
+
+        tokio::time::sleep(tokio::time::Duration::from_secs(RESTART_DELAY)).await;
+    }
+}
+
+/// Every daemon, in the order they're shown on the daemons page
+#[derive(Debug)]
+pub struct Supervisor {
+    daemons: Vec<Arc<Daemon>>,
+    /// Set once Hawkeye starts shutting down
+    shutdown: watch::Sender<bool>
+}
+impl Supervisor {
+    pub fn new ( clusters: &[Arc<Cluster>] ) -> Self {
+        let mut kinds = Vec::new();
+        for cluster in clusters {
+            kinds.push(DaemonKind::Jobs(cluster.clone()));
+            kinds.push(DaemonKind::OldJobs(cluster.clone()));
+        }
+        kinds.extend([DaemonKind::Groups, DaemonKind::Maintenance, DaemonKind::Backfill]);
+
+        Self {
+            daemons: kinds.into_iter()
+                .map(|kind| Arc::new(Daemon::new(kind)))
+                .collect(),
+            shutdown: watch::Sender::new(false)
+        }
+    }
+    pub fn daemons ( &self ) -> &[Arc<Daemon>] {
+        &self.daemons
+    }
+    /// A daemon by its position on the daemons page
+    pub fn daemon ( &self, id: usize ) -> Option<&Arc<Daemon>> {
+        self.daemons.get(id)
+    }
+    /// The daemon pulling a cluster's running jobs
+    pub fn jobs_daemon ( &self, cluster: &str ) -> Option<&Arc<Daemon>> {
+        self.daemons.iter()
+            .find(|daemon| matches!(&daemon.kind, DaemonKind::Jobs(jobs_cluster) if jobs_cluster.name == cluster))
+    }
+    /// Starts every daemon, each in its own supervised task, which
+    ///  finishes once the daemon has stopped for shutdown. Aborting
+    ///  a task also aborts the daemon it's running.
+    pub fn start ( app: &Arc<AppState> ) -> JoinSet<()> {
+        let mut tasks = JoinSet::new();
+        for daemon in app.daemons.daemons() {
+            tasks.spawn(supervise(app.clone(), daemon.clone()));
+        }
+
+        tasks
+    }
+    /// Asks every daemon to stop at its next safe point, i.e.
+    ///  between runs, or between users when pulling old jobs
+    pub fn shutdown ( &self ) {
+        self.shutdown.send_replace(true);
+    }
+    pub fn is_shutting_down ( &self ) -> bool {
+        *self.shutdown.borrow()
+    }
+    /// Waits until shutdown is requested
+    pub async fn shutdown_requested ( &self ) {
+        let mut shutdown = self.shutdown.subscribe();
+
+        // The sender lives as long as `self`, so this can't fail
+        let _ = shutdown.wait_for(|shutting_down| *shutting_down).await;
+    }
+}
@@ -1,31 +1,647 @@
-use std::{collections::{BTreeMap, HashMap, HashSet}, sync::Arc, time::{SystemTime, UNIX_EPOCH}};
+use std::{borrow::Borrow, collections::{BTreeMap, HashMap, HashSet}, sync::{atomic::{AtomicUsize, Ordering}, Arc, PoisonError}, time::{Instant, SystemTime, UNIX_EPOCH}};
 
-use chrono::{DateTime, Utc};
 use anyhow::{Context, Result, anyhow};
-use rusqlite::{params, params_from_iter, Connection};
-use tracing::{info, error};
+use chrono_tz::Tz;
+use rusqlite::{params, params_from_iter, Connection, OpenFlags, OptionalExtension};
+use tracing::{info, error, warn};
 use tokio::sync::Mutex;
 
-use crate::routes::AppState;
+use crate::{config::Config, parsing::{jobs::{count_array_indices, short_job_id}, units::{Duration, Memory}}, routes::{Cluster, ClusterStatus}};
 
 use super::super::remote::auth::verify_login;
 
+/// How long a connection waits on another's lock before giving up
+const BUSY_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+/// Waiting for or holding a connection longer than this is logged, so
+///  that slow pages can be tied to the daemon run holding the writer
+const SLOW_CONNECTION: std::time::Duration = std::time::Duration::from_secs(1);
+
+/// Queries run on Tokio's blocking threads, so they never hold up
+///  the async workers. Pages read through a pool of read-only
+///  connections, which (with WAL) carry on while the one writer
+///  is mid-transaction, i.e. during a daemon's ingest.
 #[derive(Debug)]
 pub struct DB {
-    conn: Mutex<Connection>,
+    writer: Arc<std::sync::Mutex<Connection>>,
+    readers: Arc<Vec<std::sync::Mutex<Connection>>>,
+    next_reader: AtomicUsize,
     groups_cache: Mutex<HashMap<String, Vec<String>>>
 }
 pub struct LoginResult {
     pub success: bool,
     pub created_new: bool
 }
+/// How many jobs a batch inserted for the first time, and
+///  how many it updated
+#[derive(Debug, Clone, Copy, Default)]
+pub struct IngestCounts {
+    pub inserted: usize,
+    pub updated: usize
+}
+impl IngestCounts {
+    pub fn total ( &self ) -> usize {
+        self.inserted + self.updated
+    }
+}
+/// Whether `insert_job` added a new job or updated a known one
+enum JobWrite {
+    Inserted,
+    Updated
+}
+/// An admin-triggered backfill of old `jmanl` records, which walks
+///  back a month at a time from `first_month` to `since`
+#[derive(Debug, Clone)]
+pub struct BackfillRun {
+    pub run_id: i64,
+    pub cluster: String,
+    /// The users to backfill, or every known user if empty
+    pub users: Vec<String>,
+    /// `YYYY-MM-DD`
+    pub since: String,
+    /// The first day of the month to pull next, if any are left
+    pub next_month: Option<String>,
+    /// `running`, `done`, or `cancelled`
+    pub state: String,
+    pub requested_by: String,
+    pub created_at: i64,
+    pub updated_at: i64,
+    pub months_done: u32,
+    pub months_total: u32,
+    pub jobs_ingested: u64,
+    /// How many `jmanl` pulls failed (i.e. for one user in one month)
+    pub errors: u64,
+    pub last_error: Option<String>
+}
+impl BackfillRun {
+    fn from_row ( row: &rusqlite::Row ) -> rusqlite::Result<Self> {
+        Ok(Self {
+            run_id: row.get("run_id")?,
+            cluster: row.get("cluster")?,
+            users: row.get::<_, String>("users")?
+                .split(',')
+                .filter(|user| !user.is_empty())
+                .map(|user| user.to_string())
+                .collect(),
+            since: row.get("since")?,
+            next_month: row.get("next_month")?,
+            state: row.get("state")?,
+            requested_by: row.get("requested_by")?,
+            created_at: row.get("created_at")?,
+            updated_at: row.get("updated_at")?,
+            months_done: row.get("months_done")?,
+            months_total: row.get("months_total")?,
+            jobs_ingested: row.get("jobs_ingested")?,
+            errors: row.get("errors")?,
+            last_error: row.get("last_error")?
+        })
+    }
+}
+
+/// Something recorded in the audit log
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuditAction {
+    Login,
+    LoginFailed,
+    Logout,
+    /// Viewed a job's stats, with its owner as the target
+    ViewJob,
+    /// Viewed a user's completed jobs
+    ViewCompleted,
+    /// Viewed a user's report card
+    ViewUser,
+    /// Viewed a job array's subjobs, with its owner as the target
+    ViewArray,
+    /// Viewed a group's allocations and usage, with it as the target
+    ViewGroup,
+    /// Searched jobs, with any user filtered on as the target
+    Search
+}
+impl AuditAction {
+    pub const ALL: [Self; 9] = [
+        Self::Login, Self::LoginFailed, Self::Logout,
+        Self::ViewJob, Self::ViewCompleted, Self::ViewUser, Self::ViewArray, Self::ViewGroup,
+        Self::Search
+    ];
+
+    pub fn as_str ( &self ) -> &'static str {
+        match self {
+            Self::Login => "login",
+            Self::LoginFailed => "login_failed",
+            Self::Logout => "logout",
+            Self::ViewJob => "view_job",
+            Self::ViewCompleted => "view_completed",
+            Self::ViewUser => "view_user",
+            Self::ViewArray => "view_array",
+            Self::ViewGroup => "view_group",
+            Self::Search => "search"
+        }
+    }
+    pub fn parse ( action: &str ) -> Option<Self> {
+        Self::ALL.into_iter()
+            .find(|known| known.as_str() == action)
+    }
+}
+/// An entry of the audit log
+#[derive(Debug, Clone)]
+pub struct AuditEntry {
+    pub entry_id: i64,
+    pub timestamp: i64,
+    /// Who did it, being the attempted username for failed logins
+    pub username: String,
+    pub action: String,
+    /// Whose data was viewed (a user, or a group), if anyone's
+    pub target: Option<String>,
+    pub detail: Option<String>
+}
+impl AuditEntry {
+    fn from_row ( row: &rusqlite::Row ) -> rusqlite::Result<Self> {
+        Ok(Self {
+            entry_id: row.get("entry_id")?,
+            timestamp: row.get("timestamp")?,
+            username: row.get("username")?,
+            action: row.get("action")?,
+            target: row.get("target")?,
+            detail: row.get("detail")?
+        })
+    }
+}
+/// Narrows down the audit log, with each filter optional
+#[derive(Debug, Clone, Default)]
+pub struct AuditFilter {
+    pub username: Option<String>,
+    pub action: Option<AuditAction>,
+    /// Inclusive lower bound, as a timestamp
+    pub since: Option<i64>,
+    /// Exclusive upper bound, as a timestamp
+    pub until: Option<i64>
+}
+
+/// Schema migrations for databases created by older versions,
+///  in order. A DB's `user_version` is the number of migrations
+///  which have already been applied to it.
+///
+/// Tables which are only ever added don't need a migration, as
+///  `DB::new` creates any which are missing.
+const MIGRATIONS: &[&str] = &[
+    // 1. Key jobs by their textual PBS ID to support job arrays
+    "CREATE TABLE Jobs_new (
+        job_id TEXT PRIMARY KEY,
+        pbs_id INTEGER NOT NULL,
+        is_array INTEGER NOT NULL DEFAULT 0,
+        array_indices TEXT,
+        array_parent TEXT,
+        array_index INTEGER,
+        name TEXT NOT NULL,
+        owner TEXT NOT NULL,
+        state TEXT NOT NULL,
+        start_time INTEGER NOT NULL,
+        queue TEXT NOT NULL,
+        nodes TEXT NOT NULL,
+        req_mem REAL NOT NULL,
+        req_cpus INTEGER NOT NULL,
+        req_gpus INTEGER NOT NULL,
+        req_walltime TEXT NOT NULL,
+        req_select TEXT NOT NULL,
+        mem_efficiency REAL NOT NULL,
+        walltime_efficiency REAL NOT NULL,
+        cpu_efficiency REAL NOT NULL,
+        used_cpu_percent REAL NOT NULL,
+        used_mem REAL NOT NULL,
+        used_walltime TEXT NOT NULL,
+        end_time INTEGER NOT NULL,
+        chunks TEXT NOT NULL,
+        exit_status TEXT NOT NULL,
+        est_start_time TEXT NOT NULL,
+        used_cpu_time TEXT NOT NULL,
+        FOREIGN KEY (owner) REFERENCES Users(name)
+    );
+    INSERT INTO Jobs_new (job_id, pbs_id, name, owner, state, start_time, queue, nodes, req_mem, req_cpus, req_gpus, req_walltime, req_select, mem_efficiency, walltime_efficiency, cpu_efficiency, used_cpu_percent, used_mem, used_walltime, end_time, chunks, exit_status, est_start_time, used_cpu_time)
+        SELECT CAST(pbs_id AS TEXT), pbs_id, name, owner, state, start_time, queue, nodes, req_mem, req_cpus, req_gpus, req_walltime, req_select, mem_efficiency, walltime_efficiency, cpu_efficiency, used_cpu_percent, used_mem, used_walltime, end_time, chunks, exit_status, est_start_time, used_cpu_time
+        FROM Jobs;
+    DROP TABLE Jobs;
+    ALTER TABLE Jobs_new RENAME TO Jobs;
+
+    CREATE TABLE PastStats_new (
+        stat_id INTEGER PRIMARY KEY AUTOINCREMENT,
+        job_id TEXT NOT NULL,
+        cpu_percent REAL NOT NULL,
+        mem REAL NOT NULL,
+        datetime STRING NOT NULL,
+        FOREIGN KEY (job_id) REFERENCES Jobs(job_id)
+    );
+    INSERT INTO PastStats_new (stat_id, job_id, cpu_percent, mem, datetime)
+        SELECT stat_id, CAST(pbs_id AS TEXT), cpu_percent, mem, datetime
+        FROM PastStats;
+    DROP TABLE PastStats;
+    ALTER TABLE PastStats_new RENAME TO PastStats;",
+
+    // 2. Scope jobs and their stats to the cluster they ran on
+    //  (existing rows are assigned to the primary cluster by `migrate`)
+    "CREATE TABLE Jobs_new (
+        cluster TEXT NOT NULL,
+        job_id TEXT NOT NULL,
+        pbs_id INTEGER NOT NULL,
+        is_array INTEGER NOT NULL DEFAULT 0,
+        array_indices TEXT,
+        array_parent TEXT,
+        array_index INTEGER,
+        name TEXT NOT NULL,
+        owner TEXT NOT NULL,
+        state TEXT NOT NULL,
+        start_time INTEGER NOT NULL,
+        queue TEXT NOT NULL,
+        nodes TEXT NOT NULL,
+        req_mem REAL NOT NULL,
+        req_cpus INTEGER NOT NULL,
+        req_gpus INTEGER NOT NULL,
+        req_walltime TEXT NOT NULL,
+        req_select TEXT NOT NULL,
+        mem_efficiency REAL NOT NULL,
+        walltime_efficiency REAL NOT NULL,
+        cpu_efficiency REAL NOT NULL,
+        used_cpu_percent REAL NOT NULL,
+        used_mem REAL NOT NULL,
+        used_walltime TEXT NOT NULL,
+        end_time INTEGER NOT NULL,
+        chunks TEXT NOT NULL,
+        exit_status TEXT NOT NULL,
+        est_start_time TEXT NOT NULL,
+        used_cpu_time TEXT NOT NULL,
+        PRIMARY KEY (cluster, job_id),
+        FOREIGN KEY (owner) REFERENCES Users(name)
+    );
+    INSERT INTO Jobs_new (cluster, job_id, pbs_id, is_array, array_indices, array_parent, array_index, name, owner, state, start_time, queue, nodes, req_mem, req_cpus, req_gpus, req_walltime, req_select, mem_efficiency, walltime_efficiency, cpu_efficiency, used_cpu_percent, used_mem, used_walltime, end_time, chunks, exit_status, est_start_time, used_cpu_time)
+        SELECT '', job_id, pbs_id, is_array, array_indices, array_parent, array_index, name, owner, state, start_time, queue, nodes, req_mem, req_cpus, req_gpus, req_walltime, req_select, mem_efficiency, walltime_efficiency, cpu_efficiency, used_cpu_percent, used_mem, used_walltime, end_time, chunks, exit_status, est_start_time, used_cpu_time
+        FROM Jobs;
+    DROP TABLE Jobs;
+    ALTER TABLE Jobs_new RENAME TO Jobs;
+
+    ALTER TABLE PastStats ADD COLUMN cluster TEXT NOT NULL DEFAULT '';",
+
+    // 3. Record more of each job's usage per sample, keyed by
+    //  an epoch timestamp rather than a formatted UTC date
+    "CREATE TABLE PastStats_new (
+        stat_id INTEGER PRIMARY KEY AUTOINCREMENT,
+        cluster TEXT NOT NULL DEFAULT '',
+        job_id TEXT NOT NULL,
+        timestamp INTEGER NOT NULL,
+        cpu_efficiency REAL NOT NULL,
+        cpupercent REAL NOT NULL DEFAULT 0,
+        cput INTEGER NOT NULL DEFAULT 0,
+        walltime INTEGER NOT NULL DEFAULT 0,
+        mem REAL NOT NULL,
+        vmem REAL NOT NULL DEFAULT 0,
+        ncpus INTEGER NOT NULL DEFAULT 0,
+        gpu_percent REAL,
+        FOREIGN KEY (cluster, job_id) REFERENCES Jobs(cluster, job_id)
+    );
+    INSERT INTO PastStats_new (stat_id, cluster, job_id, timestamp, cpu_efficiency, mem)
+        SELECT stat_id, cluster, job_id, COALESCE(CAST(strftime('%s', datetime) AS INTEGER), 0), cpu_percent, mem
+        FROM PastStats;
+    DROP TABLE PastStats;
+    ALTER TABLE PastStats_new RENAME TO PastStats;",
+
+    // 4. Checkpoint `jmanl` pulls by when they last succeeded rather
+    //  than by the newest record, which never moves for idle users
+    //  (the table may not exist yet, in which case it starts empty)
+    "CREATE TABLE IF NOT EXISTS JmanlCheckpoints (
+        cluster TEXT NOT NULL,
+        user TEXT NOT NULL,
+        newest_end_time INTEGER NOT NULL,
+        PRIMARY KEY (cluster, user)
+    );
+    CREATE TABLE JmanlCheckpoints_new (
+        cluster TEXT NOT NULL,
+        user TEXT NOT NULL,
+        last_pulled INTEGER NOT NULL,
+        PRIMARY KEY (cluster, user)
+    );
+    INSERT INTO JmanlCheckpoints_new (cluster, user, last_pulled)
+        SELECT cluster, user, newest_end_time
+        FROM JmanlCheckpoints;
+    DROP TABLE JmanlCheckpoints;
+    ALTER TABLE JmanlCheckpoints_new RENAME TO JmanlCheckpoints;",
+];
+fn migrate (
+    conn: &mut Connection,
+    primary_cluster: &str
+) -> Result<()> {
+    // A DB without a `Jobs` table is brand new, and
+    //  will be created with the latest schema
+    let is_new: bool = conn.query_row(
+        "SELECT COUNT(*) = 0 FROM sqlite_master WHERE type = 'table' AND name = 'Jobs'",
+        [],
+        |row| row.get(0)
+    )?;
+    if is_new {
+        return Ok(());
+    }
+
+    let version: usize = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+    for (i, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+        info!("[ Applying DB migration {}... ]", i + 1);
+
+        let tx = conn.transaction()?;
+        tx.execute_batch(migration)
+            .with_context(|| format!("Failed to apply migration {}!", i + 1))?;
+        tx.pragma_update(None, "user_version", i + 1)?;
+        tx.commit()?;
+    }
+
+    // Everything recorded before multi-cluster support
+    //  came from what is now the primary cluster
+    if version < 2 {
+        conn.execute("UPDATE Jobs SET cluster = ?1 WHERE cluster = ''", [primary_cluster])?;
+        conn.execute("UPDATE PastStats SET cluster = ?1 WHERE cluster = ''", [primary_cluster])?;
+    }
+
+    Ok(())
+}
+
+/// Fields which Hawkeye derives itself rather than parsing from PBS,
+///  so aren't recorded as raw attributes
+const DERIVED_ATTRIBUTES: &[&str] = &["job_id", "short_id", "pbs_id", "is_array", "array_index", "array_parent", "mem_efficiency", "walltime_efficiency", "cpu_efficiency"];
+
+/// Every column of the `Jobs` table, in the order `job_from_row` expects
+const JOB_COLUMNS: &str = "cluster, job_id, pbs_id, is_array, array_indices, array_parent, array_index, name, owner, state, start_time, queue, nodes, req_mem, req_cpus, req_gpus, req_walltime, req_select, mem_efficiency, walltime_efficiency, cpu_efficiency, used_cpu_percent, used_mem, used_walltime, end_time, chunks, exit_status, est_start_time, used_cpu_time";
+fn job_from_row ( row: &rusqlite::Row ) -> rusqlite::Result<BTreeMap<String, String>> {
+    let mut job = BTreeMap::from_iter(vec![
+        ("cluster".to_string(), row.get::<_, String>("cluster")?),
+        ("job_id".to_string(), row.get::<_, String>("job_id")?),
+        ("pbs_id".to_string(), row.get::<_, i64>("pbs_id")?.to_string()),
+        ("is_array".to_string(), row.get::<_, i32>("is_array")?.to_string()),
+        ("name".to_string(), row.get::<_, String>("name")?),
+        ("owner".to_string(), row.get::<_, String>("owner")?),
+        ("state".to_string(), row.get::<_, String>("state")?),
+        ("start_time".to_string(), row.get::<_, i32>("start_time")?.to_string()),
+        ("queue".to_string(), row.get::<_, String>("queue")?),
+        ("nodes".to_string(), row.get::<_, String>("nodes")?),
+        ("req_mem".to_string(), row.get::<_, f64>("req_mem")?.to_string()),
+        ("req_cpus".to_string(), row.get::<_, i32>("req_cpus")?.to_string()),
+        ("req_gpus".to_string(), row.get::<_, i32>("req_gpus")?.to_string()),
+        ("req_walltime".to_string(), row.get::<_, String>("req_walltime")?),
+        ("req_select".to_string(), row.get::<_, String>("req_select")?),
+        ("mem_efficiency".to_string(), row.get::<_, f64>("mem_efficiency")?.to_string()),
+        ("walltime_efficiency".to_string(), row.get::<_, f64>("walltime_efficiency")?.to_string()),
+        ("cpu_efficiency".to_string(), row.get::<_, f64>("cpu_efficiency")?.to_string()),
+        ("used_cpu_percent".to_string(), row.get::<_, f64>("used_cpu_percent")?.to_string()),
+        ("used_mem".to_string(), row.get::<_, f64>("used_mem")?.to_string()),
+        ("used_walltime".to_string(), row.get::<_, String>("used_walltime")?),
+        ("end_time".to_string(), row.get::<_, i32>("end_time")?.to_string()),
+        ("chunks".to_string(), row.get::<_, String>("chunks")?),
+        ("exit_status".to_string(), row.get::<_, String>("exit_status")?),
+        ("est_start_time".to_string(), row.get::<_, String>("est_start_time")?),
+        ("used_cpu_time".to_string(), row.get::<_, String>("used_cpu_time")?),
+    ]);
+
+    // Job IDs are stored in full, but shown without the server suffix
+    let display_id = short_job_id(&job["job_id"]).to_string();
+    job.insert("display_id".to_string(), display_id);
+
+    // Array fields only exist for array parents and subjobs
+    if let Some(array_indices) = row.get::<_, Option<String>>("array_indices")? {
+        job.insert("array_indices".to_string(), array_indices);
+    }
+    if let Some(array_parent) = row.get::<_, Option<String>>("array_parent")? {
+        job.insert("array_parent".to_string(), array_parent);
+    }
+    if let Some(array_index) = row.get::<_, Option<i64>>("array_index")? {
+        job.insert("array_index".to_string(), array_index.to_string());
+    }
+
+    Ok(job)
+}
+/// Fills in the `array_*` progress fields of any array parents in `jobs`,
+///  counting the states of the subjobs recorded for each
+fn attach_array_progress (
+    conn: &Connection,
+    jobs: &mut [BTreeMap<String, String>]
+) -> Result<()> {
+    let mut stmt = conn.prepare(
+        "SELECT
+            SUM(state = 'R'),
+            SUM(state = 'Q'),
+            SUM(state NOT IN ('R', 'Q') AND CAST(exit_status AS INTEGER) = 0),
+            SUM(state NOT IN ('R', 'Q') AND CAST(exit_status AS INTEGER) != 0)
+        FROM Jobs WHERE cluster = ?1 AND array_parent = ?2"
+    )?;
+
+    for job in jobs.iter_mut().filter(|job| job.get("is_array").is_some_and(|st| st == "1")) {
+        let cluster = job.get("cluster")
+            .context("Missing `cluster` field!")?
+            .to_owned();
+        let job_id = job.get("job_id")
+            .context("Missing `job_id` field!")?
+            .to_owned();
+        let (running, queued, done, failed) = stmt.query_row([&cluster, &job_id], |row| Ok((
+            row.get::<_, Option<u32>>(0)?.unwrap_or(0),
+            row.get::<_, Option<u32>>(1)?.unwrap_or(0),
+            row.get::<_, Option<u32>>(2)?.unwrap_or(0),
+            row.get::<_, Option<u32>>(3)?.unwrap_or(0),
+        )))?;
+
+        // Subjobs we haven't seen yet are still waiting in the queue,
+        //  unless the whole array has already finished
+        let seen = running + queued + done + failed;
+        let total = job.get("array_indices")
+            .and_then(|indices| count_array_indices(indices))
+            .unwrap_or(seen)
+            .max(seen);
+        let (queued, done) = match job.get("state").map(|st| st.as_str()) {
+            Some("Q") | Some("B") => (total - running - done - failed, done),
+            _ => (queued, total - running - queued - failed)
+        };
+
+        job.insert("array_total".to_string(), total.to_string());
+        job.insert("array_running".to_string(), running.to_string());
+        job.insert("array_queued".to_string(), queued.to_string());
+        job.insert("array_done".to_string(), done.to_string());
+        job.insert("array_failed".to_string(), failed.to_string());
+    }
+
+    Ok(())
+}
+/// Hides array subjobs from job listings, as they're aggregated
+///  into their parent's row (if we know about the parent)
+const HIDE_SUBJOBS: &str = "(array_parent IS NULL OR NOT EXISTS (SELECT 1 FROM Jobs AS parent WHERE parent.cluster = Jobs.cluster AND parent.job_id = Jobs.array_parent))";
+/// Hides array parents whose subjobs are recorded, so that usage is
+///  counted once, by the subjobs which actually ran
+const HIDE_COUNTED_PARENTS: &str = "NOT (Jobs.is_array = 1 AND EXISTS (SELECT 1 FROM Jobs AS subjob WHERE subjob.cluster = Jobs.cluster AND subjob.array_parent = Jobs.job_id))";
+
+/// Writes one job (and its attributes and, if it's running, its
+///  latest stats) as part of an `insert_jobs` transaction
+fn insert_job (
+    conn: &Connection,
+    cluster: &str,
+    job: &BTreeMap<String, String>,
+    timestamp: u64
+) -> Result<JobWrite> {
+    conn.prepare_cached("INSERT OR IGNORE INTO Users (name) VALUES (?1)")?
+        .execute([&job.get("Job_Owner").context("Missing job owner")?])
+        .context("Failed to `INSERT` user!")?;
+
+    // Fix the `Resource_List.select` field (add `nchunks=` to the beginning)
+    //  so that `1:ncpus=32:mpiprocs=32:ngpus=1:mem=50gb` becomes 
+    //  `nchunks=1:ncpus=32:mpiprocs=32:ngpus=1:mem=50gb`
+    let select = job.get("Resource_List.select").context("Missing job select")?;
+    let select = if select.starts_with("nchunks=") {
+        select.to_string()
+    } else {
+        format!("nchunks={select}")
+    };
+
+    // If there are no chunks, get it from the `Resource_List.select` field
+    let chunks = match job.get("chunks") {
+        Some(chunks) => chunks.to_string(),
+        None => {
+            select.split('=')
+                .nth(1).context("Missing chunks value in select statement (1)")?
+                .split(':')
+                .next().context("Missing chunks value in select statement (2)")?
+                .to_string()
+        }
+    };
+    
+    // Jobs recorded before full job IDs were stored are keyed by
+    //  their short ID alone, so upgrade them in place (along with
+    //  their stats and subjobs) rather than recording them twice
+    let job_id = job.get("job_id").context("Missing job ID")?;
+    if let Some(short_id) = job.get("short_id").filter(|short_id| *short_id != job_id) {
+        let upgraded = conn.prepare_cached("UPDATE Jobs SET job_id = ?1 WHERE cluster = ?2 AND job_id = ?3 AND owner = ?4")?
+            .execute(params![job_id, cluster, short_id, job.get("Job_Owner").context("Missing job owner")?])
+            .context("Failed to upgrade legacy job ID!")?;
+
+        if upgraded > 0 {
+            info!("[ Upgraded legacy job ID {short_id} to {job_id} on {cluster} ]");
+            conn.execute(
+                "UPDATE PastStats SET job_id = ?1 WHERE cluster = ?2 AND job_id = ?3",
+                params![job_id, cluster, short_id]
+            ).context("Failed to upgrade legacy job ID in stats!")?;
+            conn.execute(
+                "UPDATE JobAttributes SET job_id = ?1 WHERE cluster = ?2 AND job_id = ?3",
+                params![job_id, cluster, short_id]
+            ).context("Failed to upgrade legacy job ID in attributes!")?;
+            conn.execute(
+                "UPDATE Jobs SET array_parent = ?1 WHERE cluster = ?2 AND array_parent = ?3",
+                params![job_id, cluster, short_id]
+            ).context("Failed to upgrade legacy array parent ID!")?;
+        }
+    }
+
+    let existed = conn.prepare_cached("SELECT EXISTS (SELECT 1 FROM Jobs WHERE cluster = ?1 AND job_id = ?2)")?
+        .query_row([cluster, job_id.as_str()], |row| row.get::<_, bool>(0))
+        .context("Failed to check for existing job!")?;
+
+    // Add the job
+    conn.prepare_cached(&format!("INSERT OR REPLACE INTO Jobs ({JOB_COLUMNS}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22, ?23, ?24, ?25, ?26, ?27, ?28, ?29)"))?
+        .execute(params![
+            cluster,
+            job_id,
+            job.get("pbs_id").context("Missing job sequence number")?,
+            job.get("is_array").map(|st| st.as_str()).unwrap_or("0"),
+            job.get("array_indices_submitted"),
+            job.get("array_parent"),
+            job.get("array_index"),
+            job.get("Job_Name").context("Missing job name")?,
+            job.get("Job_Owner").context("Missing job owner")?,
+            job.get("job_state").context("Missing job state")?,
+            job.get("start_time").context("Missing job start time")?,
+            job.get("queue").context("Missing job queue")?,
+            job.get("Nodes").unwrap_or(&String::from("None")),
+            job.get("Resource_List.mem").context("Missing job memory")?,
+            job.get("Resource_List.ncpus").unwrap_or(&String::from("0")),
+            job.get("Resource_List.ngpus").unwrap_or(&String::from("0")),
+            job.get("Resource_List.walltime").context("Missing job walltime")?,
+            select,
+            job.get("mem_efficiency").context("Missing job memory efficiency")?,
+            job.get("walltime_efficiency").context("Missing job walltime efficiency")?,
+            job.get("cpu_efficiency").context("Missing job CPU efficiency")?,
+            job.get("resources_used.cpupercent").unwrap_or(&String::from("0.0")),
+            job.get("resources_used.mem").unwrap_or(&String::from("0.0")),
+            job.get("resources_used.walltime").unwrap_or(&String::from("00:00:00")),
+            job.get("end_time").unwrap_or(&i32::MAX.to_string()),
+            chunks,
+            job.get("Exit_status").unwrap_or(&String::from("Not Yet Completed")),
+            job.get("estimated.start_time").unwrap_or(&String::from("Already Started/Unknown")),
+            job.get("resources_used.cput").unwrap_or(&String::from("00:00:00")),
+        ])
+        .context("Failed to `INSERT` job!")?;
+
+    // Keep every raw attribute, updating those seen before
+    let mut stmt = conn.prepare_cached("INSERT OR REPLACE INTO JobAttributes (cluster, job_id, name, value) VALUES (?1, ?2, ?3, ?4)")?;
+    for (name, value) in job.iter()
+        .filter(|(name, _)| !DERIVED_ATTRIBUTES.contains(&name.as_str()))
+    {
+        stmt.execute(params![cluster, job_id, name, value])
+            .with_context(|| format!("Failed to `INSERT` job attribute `{name}`!"))?;
+    }
+    
+    // Add the latest stats if the job is running
+    if job.get("job_state") == Some(&String::from("R")) {
+        let duration = |field: &str| -> Result<u64> {
+            Ok(job.get(field)
+                .map(|st| st.parse::<Duration>())
+                .transpose()
+                .with_context(|| format!("Invalid `{field}` field!"))?
+                .unwrap_or_default()
+                .seconds())
+        };
+        let vmem = job.get("resources_used.vmem")
+            .map(|st| st.parse::<Memory>())
+            .transpose()
+            .context("Couldn't unpack virtual memory field!")?
+            .unwrap_or_default()
+            .gb();
+        let gpu_percent = job.get("resources_used.gpu_percent")
+            .and_then(|st| st.parse::<f64>().ok());
+        conn.prepare_cached("INSERT INTO PastStats (cluster, job_id, timestamp, cpu_efficiency, cpupercent, cput, walltime, mem, vmem, ncpus, gpu_percent) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)")?
+            .execute(params![
+                cluster,
+                job.get("job_id").context("Missing job ID")?,
+                timestamp,
+                job.get("cpu_efficiency").context("Missing job CPU efficiency")?,
+                job.get("resources_used.cpupercent").unwrap_or(&String::from("0")),
+                duration("resources_used.cput")?,
+                duration("resources_used.walltime")?,
+                job.get("resources_used.mem").context("Missing job used memory")?,
+                vmem,
+                job.get("resources_used.ncpus").unwrap_or(&String::from("0")),
+                gpu_percent
+            ])
+            .context("Failed to `INSERT` job stats!")?;
+    }
+
+    Ok(if existed { JobWrite::Updated } else { JobWrite::Inserted })
+}
+
+fn log_slow_connection ( what: &str, since: Instant ) {
+    let elapsed = since.elapsed();
+    if elapsed >= SLOW_CONNECTION {
+        let elapsed_ms = elapsed.as_secs_f64() * 1000f64;
+        warn!(elapsed_ms, "[ {what} for {elapsed_ms:.0}ms ]");
+    }
+}
 
 impl DB {
     pub fn new (
-        path: &str
+        config: &Config
     ) -> Result<Self> {
-        let conn = Connection::open(path)
-            .context("Failed to establish connection to DB!")?;
+        let mut conn = Connection::open(&config.server.db_path)
+            .with_context(|| format!("Failed to open DB at `{}`!", config.server.db_path))?;
+
+        // WAL lets the pages read while a daemon is mid-write, and the
+        //  busy timeout rides out the brief locks it still takes
+        conn.pragma_update_and_check(None, "journal_mode", "WAL", |row| row.get::<_, String>(0))
+            .context("Failed to enable WAL!")?;
+        conn.pragma_update(None, "synchronous", "NORMAL")
+            .context("Failed to set `synchronous`!")?;
+        conn.busy_timeout(BUSY_TIMEOUT)
+            .context("Failed to set busy timeout!")?;
+
+        // Bring databases created by older versions up to date
+        //  before making sure every table exists
+        migrate(&mut conn, &config.primary_cluster().name)
+            .context("Failed to migrate DB!")?;
 
         conn.execute(
             "CREATE TABLE IF NOT EXISTS Groups (
@@ -54,7 +670,13 @@ impl DB {
 
         conn.execute(
             "CREATE TABLE IF NOT EXISTS Jobs (
-                pbs_id INTEGER PRIMARY KEY AUTOINCREMENT,
+                cluster TEXT NOT NULL,
+                job_id TEXT NOT NULL,
+                pbs_id INTEGER NOT NULL,
+                is_array INTEGER NOT NULL DEFAULT 0,
+                array_indices TEXT,
+                array_parent TEXT,
+                array_index INTEGER,
                 name TEXT NOT NULL,
                 owner TEXT NOT NULL,
                 state TEXT NOT NULL,
@@ -77,111 +699,366 @@ impl DB {
                 exit_status TEXT NOT NULL,
                 est_start_time TEXT NOT NULL,
                 used_cpu_time TEXT NOT NULL,
-                FOREIGN KEY (owner) REFERENCES Users(owner)
+                PRIMARY KEY (cluster, job_id),
+                FOREIGN KEY (owner) REFERENCES Users(name)
             )",
             [],
         )?;
+        conn.execute(
+            "CREATE INDEX IF NOT EXISTS JobsArrayParent ON Jobs (cluster, array_parent)",
+            [],
+        )?;
 
         conn.execute(
             "CREATE TABLE IF NOT EXISTS PastStats (
                 stat_id INTEGER PRIMARY KEY AUTOINCREMENT,
-                pbs_id INTEGER NOT NULL,
-                cpu_percent REAL NOT NULL,
+                cluster TEXT NOT NULL DEFAULT '',
+                job_id TEXT NOT NULL,
+                timestamp INTEGER NOT NULL,
+                cpu_efficiency REAL NOT NULL,
+                cpupercent REAL NOT NULL DEFAULT 0,
+                cput INTEGER NOT NULL DEFAULT 0,
+                walltime INTEGER NOT NULL DEFAULT 0,
                 mem REAL NOT NULL,
-                datetime STRING NOT NULL,
-                FOREIGN KEY (pbs_id) REFERENCES Jobs(pbs_id)
+                vmem REAL NOT NULL DEFAULT 0,
+                ncpus INTEGER NOT NULL DEFAULT 0,
+                gpu_percent REAL,
+                FOREIGN KEY (cluster, job_id) REFERENCES Jobs(cluster, job_id)
+            )",
+            [],
+        )?;
+        conn.execute(
+            "CREATE INDEX IF NOT EXISTS PastStatsJob ON PastStats (cluster, job_id, timestamp)",
+            [],
+        )?;
+
+        // Every attribute PBS reported for each job, from any source
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS JobAttributes (
+                cluster TEXT NOT NULL,
+                job_id TEXT NOT NULL,
+                name TEXT NOT NULL,
+                value TEXT NOT NULL,
+                PRIMARY KEY (cluster, job_id, name),
+                FOREIGN KEY (cluster, job_id) REFERENCES Jobs(cluster, job_id)
+            )",
+            [],
+        )?;
+
+        // Samples older than `retention.raw_days`, rolled up by hour
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS PastStatsHourly (
+                cluster TEXT NOT NULL,
+                job_id TEXT NOT NULL,
+                hour INTEGER NOT NULL,
+                samples INTEGER NOT NULL,
+                cpu_efficiency_min REAL NOT NULL,
+                cpu_efficiency_avg REAL NOT NULL,
+                cpu_efficiency_max REAL NOT NULL,
+                cpupercent_min REAL NOT NULL,
+                cpupercent_avg REAL NOT NULL,
+                cpupercent_max REAL NOT NULL,
+                mem_min REAL NOT NULL,
+                mem_avg REAL NOT NULL,
+                mem_max REAL NOT NULL,
+                vmem_min REAL NOT NULL,
+                vmem_avg REAL NOT NULL,
+                vmem_max REAL NOT NULL,
+                gpu_percent_min REAL,
+                gpu_percent_avg REAL,
+                gpu_percent_max REAL,
+                cput INTEGER NOT NULL,
+                walltime INTEGER NOT NULL,
+                ncpus INTEGER NOT NULL,
+                PRIMARY KEY (cluster, job_id, hour),
+                FOREIGN KEY (cluster, job_id) REFERENCES Jobs(cluster, job_id)
+            )",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS ClusterStatusHistory (
+                cluster TEXT NOT NULL,
+                timestamp INTEGER NOT NULL,
+                total_nodes INTEGER NOT NULL,
+                used_nodes INTEGER NOT NULL,
+                total_cpus INTEGER NOT NULL,
+                used_cpus INTEGER NOT NULL,
+                total_gpus INTEGER NOT NULL,
+                used_gpus INTEGER NOT NULL,
+                running_jobs INTEGER NOT NULL,
+                queued_jobs INTEGER NOT NULL,
+                PRIMARY KEY (cluster, timestamp)
+            )",
+            [],
+        )?;
+
+        // When each user's `jmanl` records were last pulled,
+        //  so that later runs only fetch what's new
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS JmanlCheckpoints (
+                cluster TEXT NOT NULL,
+                user TEXT NOT NULL,
+                last_pulled INTEGER NOT NULL,
+                PRIMARY KEY (cluster, user)
+            )",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS BackfillRuns (
+                run_id INTEGER PRIMARY KEY AUTOINCREMENT,
+                cluster TEXT NOT NULL,
+                users TEXT NOT NULL,
+                since TEXT NOT NULL,
+                next_month TEXT,
+                state TEXT NOT NULL,
+                requested_by TEXT NOT NULL,
+                created_at INTEGER NOT NULL,
+                updated_at INTEGER NOT NULL,
+                months_done INTEGER NOT NULL DEFAULT 0,
+                months_total INTEGER NOT NULL,
+                jobs_ingested INTEGER NOT NULL DEFAULT 0,
+                errors INTEGER NOT NULL DEFAULT 0,
+                last_error TEXT
+            )",
+            [],
+        )?;
+
+        // Who logged in (or failed to), and whose jobs they
+        //  viewed. The triggers keep it append-only.
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS AuditLog (
+                entry_id INTEGER PRIMARY KEY AUTOINCREMENT,
+                timestamp INTEGER NOT NULL,
+                username TEXT NOT NULL,
+                action TEXT NOT NULL,
+                target TEXT,
+                detail TEXT
             )",
             [],
         )?;
+        conn.execute(
+            "CREATE INDEX IF NOT EXISTS AuditLogTimestamp ON AuditLog (timestamp)",
+            [],
+        )?;
+        conn.execute(
+            "CREATE INDEX IF NOT EXISTS AuditLogUser ON AuditLog (username, timestamp)",
+            [],
+        )?;
+        conn.execute(
+            "CREATE TRIGGER IF NOT EXISTS AuditLogNoUpdate BEFORE UPDATE ON AuditLog
+            BEGIN
+                SELECT RAISE(ABORT, 'The audit log is append-only');
+            END",
+            [],
+        )?;
+        conn.execute(
+            "CREATE TRIGGER IF NOT EXISTS AuditLogNoDelete BEFORE DELETE ON AuditLog
+            BEGIN
+                SELECT RAISE(ABORT, 'The audit log is append-only');
+            END",
+            [],
+        )?;
+
+        // Preferences chosen by each user, such as their
+        //  display timezone (an IANA name, i.e. `America/Chicago`)
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS UserSettings (
+                name TEXT PRIMARY KEY,
+                timezone TEXT
+            )",
+            [],
+        )?;
+
+        conn.pragma_update(None, "user_version", MIGRATIONS.len())
+            .context("Failed to set DB schema version!")?;
         
+        // Opened once the schema is in place, so they can't create it
+        let readers = (0..config.server.db_readers)
+            .map(|_| {
+                let reader = Connection::open_with_flags(
+                    &config.server.db_path,
+                    OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_URI | OpenFlags::SQLITE_OPEN_NO_MUTEX
+                ).with_context(|| format!("Failed to open DB reader at `{}`!", config.server.db_path))?;
+                reader.busy_timeout(BUSY_TIMEOUT)
+                    .context("Failed to set reader busy timeout!")?;
+
+                Ok(std::sync::Mutex::new(reader))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
         Ok(Self {
-            conn: Mutex::new(conn),
+            writer: Arc::new(std::sync::Mutex::new(conn)),
+            readers: Arc::new(readers),
+            next_reader: AtomicUsize::new(0),
             groups_cache: Mutex::new(HashMap::new())
         })
     }
-    pub async fn insert_job (
+    /// Runs `query` on a free reader (or, if all are busy, waits on
+    ///  the next in turn) on a blocking thread
+    async fn read <T, F> (
         &self,
-        job: &BTreeMap<&str, String>
-    ) -> Result<()> {
-        let conn = self.conn.lock().await;
+        query: F
+    ) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&Connection) -> Result<T> + Send + 'static
+    {
+        let readers = self.readers.clone();
+        let start = self.next_reader.fetch_add(1, Ordering::Relaxed) % readers.len();
+        let span = tracing::Span::current();
 
-        conn.execute(
-            "INSERT OR IGNORE INTO Users (name) VALUES (?1)",
-            [&job.get("Job_Owner").context("Missing job owner")?],
-        ).context("Failed to `INSERT` user!")?;
-
-        // Fix the `Resource_List.select` field (add `nchunks=` to the beginning)
-        //  so that `1:ncpus=32:mpiprocs=32:ngpus=1:mem=50gb` becomes 
-        //  `nchunks=1:ncpus=32:mpiprocs=32:ngpus=1:mem=50gb`
-        let select = job.get("Resource_List.select").context("Missing job select")?;
-        let select = if select.starts_with("nchunks=") {
-            select.to_string()
-        } else {
-            format!("nchunks={select}")
-        };
+        tokio::task::spawn_blocking(move || {
+            let _span = span.enter();
+            let requested = Instant::now();
+            let reader = (0..readers.len())
+                .map(|offset| &readers[(start + offset) % readers.len()])
+                .find_map(|reader| reader.try_lock().ok())
+                .unwrap_or_else(|| readers[start].lock().unwrap_or_else(PoisonError::into_inner));
+            log_slow_connection("Waited for a DB reader", requested);
+
+            let acquired = Instant::now();
+            let result = query(&reader);
+            log_slow_connection("Held a DB reader", acquired);
 
-        // If there are no chunks, get it from the `Resource_List.select` field
-        let chunks = match job.get("chunks") {
-            Some(chunks) => chunks.to_string(),
-            None => {
-                select.split('=')
-                    .nth(1).context("Missing chunks value in select statement (1)")?
-                    .split(':')
-                    .next().context("Missing chunks value in select statement (2)")?
-                    .to_string()
+            result
+        })
+            .await
+            .context("DB reader panicked!")?
+    }
+    /// Runs `query` on the writer, once it's free, on a blocking thread
+    async fn write <T, F> (
+        &self,
+        query: F
+    ) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut Connection) -> Result<T> + Send + 'static
+    {
+        let writer = self.writer.clone();
+        let span = tracing::Span::current();
+
+        tokio::task::spawn_blocking(move || {
+            let _span = span.enter();
+            let requested = Instant::now();
+            let mut writer = writer.lock().unwrap_or_else(PoisonError::into_inner);
+            log_slow_connection("Waited for the DB writer", requested);
+
+            let acquired = Instant::now();
+            let result = query(&mut writer);
+            log_slow_connection("Held the DB writer", acquired);
+
+            result
+        })
+            .await
+            .context("DB writer panicked!")?
+    }
+    /// Checks that the DB answers a query
+    pub async fn ping ( &self ) -> Result<()> {
+        self.read(|conn| {
+            conn.query_row("SELECT 1", [], |row| row.get::<_, i64>(0))
+                .context("Failed to query DB!")?;
+
+            Ok(())
+        }).await
+    }
+    /// Writes a batch of parsed jobs (i.e. one `jobstat` snapshot or
+    ///  one `jmanl` pull) in a single transaction, so that either every
+    ///  job is written or, if any can't be, none are
+    pub async fn insert_jobs <K: Borrow<str> + Ord> (
+        &self,
+        cluster: &str,
+        jobs: &[BTreeMap<K, String>]
+    ) -> Result<IngestCounts> {
+        let cluster = cluster.to_owned();
+        let jobs = jobs.iter()
+            .map(|job| {
+                job.iter()
+                    .map(|(name, value)| (name.borrow().to_string(), value.clone()))
+                    .collect::<BTreeMap<String, String>>()
+            })
+            .collect::<Vec<BTreeMap<String, String>>>();
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)
+            .context("Time went backwards")?
+            .as_secs();
+
+        self.write(move |conn| {
+            let tx = conn.transaction()?;
+            let mut counts = IngestCounts::default();
+            for job in jobs.iter() {
+                match insert_job(&tx, &cluster, job, timestamp)
+                    .with_context(|| format!("Couldn't insert job {:?}!", job.get("job_id")))?
+                {
+                    JobWrite::Inserted => counts.inserted += 1,
+                    JobWrite::Updated => counts.updated += 1
+                }
             }
-        };
-        
-        // Add the job
-        conn.execute(
-            "INSERT OR REPLACE INTO Jobs (pbs_id, name, owner, state, start_time, queue, nodes, req_mem, req_cpus, req_gpus, req_walltime, req_select, mem_efficiency, walltime_efficiency, cpu_efficiency, used_cpu_percent, used_mem, used_walltime, end_time, chunks, exit_status, est_start_time, used_cpu_time) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22, ?23)",
-            params![
-                job.get("job_id").context("Missing job ID")?,
-                job.get("Job_Name").context("Missing job name")?,
-                job.get("Job_Owner").context("Missing job owner")?,
-                job.get("job_state").context("Missing job state")?,
-                job.get("start_time").context("Missing job start time")?,
-                job.get("queue").context("Missing job queue")?,
-                job.get("Nodes").unwrap_or(&String::from("None")),
-                job.get("Resource_List.mem").context("Missing job memory")?,
-                job.get("Resource_List.ncpus").unwrap_or(&String::from("0")),
-                job.get("Resource_List.ngpus").unwrap_or(&String::from("0")),
-                job.get("Resource_List.walltime").context("Missing job walltime")?,
-                select,
-                job.get("mem_efficiency").context("Missing job memory efficiency")?,
-                job.get("walltime_efficiency").context("Missing job walltime efficiency")?,
-                job.get("cpu_efficiency").context("Missing job CPU efficiency")?,
-                job.get("resources_used.cpupercent").unwrap_or(&String::from("0.0")),
-                job.get("resources_used.mem").unwrap_or(&String::from("0.0")),
-                job.get("resources_used.walltime").unwrap_or(&String::from("00:00:00")),
-                job.get("end_time").unwrap_or(&i32::MAX.to_string()),
-                chunks,
-                job.get("Exit_status").unwrap_or(&String::from("Not Yet Completed")),
-                job.get("estimated.start_time").unwrap_or(&String::from("Already Started/Unknown")),
-                job.get("resources_used.cput").unwrap_or(&String::from("00:00:00")),
-            ],
-        ).context("Failed to `INSERT` job!")?;
-        
-        // Add the latest stats if the job is running
-        if job.get("job_state") == Some(&String::from("R")) {
-            // Get the current system time
-            let now = SystemTime::now();
-            let duration_since_epoch = now.duration_since(UNIX_EPOCH)
-                .context("Time went backwards")?;
-            let datetime = DateTime::<Utc>::from(UNIX_EPOCH + duration_since_epoch);
-            let formatted_datetime = datetime.format("%Y-%m-%d %H:%M:%S").to_string();
+            tx.commit()
+                .context("Failed to commit jobs!")?;
+
+            Ok(counts)
+        }).await
+    }
+
+    /// Records a snapshot of a cluster's status, taken now
+    pub async fn insert_cluster_status (
+        &self,
+        cluster: &str,
+        status: &ClusterStatus
+    ) -> Result<()> {
+        let cluster = cluster.to_owned();
+        let status = *status;
+        self.write(move |conn| {
+            let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)
+                .context("Time went backwards")?
+                .as_secs();
             conn.execute(
-                "INSERT INTO PastStats (pbs_id, cpu_percent, mem, datetime) VALUES (?1, ?2, ?3, ?4)",
+                "INSERT OR REPLACE INTO ClusterStatusHistory (cluster, timestamp, total_nodes, used_nodes, total_cpus, used_cpus, total_gpus, used_gpus, running_jobs, queued_jobs) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
                 params![
-                    job.get("job_id").context("Missing job ID")?,
-                    job.get("cpu_efficiency").context("Missing job CPU efficiency")?,
-                    job.get("resources_used.mem").context("Missing job used memory")?,
-                    formatted_datetime
+                    cluster,
+                    timestamp,
+                    status.total_nodes,
+                    status.used_nodes,
+                    status.total_cpus,
+                    status.used_cpus,
+                    status.total_gpus,
+                    status.used_gpus,
+                    status.running_jobs,
+                    status.queued_jobs
                 ],
-            ).context("Failed to `INSERT` job stats!")?;
-        }
+            ).context("Failed to `INSERT` cluster status!")?;
+
+            Ok(())
+        }).await
+    }
+    /// Gets a cluster's status snapshots since `since`, oldest first
+    pub async fn get_cluster_status_history (
+        &self,
+        cluster: &str,
+        since: i64
+    ) -> Result<Vec<(i64, ClusterStatus)>> {
+        let cluster = cluster.to_owned();
+        self.read(move |conn| {
+            let mut stmt = conn.prepare("SELECT timestamp, total_nodes, used_nodes, total_cpus, used_cpus, total_gpus, used_gpus, running_jobs, queued_jobs FROM ClusterStatusHistory WHERE cluster = ?1 AND timestamp >= ?2 ORDER BY timestamp")?;
+            let rows = stmt.query_map(params![cluster, since], |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    ClusterStatus {
+                        total_nodes: row.get(1)?,
+                        used_nodes: row.get(2)?,
+                        total_cpus: row.get(3)?,
+                        used_cpus: row.get(4)?,
+                        total_gpus: row.get(5)?,
+                        used_gpus: row.get(6)?,
+                        running_jobs: row.get(7)?,
+                        queued_jobs: row.get(8)?
+                    }
+                ))
+            }).context("Failed to get rows!")?;
 
-        Ok(())
+            Ok(rows.flatten().collect())
+        }).await
     }
 
     pub async fn get_groups_cache (
@@ -189,43 +1066,55 @@ impl DB {
     ) -> HashMap<String, Vec<String>> {
         self.groups_cache.lock().await.clone()
     }
-    pub async fn insert_user_groups (
+    /// Replaces the groups of each user in `memberships`, adding new
+    ///  memberships and removing those which no longer exist, and
+    ///  returns how many were added and removed
+    pub async fn set_user_groups (
         &self,
-        user: &str,
-        groups: Vec<&str>
-    ) -> Result<()> {
-        self.groups_cache.lock().await.insert(
-            user.to_string(),
-            groups.iter()
-                .map(|&st| st.to_string())
-                .collect()
-        );
+        memberships: &HashMap<String, Vec<String>>
+    ) -> Result<(usize, usize)> {
+        let owned_memberships = memberships.clone();
+        let (added, removed) = self.write(move |conn| {
+            let tx = conn.transaction()?;
+            let (mut added, mut removed) = (0, 0);
+            for (user, groups) in owned_memberships.iter() {
+                let current = {
+                    let mut stmt = tx.prepare_cached("SELECT group_name FROM UserGroups WHERE user_name = ?1")?;
+                    let rows = stmt.query_map([user], |row| row.get::<_, String>(0))?;
+                    rows.collect::<rusqlite::Result<HashSet<String>>>()?
+                };
 
-        for group in groups {
-            self.insert_user_group(&user, group)
-                .await
-                .with_context(|| format!("Couldn't insert user {user} into group {group}!"))?;
-        }
+                for group in current.iter().filter(|group| !groups.contains(group)) {
+                    removed += tx.prepare_cached("DELETE FROM UserGroups WHERE user_name = ?1 AND group_name = ?2")?
+                        .execute([user, group])
+                        .with_context(|| format!("Couldn't remove user {user} from group {group}!"))?;
+                }
+                for group in groups.iter().filter(|group| !current.contains(*group)) {
+                    tx.prepare_cached("INSERT OR IGNORE INTO Groups (name) VALUES (?1)")?
+                        .execute([group])?;
+                    added += tx.prepare_cached("INSERT OR IGNORE INTO UserGroups (user_name, group_name) VALUES (?1, ?2)")?
+                        .execute([user, group])
+                        .with_context(|| format!("Couldn't insert user {user} into group {group}!"))?;
+                }
+            }
 
-        Ok(())
-    }
-    pub async fn insert_user_group (
-        &self,
-        user: &str,
-        group: &str,
-    ) -> Result<()> {
-        let conn = self.conn.lock().await;
+            // Forget groups which no longer have any known members
+            tx.execute(
+                "DELETE FROM Groups WHERE name NOT IN (SELECT group_name FROM UserGroups)",
+                []
+            ).context("Couldn't remove empty groups!")?;
+            tx.commit()?;
 
-        conn.execute(
-            "INSERT OR IGNORE INTO Groups (name) VALUES (?1)",
-            [group],
-        )?;
-        conn.execute(
-            "INSERT OR IGNORE INTO UserGroups (user_name, group_name) VALUES (?1, ?2)",
-            [user, group],
-        )?;
+            Ok((added, removed))
+        }).await?;
 
-        Ok(())
+        // Only update the cache once the DB agrees with it
+        self.groups_cache.lock().await.extend(
+            memberships.iter()
+                .map(|(user, groups)| (user.clone(), groups.clone()))
+        );
+
+        Ok((added, removed))
     }
     
     /// Update all jobs in state 'R' that are *not* in the current list of active jobs.
@@ -233,47 +1122,46 @@ impl DB {
     #[tracing::instrument]
     pub async fn mark_completed_jobs(
         &self,
+        cluster: &str,
         active_jobs: &[BTreeMap<&str, String>],
     ) -> Result<()> {
-        let conn = self.conn.lock().await;
+        let cluster = cluster.to_owned();
 
         // Build a set of IDs for *currently active* jobs
-        let active_ids: HashSet<_> = active_jobs
+        let active_ids: HashSet<String> = active_jobs
             .iter()
-            .flat_map(|job| Some(job.get("job_id")?.parse::<i32>().ok()?))
+            .flat_map(|job| job.get("job_id").cloned())
             .collect();
-        
-        // Find all jobs that are in state 'R' or 'Q' in our local DB
-        let mut stmt = conn.prepare("SELECT pbs_id FROM Jobs WHERE state = 'R' OR state = 'Q'")?;
-        let rows = stmt.query_map([], |row| row.get::<_, i32>(0))?;
-    
-        // For each job in state 'R', check if it's still active
-        for row_result in rows {
-            let pbs_id = row_result?;
-            // If a job's ID is *not* in the active set, we assume it completed
-            if !active_ids.contains(&pbs_id) {
-                info!("[ Marking job {} as completed... ]", pbs_id);
-
-                conn.execute(
-                    "UPDATE Jobs SET state = 'E' WHERE pbs_id = ?1",
-                    [pbs_id],
-                )?;
-
-                let now = SystemTime::now();
-                let secs_since_epoch = now.duration_since(UNIX_EPOCH)
-                    .context("Time went backwards")?
-                    .as_secs();
-
-                conn.execute(
-                    "UPDATE Jobs SET end_time = ?1 WHERE pbs_id = ?2",
-                    [secs_since_epoch.to_string(), pbs_id.to_string()],
-                )?;
+
+        self.write(move |conn| {
+            // Find all jobs that are running, queued, or (for arrays) begun in our local DB
+            let mut stmt = conn.prepare("SELECT job_id FROM Jobs WHERE cluster = ?1 AND state IN ('R', 'Q', 'B')")?;
+            let rows = stmt.query_map([&cluster], |row| row.get::<_, String>(0))?
+                .collect::<rusqlite::Result<Vec<String>>>()?;
+
+            // For each of those jobs, check if it's still active
+            for job_id in rows {
+                // If a job's ID is *not* in the active set, we assume it completed
+                if !active_ids.contains(job_id.as_str()) {
+                    info!("[ Marking job {} as completed... ]", job_id);
+
+                    let now = SystemTime::now();
+                    let secs_since_epoch = now.duration_since(UNIX_EPOCH)
+                        .context("Time went backwards")?
+                        .as_secs();
+
+                    conn.execute(
+                        "UPDATE Jobs SET state = 'E', end_time = ?1 WHERE cluster = ?2 AND job_id = ?3",
+                        params![secs_since_epoch, cluster, job_id],
+                    )?;
+                }
             }
-        }
-    
-        Ok(())
+
+            Ok(())
+        }).await
     }
 
+    #[allow(clippy::too_many_arguments)]
     #[tracing::instrument]
     pub async fn get_user_jobs(
         &self,
@@ -282,68 +1170,55 @@ impl DB {
         filter_queue: Option<&String>,
         filter_owner: Option<&String>,
         filter_name: Option<&String>,
+        filter_cluster: Option<&String>,
         filter_date: Option<&String>
     ) -> Result<Vec<BTreeMap<String, String>>> {
-        let conn = self.conn.lock().await;
+        let username = username.to_owned();
+        let (filter_state, filter_queue, filter_owner) = (filter_state.cloned(), filter_queue.cloned(), filter_owner.cloned());
+        let (filter_name, filter_cluster, filter_date) = (filter_name.cloned(), filter_cluster.cloned(), filter_date.cloned());
+        self.read(move |conn| {
+            let mut additional_filters= String::new();
+            let mut params = vec![username.to_string()];
+            if let Some(filter_state) = filter_state {
+                additional_filters.push_str(" AND state = ?2");
+                params.push(filter_state.to_owned());
+            }
+            if let Some(filter_queue) = filter_queue {
+                additional_filters.push_str(&format!(" AND queue = ?{}", params.len() + 1));
+                params.push(filter_queue.to_owned());
+            }
+            if let Some(filter_owner) = filter_owner {
+                additional_filters.push_str(&format!(" AND owner = ?{}", params.len() + 1));
+                params.push(filter_owner.to_owned());
+            }
+            if let Some(filter_name) = filter_name {
+                additional_filters.push_str(&format!(" AND name = ?{}", params.len() + 1));
+                params.push(filter_name.to_owned());
+            }
+            if let Some(filter_cluster) = filter_cluster {
+                additional_filters.push_str(&format!(" AND cluster = ?{}", params.len() + 1));
+                params.push(filter_cluster.to_owned());
+            }
+            // Make sure that the job is before or on the specified date,
+            //  OR has not completed (state = R).
+            if let Some(filter_date) = filter_date {
+                info!("Filtering by date: {filter_date}");
+                additional_filters.push_str(&format!(" AND start_time >= ?{}", params.len() + 1));
+                params.push(filter_date.to_owned());
+            }
 
-        let mut additional_filters= String::new();
-        let mut params = vec![username.to_string()];
-        if let Some(filter_state) = filter_state {
-            additional_filters.push_str(" AND state = ?2");
-            params.push(filter_state.to_owned());
-        }
-        if let Some(filter_queue) = filter_queue {
-            additional_filters.push_str(&format!(" AND queue = ?{}", params.len() + 1));
-            params.push(filter_queue.to_owned());
-        }
-        if let Some(filter_owner) = filter_owner {
-            additional_filters.push_str(&format!(" AND owner = ?{}", params.len() + 1));
-            params.push(filter_owner.to_owned());
-        }
-        if let Some(filter_name) = filter_name {
-            additional_filters.push_str(&format!(" AND name = ?{}", params.len() + 1));
-            params.push(filter_name.to_owned());
-        }
-        // Make sure that the job is before or on the specified date,
-        //  OR has not completed (state = R).
-        if let Some(filter_date) = filter_date {
-            info!("Filtering by date: {filter_date}");
-            additional_filters.push_str(&format!(" AND start_time >= ?{}", params.len() + 1));
-            params.push(filter_date.to_owned());
-        }
+            let mut stmt = conn.prepare(&format!("SELECT {JOB_COLUMNS} FROM Jobs WHERE owner = ?1 AND {HIDE_SUBJOBS}{}", additional_filters))?;
+            let rows = stmt.query_map(params_from_iter(params), job_from_row).context("Failed to get rows!")?;
+            let mut jobs: Vec<BTreeMap<String, String>> = rows.flatten().collect();
 
-        let mut stmt = conn.prepare(&format!("SELECT * FROM Jobs WHERE owner = ?1{}", additional_filters))?;
-        let rows = stmt.query_map(params_from_iter(params), |row| {
-            Ok(BTreeMap::from_iter(vec![
-                ("pbs_id".to_string(), row.get::<_, i32>(0)?.to_string()),
-                ("name".to_string(), row.get::<_, String>(1)?),
-                ("owner".to_string(), row.get::<_, String>(2)?),
-                ("state".to_string(), row.get::<_, String>(3)?),
-                ("start_time".to_string(), row.get::<_, i32>(4)?.to_string()),
-                ("queue".to_string(), row.get::<_, String>(5)?),
-                ("nodes".to_string(), row.get::<_, String>(6)?),
-                ("req_mem".to_string(), row.get::<_, f64>(7)?.to_string()),
-                ("req_cpus".to_string(), row.get::<_, i32>(8)?.to_string()),
-                ("req_gpus".to_string(), row.get::<_, i32>(9)?.to_string()),
-                ("req_walltime".to_string(), row.get::<_, String>(10)?),
-                ("req_select".to_string(), row.get::<_, String>(11)?),
-                ("mem_efficiency".to_string(), row.get::<_, f64>(12)?.to_string()),
-                ("walltime_efficiency".to_string(), row.get::<_, f64>(13)?.to_string()),
-                ("cpu_efficiency".to_string(), row.get::<_, f64>(14)?.to_string()),
-                ("used_cpu_percent".to_string(), row.get::<_, f64>(15)?.to_string()),
-                ("used_mem".to_string(), row.get::<_, f64>(16)?.to_string()),
-                ("used_walltime".to_string(), row.get::<_, String>(17)?),
-                ("end_time".to_string(), row.get::<_, i32>(18)?.to_string()),
-                ("chunks".to_string(), row.get::<_, String>(19)?),
-                ("exit_status".to_string(), row.get::<_, String>(20)?),
-                ("est_start_time".to_string(), row.get::<_, String>(21)?),
-                ("used_cpu_time".to_string(), row.get::<_, String>(22)?),
-            ]))
-        }).context("Failed to get rows!")?;
-    
-        Ok(rows.flatten().collect())
+            attach_array_progress(conn, &mut jobs)
+                .context("Failed to get array progress!")?;
+
+            Ok(jobs)
+        }).await
     }
 
+    #[allow(clippy::too_many_arguments)]
     #[tracing::instrument]
     pub async fn get_all_jobs (
         &self,
@@ -352,114 +1227,102 @@ impl DB {
         filter_owner:  Option<&String>,
         filter_name:   Option<&String>,
         filter_group:  Option<&String>,
+        filter_cluster: Option<&String>,
         filter_date:   Option<&String>
     ) -> Result<Vec<BTreeMap<String, String>>> {
-        let conn = self.conn.lock().await;
-
-        let mut additional_filters= String::new();
-        let mut params = vec![];
-        if let Some(filter_state) = filter_states {
-            if filter_state.len() == 0 {
-                additional_filters.push_str("state = ?1");
-                params.push(filter_state[0]);
-            } else {
-                additional_filters.push_str("state IN (");
-                for (i, state) in filter_state.iter().enumerate() {
-                    additional_filters.push_str(&format!("?{},", i + 1));
-                    params.push(state);
+        let filter_states = filter_states.map(|states| states.into_iter().map(String::from).collect::<Vec<String>>());
+        let (filter_queue, filter_owner, filter_name) = (filter_queue.cloned(), filter_owner.cloned(), filter_name.cloned());
+        let (filter_group, filter_cluster, filter_date) = (filter_group.cloned(), filter_cluster.cloned(), filter_date.cloned());
+        self.read(move |conn| {
+            let mut additional_filters= String::new();
+            let mut params = vec![];
+            if let Some(filter_state) = filter_states {
+                if filter_state.is_empty() {
+                    additional_filters.push_str("state = ?1");
+                    params.push(filter_state[0].clone());
+                } else {
+                    additional_filters.push_str("state IN (");
+                    for (i, state) in filter_state.iter().enumerate() {
+                        additional_filters.push_str(&format!("?{},", i + 1));
+                        params.push(state.clone());
+                    }
+                    additional_filters.pop();
+                    additional_filters.push(')');
                 }
-                additional_filters.pop();
-                additional_filters.push(')');
             }
-        }
-        if let Some(filter_queue) = filter_queue {
-            if !additional_filters.is_empty() {
-                additional_filters.push_str(" AND ");
+            if let Some(filter_queue) = filter_queue {
+                if !additional_filters.is_empty() {
+                    additional_filters.push_str(" AND ");
+                }
+                additional_filters.push_str(&format!("queue = ?{}", params.len() + 1));
+                params.push(filter_queue);
             }
-            additional_filters.push_str(&format!("queue = ?{}", params.len() + 1));
-            params.push(filter_queue);
-        }
-        if let Some(filter_owner) = filter_owner {
-            if !additional_filters.is_empty() {
-                additional_filters.push_str(" AND ");
+            if let Some(filter_owner) = filter_owner {
+                if !additional_filters.is_empty() {
+                    additional_filters.push_str(" AND ");
+                }
+                additional_filters.push_str(&format!("owner = ?{}", params.len() + 1));
+                params.push(filter_owner);
             }
-            additional_filters.push_str(&format!("owner = ?{}", params.len() + 1));
-            params.push(filter_owner);
-        }
-        if let Some(filter_name) = filter_name {
-            if !additional_filters.is_empty() {
-                additional_filters.push_str(" AND ");
+            if let Some(filter_name) = filter_name {
+                if !additional_filters.is_empty() {
+                    additional_filters.push_str(" AND ");
+                }
+                additional_filters.push_str(&format!("name = ?{}", params.len() + 1));
+                params.push(filter_name);
             }
-            additional_filters.push_str(&format!("name = ?{}", params.len() + 1));
-            params.push(filter_name);
-        }
-        if let Some(filter_group) = filter_group {
-            if !additional_filters.is_empty() {
-                additional_filters.push_str(" AND ");
+            if let Some(filter_group) = filter_group {
+                if !additional_filters.is_empty() {
+                    additional_filters.push_str(" AND ");
+                }
+                info!("Filtering by group: {filter_group}");
+                additional_filters.push_str(&format!("owner IN (SELECT user_name FROM UserGroups WHERE group_name = ?{})", params.len() + 1));
+                params.push(filter_group);
+            }
+            if let Some(filter_cluster) = filter_cluster {
+                if !additional_filters.is_empty() {
+                    additional_filters.push_str(" AND ");
+                }
+                additional_filters.push_str(&format!("cluster = ?{}", params.len() + 1));
+                params.push(filter_cluster);
+            }
+
+            // Make sure that the job is before or on the specified date,
+            //  OR has not completed (state = R).
+            if let Some(filter_date) = filter_date {
+                if !additional_filters.is_empty() {
+                    additional_filters.push_str(" AND ");
+                }
+                info!("Filtering by date: {filter_date}");
+                additional_filters.push_str(&format!("(start_time >= ?{})", params.len() + 1));
+                params.push(filter_date);
             }
-            info!("Filtering by group: {filter_group}");
-            additional_filters.push_str(&format!("owner IN (SELECT user_name FROM UserGroups WHERE group_name = ?{})", params.len() + 1));
-            params.push(filter_group);
-        }
 
-        // Make sure that the job is before or on the specified date,
-        //  OR has not completed (state = R).
-        if let Some(filter_date) = filter_date {
+            // Array subjobs are always shown as part of their parent
             if !additional_filters.is_empty() {
                 additional_filters.push_str(" AND ");
             }
-            info!("Filtering by date: {filter_date}");
-            additional_filters.push_str(&format!("(start_time >= ?{})", params.len() + 1));
-            params.push(filter_date);
-        }
+            additional_filters.push_str(HIDE_SUBJOBS);
 
-        // If there were any filters, add the 'WHERE' keyword
-        if !additional_filters.is_empty() {
-            additional_filters = format!(" WHERE {}", additional_filters);
-        }
+            let final_query = format!("SELECT {JOB_COLUMNS} FROM Jobs WHERE {}", additional_filters);
 
-        let final_query = format!("SELECT * FROM Jobs{}", additional_filters);
-
-        //  ORDER BY start_time DESC
-        let mut stmt = conn.prepare(&final_query)?;
-        let rows = stmt.query_map(params_from_iter(params), |row| {
-            Ok(BTreeMap::from_iter(vec![
-                ("pbs_id".to_string(), row.get::<_, i32>(0)?.to_string()),
-                ("name".to_string(), row.get::<_, String>(1)?),
-                ("owner".to_string(), row.get::<_, String>(2)?),
-                ("state".to_string(), row.get::<_, String>(3)?),
-                ("start_time".to_string(), row.get::<_, i32>(4)?.to_string()),
-                ("queue".to_string(), row.get::<_, String>(5)?),
-                ("nodes".to_string(), row.get::<_, String>(6)?),
-                ("req_mem".to_string(), row.get::<_, f64>(7)?.to_string()),
-                ("req_cpus".to_string(), row.get::<_, i32>(8)?.to_string()),
-                ("req_gpus".to_string(), row.get::<_, i32>(9)?.to_string()),
-                ("req_walltime".to_string(), row.get::<_, String>(10)?),
-                ("req_select".to_string(), row.get::<_, String>(11)?),
-                ("mem_efficiency".to_string(), row.get::<_, f64>(12)?.to_string()),
-                ("walltime_efficiency".to_string(), row.get::<_, f64>(13)?.to_string()),
-                ("cpu_efficiency".to_string(), row.get::<_, f64>(14)?.to_string()),
-                ("used_cpu_percent".to_string(), row.get::<_, f64>(15)?.to_string()),
-                ("used_mem".to_string(), row.get::<_, f64>(16)?.to_string()),
-                ("used_walltime".to_string(), row.get::<_, String>(17)?),
-                ("end_time".to_string(), row.get::<_, i32>(18)?.to_string()),
-                ("chunks".to_string(), row.get::<_, String>(19)?),
-                ("exit_status".to_string(), row.get::<_, String>(20)?),
-                ("est_start_time".to_string(), row.get::<_, String>(21)?),
-                ("used_cpu_time".to_string(), row.get::<_, String>(22)?),
-            ]))
-        });
-
-        match rows {
-            Ok(rows) => {
-                let rows = rows.flatten().collect();
-                Ok(rows)
-            },
-            Err(e) => {
-                error!(%e, "Failed to get rows!");
-                Err(anyhow!("Failed to get rows! Error: {e:?}"))
+            //  ORDER BY start_time DESC
+            let mut stmt = conn.prepare(&final_query)?;
+            let rows = stmt.query_map(params_from_iter(params), job_from_row);
+
+            match rows {
+                Ok(rows) => {
+                    let mut rows: Vec<BTreeMap<String, String>> = rows.flatten().collect();
+                    attach_array_progress(conn, &mut rows)
+                        .context("Failed to get array progress!")?;
+                    Ok(rows)
+                },
+                Err(e) => {
+                    error!(%e, "Failed to get rows!");
+                    Err(anyhow!("Failed to get rows! Error: {e:?}"))
+                }
             }
-        }
+        }).await
     }
 
     #[tracing::instrument]
@@ -472,185 +1335,576 @@ impl DB {
         filter_date: Option<&String>,
         group: &str
     ) -> Result<Vec<BTreeMap<String, String>>> {
-        let conn = self.conn.lock().await;
-        
-        let mut additional_filters= String::new();
-        let mut params: Vec<String> = vec![group.to_string()];
-        if let Some(filter_state) = filter_state {
-            additional_filters.push_str(" AND state = ?2");
-            params.push(filter_state.to_owned());
-        }
-        if let Some(filter_queue) = filter_queue {
-            additional_filters.push_str(&format!(" AND queue = ?{}", params.len() + 1));
-            params.push(filter_queue.to_owned());
-        }
-        if let Some(filter_owner) = filter_owner {
-            additional_filters.push_str(&format!(" AND owner = ?{}", params.len() + 1));
-            params.push(filter_owner.to_owned());
-        }
-        if let Some(filter_name) = filter_name {
-            additional_filters.push_str(&format!(" AND name = ?{}", params.len() + 1));
-            params.push(filter_name.to_owned());
-        }
-        // Make sure that the job is before or on the specified date,
-        //  OR has not completed (state = R).
-        if let Some(filter_date) = filter_date {
-            info!("Filtering by date: {filter_date}");
-            additional_filters.push_str(&format!(" AND start_time >= ?{}", params.len() + 1));
-            params.push(filter_date.to_owned());
-        }
+        let (filter_state, filter_queue, filter_owner) = (filter_state.cloned(), filter_queue.cloned(), filter_owner.cloned());
+        let (filter_name, filter_date, group) = (filter_name.cloned(), filter_date.cloned(), group.to_owned());
+        self.read(move |conn| {
+            let mut additional_filters= String::new();
+            let mut params: Vec<String> = vec![group.to_string()];
+            if let Some(filter_state) = filter_state {
+                additional_filters.push_str(" AND state = ?2");
+                params.push(filter_state.to_owned());
+            }
+            if let Some(filter_queue) = filter_queue {
+                additional_filters.push_str(&format!(" AND queue = ?{}", params.len() + 1));
+                params.push(filter_queue.to_owned());
+            }
+            if let Some(filter_owner) = filter_owner {
+                additional_filters.push_str(&format!(" AND owner = ?{}", params.len() + 1));
+                params.push(filter_owner.to_owned());
+            }
+            if let Some(filter_name) = filter_name {
+                additional_filters.push_str(&format!(" AND name = ?{}", params.len() + 1));
+                params.push(filter_name.to_owned());
+            }
+            // Make sure that the job is before or on the specified date,
+            //  OR has not completed (state = R).
+            if let Some(filter_date) = filter_date {
+                info!("Filtering by date: {filter_date}");
+                additional_filters.push_str(&format!(" AND start_time >= ?{}", params.len() + 1));
+                params.push(filter_date.to_owned());
+            }
 
-        info!("Filtering by group: {group}");
-        info!("Additional filters: '{additional_filters}'");
-        info!("Params: {params:?}");
-
-        let mut stmt = conn.prepare(&format!("SELECT * FROM Jobs WHERE owner IN (SELECT user_name FROM UserGroups WHERE group_name = ?1){}", additional_filters))?;
-        let rows = stmt.query_map(params_from_iter(params), |row| {
-            Ok(BTreeMap::from_iter(vec![
-                ("pbs_id".to_string(), row.get::<_, i32>(0)?.to_string()),
-                ("name".to_string(), row.get::<_, String>(1)?),
-                ("owner".to_string(), row.get::<_, String>(2)?),
-                ("state".to_string(), row.get::<_, String>(3)?),
-                ("start_time".to_string(), row.get::<_, i32>(4)?.to_string()),
-                ("queue".to_string(), row.get::<_, String>(5)?),
-                ("nodes".to_string(), row.get::<_, String>(6)?),
-                ("req_mem".to_string(), row.get::<_, f64>(7)?.to_string()),
-                ("req_cpus".to_string(), row.get::<_, i32>(8)?.to_string()),
-                ("req_gpus".to_string(), row.get::<_, i32>(9)?.to_string()),
-                ("req_walltime".to_string(), row.get::<_, String>(10)?),
-                ("req_select".to_string(), row.get::<_, String>(11)?),
-                ("mem_efficiency".to_string(), row.get::<_, f64>(12)?.to_string()),
-                ("walltime_efficiency".to_string(), row.get::<_, f64>(13)?.to_string()),
-                ("cpu_efficiency".to_string(), row.get::<_, f64>(14)?.to_string()),
-                ("used_cpu_percent".to_string(), row.get::<_, f64>(15)?.to_string()),
-                ("used_mem".to_string(), row.get::<_, f64>(16)?.to_string()),
-                ("used_walltime".to_string(), row.get::<_, String>(17)?),
-                ("end_time".to_string(), row.get::<_, i32>(18)?.to_string()),
-                ("chunks".to_string(), row.get::<_, String>(19)?),
-                ("exit_status".to_string(), row.get::<_, String>(20)?),
-                ("est_start_time".to_string(), row.get::<_, String>(21)?),
-                ("used_cpu_time".to_string(), row.get::<_, String>(22)?),
-            ]))
-        });
-    
-        match rows {
-            Ok(rows) => {
-                let ret: Vec<BTreeMap<String, String>> = rows.flatten().collect();
-                info!("Returning {} rows!", ret.len());
-                Ok(ret)
-            },
-            Err(e) => {
-                error!(%e, "Failed to get rows!");
-                Err(anyhow!("Failed to get rows! Error: {e:?}"))
+            info!("Filtering by group: {group}");
+            info!("Additional filters: '{additional_filters}'");
+            info!("Params: {params:?}");
+
+            let mut stmt = conn.prepare(&format!("SELECT {JOB_COLUMNS} FROM Jobs WHERE owner IN (SELECT user_name FROM UserGroups WHERE group_name = ?1){}", additional_filters))?;
+            let rows = stmt.query_map(params_from_iter(params), job_from_row);
+
+            match rows {
+                Ok(rows) => {
+                    let ret: Vec<BTreeMap<String, String>> = rows.flatten().collect();
+                    info!("Returning {} rows!", ret.len());
+                    Ok(ret)
+                },
+                Err(e) => {
+                    error!(%e, "Failed to get rows!");
+                    Err(anyhow!("Failed to get rows! Error: {e:?}"))
+                }
             }
-        }
+        }).await
+    }
+
+    /// A user's completed jobs which started since `since`, for their
+    ///  report card. Arrays are counted by their subjobs (like in
+    ///  `get_accounting_jobs`), as their parents don't record usage.
+    pub async fn get_user_report_jobs (
+        &self,
+        username: &str,
+        since: i64,
+        filter_cluster: Option<&String>
+    ) -> Result<Vec<BTreeMap<String, String>>> {
+        let (username, filter_cluster) = (username.to_owned(), filter_cluster.cloned());
+        self.read(move |conn| {
+            let mut params = vec![username, since.to_string()];
+            let mut additional_filters = String::new();
+            if let Some(filter_cluster) = filter_cluster {
+                additional_filters.push_str(" AND cluster = ?3");
+                params.push(filter_cluster);
+            }
+
+            let mut stmt = conn.prepare(&format!(
+                "SELECT {JOB_COLUMNS} FROM Jobs WHERE owner = ?1 AND state = 'E' AND start_time >= ?2 AND {HIDE_COUNTED_PARENTS}{additional_filters}"
+            ))?;
+            let rows = stmt.query_map(params_from_iter(params), job_from_row)
+                .context("Failed to get rows!")?;
+
+            Ok(rows.flatten().collect())
+        }).await
+    }
+    /// Gets every completed job which ended since `since`, with one row per
+    ///  group of the job's owner (`group_name` is empty for owners without
+    ///  groups). Arrays are counted by their subjobs, where recorded.
+    #[tracing::instrument]
+    pub async fn get_accounting_jobs (
+        &self,
+        since: i64,
+        filter_cluster: Option<&String>
+    ) -> Result<Vec<BTreeMap<String, String>>> {
+        let filter_cluster = filter_cluster.cloned();
+        self.read(move |conn| {
+            let mut params = vec![since.to_string()];
+            let mut additional_filters = String::new();
+            if let Some(filter_cluster) = filter_cluster {
+                additional_filters.push_str(" AND Jobs.cluster = ?2");
+                params.push(filter_cluster.to_owned());
+            }
+
+            let mut stmt = conn.prepare(&format!(
+                "SELECT Jobs.cluster, Jobs.job_id, Jobs.owner, Jobs.queue, Jobs.req_cpus, Jobs.req_gpus, Jobs.req_mem, Jobs.used_walltime, Jobs.end_time, UserGroups.group_name
+                    FROM Jobs LEFT JOIN UserGroups ON UserGroups.user_name = Jobs.owner
+                    WHERE Jobs.state = 'E' AND Jobs.end_time >= ?1 AND {HIDE_COUNTED_PARENTS}{additional_filters}"
+            ))?;
+            let rows = stmt.query_map(params_from_iter(params), |row| {
+                Ok(BTreeMap::from_iter(vec![
+                    ("cluster".to_string(), row.get::<_, String>(0)?),
+                    ("job_id".to_string(), row.get::<_, String>(1)?),
+                    ("owner".to_string(), row.get::<_, String>(2)?),
+                    ("queue".to_string(), row.get::<_, String>(3)?),
+                    ("req_cpus".to_string(), row.get::<_, i64>(4)?.to_string()),
+                    ("req_gpus".to_string(), row.get::<_, i64>(5)?.to_string()),
+                    ("req_mem".to_string(), row.get::<_, f64>(6)?.to_string()),
+                    ("used_walltime".to_string(), row.get::<_, String>(7)?),
+                    ("end_time".to_string(), row.get::<_, i64>(8)?.to_string()),
+                    ("group_name".to_string(), row.get::<_, Option<String>>(9)?.unwrap_or_default()),
+                ]))
+            }).context("Failed to get rows!")?;
+
+            Ok(rows.flatten().collect())
+        }).await
     }
 
     pub async fn get_job (
         &self,
-        pbs_id: i32,
+        cluster: &str,
+        job_id: &str,
     ) -> Result<BTreeMap<String, String>> {
-        let conn = self.conn.lock().await;
-
-        let mut stmt = conn.prepare("SELECT * FROM Jobs WHERE pbs_id = ?1")?;
-        let row = stmt.query_row([pbs_id], |row| {
-            Ok(BTreeMap::from_iter(vec![
-                ("pbs_id".to_string(), row.get::<_, i32>(0)?.to_string()),
-                ("name".to_string(), row.get::<_, String>(1)?),
-                ("owner".to_string(), row.get::<_, String>(2)?),
-                ("state".to_string(), row.get::<_, String>(3)?),
-                ("start_time".to_string(), row.get::<_, i32>(4)?.to_string()),
-                ("queue".to_string(), row.get::<_, String>(5)?),
-                ("nodes".to_string(), row.get::<_, String>(6)?),
-                ("req_mem".to_string(), row.get::<_, f64>(7)?.to_string()),
-                ("req_cpus".to_string(), row.get::<_, i32>(8)?.to_string()),
-                ("req_gpus".to_string(), row.get::<_, i32>(9)?.to_string()),
-                ("req_walltime".to_string(), row.get::<_, String>(10)?),
-                ("req_select".to_string(), row.get::<_, String>(11)?),
-                ("mem_efficiency".to_string(), row.get::<_, f64>(12)?.to_string()),
-                ("walltime_efficiency".to_string(), row.get::<_, f64>(13)?.to_string()),
-                ("cpu_efficiency".to_string(), row.get::<_, f64>(14)?.to_string()),
-                ("used_cpu_percent".to_string(), row.get::<_, f64>(15)?.to_string()),
-                ("used_mem".to_string(), row.get::<_, f64>(16)?.to_string()),
-                ("used_walltime".to_string(), row.get::<_, String>(17)?),
-                ("end_time".to_string(), row.get::<_, i32>(18)?.to_string()),
-                ("chunks".to_string(), row.get::<_, String>(19)?),
-                ("exit_status".to_string(), row.get::<_, String>(20)?),
-                ("est_start_time".to_string(), row.get::<_, String>(21)?),
-                ("used_cpu_time".to_string(), row.get::<_, String>(22)?),
-            ]))
-        }).context("Failed to get row!")?;
-    
-        Ok(row)
+        let (cluster, job_id) = (cluster.to_owned(), job_id.to_owned());
+        self.read(move |conn| {
+            let mut stmt = conn.prepare(&format!("SELECT {JOB_COLUMNS} FROM Jobs WHERE cluster = ?1 AND job_id = ?2"))?;
+            let row = stmt.query_row([&cluster, &job_id], job_from_row)
+                .context("Failed to get row!")?;
+            let mut jobs = [row];
+            attach_array_progress(conn, &mut jobs)
+                .context("Failed to get array progress!")?;
+            let [row] = jobs;
+
+            Ok(row)
+        }).await
+    }
+
+    /// Gets every raw PBS attribute recorded for a job, by name
+    pub async fn get_job_attributes (
+        &self,
+        cluster: &str,
+        job_id: &str,
+    ) -> Result<Vec<(String, String)>> {
+        let (cluster, job_id) = (cluster.to_owned(), job_id.to_owned());
+        self.read(move |conn| {
+            let mut stmt = conn.prepare("SELECT name, value FROM JobAttributes WHERE cluster = ?1 AND job_id = ?2 ORDER BY name")?;
+            let rows = stmt.query_map([&cluster, &job_id], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+            }).context("Failed to get rows!")?;
+
+            Ok(rows.flatten().collect())
+        }).await
     }
 
+    /// Gets every subjob recorded for the job array `job_id`
+    pub async fn get_array_subjobs (
+        &self,
+        cluster: &str,
+        job_id: &str,
+    ) -> Result<Vec<BTreeMap<String, String>>> {
+        let (cluster, job_id) = (cluster.to_owned(), job_id.to_owned());
+        self.read(move |conn| {
+            let mut stmt = conn.prepare(&format!("SELECT {JOB_COLUMNS} FROM Jobs WHERE cluster = ?1 AND array_parent = ?2 ORDER BY array_index"))?;
+            let rows = stmt.query_map([&cluster, &job_id], job_from_row)
+                .context("Failed to get rows!")?;
+
+            Ok(rows.flatten().collect())
+        }).await
+    }
+
+    /// Gets a job's samples, oldest first. Samples which have been
+    ///  rolled up come first, with their hourly averages (and the
+    ///  `_min` and `_max` of each) and a `resolution` of `hourly`.
     pub async fn get_job_stats (
         &self,
-        pbs_id: i32,
+        cluster: &str,
+        job_id: &str,
     ) -> Result<Vec<BTreeMap<String, String>>> {
-        let conn = self.conn.lock().await;
-        
+        let (cluster, job_id) = (cluster.to_owned(), job_id.to_owned());
+        self.read(move |conn| {
+            let mut stmt = conn.prepare("SELECT hour, samples, cpu_efficiency_min, cpu_efficiency_avg, cpu_efficiency_max, cpupercent_avg, cput, walltime, mem_min, mem_avg, mem_max, vmem_avg, ncpus, gpu_percent_avg FROM PastStatsHourly WHERE cluster = ?1 AND job_id = ?2 ORDER BY hour")?;
+            let hourly_rows = stmt.query_map([&cluster, &job_id], |row| {
+                Ok(BTreeMap::from_iter(vec![
+                    ("job_id".to_string(), job_id.to_string()),
+                    ("resolution".to_string(), "hourly".to_string()),
+                    ("timestamp".to_string(), row.get::<_, i64>(0)?.to_string()),
+                    ("samples".to_string(), row.get::<_, i64>(1)?.to_string()),
+                    ("cpu_efficiency_min".to_string(), row.get::<_, f64>(2)?.to_string()),
+                    ("cpu_efficiency".to_string(), row.get::<_, f64>(3)?.to_string()),
+                    ("cpu_efficiency_max".to_string(), row.get::<_, f64>(4)?.to_string()),
+                    ("cpupercent".to_string(), row.get::<_, f64>(5)?.to_string()),
+                    ("cput".to_string(), row.get::<_, i64>(6)?.to_string()),
+                    ("walltime".to_string(), row.get::<_, i64>(7)?.to_string()),
+                    ("mem_min".to_string(), row.get::<_, f64>(8)?.to_string()),
+                    ("mem".to_string(), row.get::<_, f64>(9)?.to_string()),
+                    ("mem_max".to_string(), row.get::<_, f64>(10)?.to_string()),
+                    ("vmem".to_string(), row.get::<_, f64>(11)?.to_string()),
+                    ("ncpus".to_string(), row.get::<_, i64>(12)?.to_string()),
+                    ("gpu_percent".to_string(), row.get::<_, Option<f64>>(13)?.map(|gpu_percent| gpu_percent.to_string()).unwrap_or_default()),
+                ]))
+            }).context("Failed to get hourly rows!")?;
+            let mut stats: Vec<BTreeMap<String, String>> = hourly_rows.flatten().collect();
 
-        let mut stmt = conn.prepare("SELECT * FROM PastStats WHERE pbs_id = ?1")?;
-        let rows = stmt.query_map([pbs_id], |row| {
-            Ok(BTreeMap::from_iter(vec![
-                ("stat_id".to_string(), row.get::<_, i32>(0)?.to_string()),
-                ("pbs_id".to_string(), row.get::<_, i32>(1)?.to_string()),
-                ("cpu_percent".to_string(), row.get::<_, f64>(2)?.to_string()),
-                ("mem".to_string(), row.get::<_, f64>(3)?.to_string()),
-                ("datetime".to_string(), row.get::<_, String>(4)?),
-            ]))
-        }).context("Failed to get rows!")?;
-    
-        Ok(rows.flatten().collect())
+            let mut stmt = conn.prepare("SELECT stat_id, job_id, timestamp, cpu_efficiency, cpupercent, cput, walltime, mem, vmem, ncpus, gpu_percent FROM PastStats WHERE cluster = ?1 AND job_id = ?2 ORDER BY timestamp")?;
+            let rows = stmt.query_map([&cluster, &job_id], |row| {
+                Ok(BTreeMap::from_iter(vec![
+                    ("stat_id".to_string(), row.get::<_, i64>(0)?.to_string()),
+                    ("job_id".to_string(), row.get::<_, String>(1)?),
+                    ("resolution".to_string(), "raw".to_string()),
+                    ("timestamp".to_string(), row.get::<_, i64>(2)?.to_string()),
+                    ("cpu_efficiency".to_string(), row.get::<_, f64>(3)?.to_string()),
+                    ("cpupercent".to_string(), row.get::<_, f64>(4)?.to_string()),
+                    ("cput".to_string(), row.get::<_, i64>(5)?.to_string()),
+                    ("walltime".to_string(), row.get::<_, i64>(6)?.to_string()),
+                    ("mem".to_string(), row.get::<_, f64>(7)?.to_string()),
+                    ("vmem".to_string(), row.get::<_, f64>(8)?.to_string()),
+                    ("ncpus".to_string(), row.get::<_, i64>(9)?.to_string()),
+                    ("gpu_percent".to_string(), row.get::<_, Option<f64>>(10)?.map(|gpu_percent| gpu_percent.to_string()).unwrap_or_default()),
+                ]))
+            }).context("Failed to get rows!")?;
+            stats.extend(rows.flatten());
+
+            Ok(stats)
+        }).await
+    }
+    /// Rolls raw samples from before `raw_cutoff` up into hourly
+    ///  aggregates, then drops them, along with any aggregates from
+    ///  before `hourly_cutoff`.
+    ///
+    /// Returns the number of raw samples rolled up, and the number
+    ///  of hourly aggregates dropped.
+    pub async fn downsample_past_stats (
+        &self,
+        raw_cutoff: i64,
+        hourly_cutoff: Option<i64>
+    ) -> Result<(usize, usize)> {
+        self.write(move |conn| {
+            // Only roll up whole hours, so that no hour is split
+            //  between raw samples and its aggregate
+            let raw_cutoff = raw_cutoff - raw_cutoff.rem_euclid(3600);
+
+            let tx = conn.transaction()?;
+            tx.execute(
+                "INSERT OR REPLACE INTO PastStatsHourly (cluster, job_id, hour, samples, cpu_efficiency_min, cpu_efficiency_avg, cpu_efficiency_max, cpupercent_min, cpupercent_avg, cpupercent_max, mem_min, mem_avg, mem_max, vmem_min, vmem_avg, vmem_max, gpu_percent_min, gpu_percent_avg, gpu_percent_max, cput, walltime, ncpus)
+                    SELECT cluster, job_id, (timestamp / 3600) * 3600 AS hour, COUNT(*),
+                        MIN(cpu_efficiency), AVG(cpu_efficiency), MAX(cpu_efficiency),
+                        MIN(cpupercent), AVG(cpupercent), MAX(cpupercent),
+                        MIN(mem), AVG(mem), MAX(mem),
+                        MIN(vmem), AVG(vmem), MAX(vmem),
+                        MIN(gpu_percent), AVG(gpu_percent), MAX(gpu_percent),
+                        MAX(cput), MAX(walltime), MAX(ncpus)
+                    FROM PastStats
+                    WHERE timestamp < ?1
+                    GROUP BY cluster, job_id, hour",
+                [raw_cutoff]
+            ).context("Failed to roll up job stats!")?;
+            let rolled_up = tx.execute(
+                "DELETE FROM PastStats WHERE timestamp < ?1",
+                [raw_cutoff]
+            ).context("Failed to drop rolled up job stats!")?;
+            let dropped = match hourly_cutoff {
+                Some(hourly_cutoff) => tx.execute(
+                    "DELETE FROM PastStatsHourly WHERE hour < ?1",
+                    [hourly_cutoff]
+                ).context("Failed to drop expired hourly job stats!")?,
+                None => 0
+            };
+            tx.commit()?;
+
+            Ok((rolled_up, dropped))
+        }).await
+    }
+    /// Refreshes the query planner's statistics and reclaims free pages
+    pub async fn optimize ( &self ) -> Result<()> {
+        self.write(move |conn| {
+            conn.execute_batch("ANALYZE; VACUUM;")
+                .context("Failed to `ANALYZE` and `VACUUM` DB!")?;
+
+            Ok(())
+        }).await
+    }
+
+    /// When a user's `jmanl` records were last pulled, if ever
+    pub async fn get_jmanl_checkpoint (
+        &self,
+        cluster: &str,
+        user: &str
+    ) -> Result<Option<i64>> {
+        let (cluster, user) = (cluster.to_owned(), user.to_owned());
+        self.read(move |conn| {
+            conn.query_row(
+                "SELECT last_pulled FROM JmanlCheckpoints WHERE cluster = ?1 AND user = ?2",
+                [cluster, user],
+                |row| row.get::<_, i64>(0)
+            )
+                .optional()
+                .context("Failed to get `jmanl` checkpoint!")
+        }).await
+    }
+    /// Records when a user's `jmanl` records were last pulled,
+    ///  never moving it backwards
+    pub async fn set_jmanl_checkpoint (
+        &self,
+        cluster: &str,
+        user: &str,
+        last_pulled: i64
+    ) -> Result<()> {
+        let (cluster, user) = (cluster.to_owned(), user.to_owned());
+        self.write(move |conn| {
+            conn.execute(
+                "INSERT INTO JmanlCheckpoints (cluster, user, last_pulled) VALUES (?1, ?2, ?3)
+                    ON CONFLICT(cluster, user) DO UPDATE SET last_pulled = MAX(last_pulled, excluded.last_pulled)",
+                params![cluster, user, last_pulled]
+            )
+                .context("Failed to set `jmanl` checkpoint!")?;
+
+            Ok(())
+        }).await
+    }
+
+    /// Queues a backfill, which the backfill daemon starts on its next check
+    pub async fn create_backfill_run (
+        &self,
+        cluster: &str,
+        users: &[String],
+        since: &str,
+        first_month: &str,
+        months_total: u32,
+        requested_by: &str
+    ) -> Result<i64> {
+        let (cluster, users, since) = (cluster.to_owned(), users.join(","), since.to_owned());
+        let (first_month, requested_by) = (first_month.to_owned(), requested_by.to_owned());
+        self.write(move |conn| {
+            let now = chrono::Utc::now().timestamp();
+
+            conn.execute(
+                "INSERT INTO BackfillRuns (cluster, users, since, next_month, state, requested_by, created_at, updated_at, months_total)
+                    VALUES (?1, ?2, ?3, ?4, 'running', ?5, ?6, ?6, ?7)",
+                params![cluster, users, since, first_month, requested_by, now, months_total]
+            )
+                .context("Failed to create backfill run!")?;
+
+            Ok(conn.last_insert_rowid())
+        }).await
+    }
+    /// The most recent backfills, newest first
+    pub async fn get_backfill_runs (
+        &self,
+        limit: u32
+    ) -> Result<Vec<BackfillRun>> {
+        self.read(move |conn| {
+            let mut stmt = conn.prepare("SELECT * FROM BackfillRuns ORDER BY run_id DESC LIMIT ?1")?;
+            let rows = stmt.query_map([limit], BackfillRun::from_row)
+                .context("Failed to get backfill runs!")?;
+
+            Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
+        }).await
+    }
+    /// The oldest backfill which is still running, if any
+    pub async fn get_running_backfill (
+        &self
+    ) -> Result<Option<BackfillRun>> {
+        self.read(move |conn| {
+            conn.query_row(
+                "SELECT * FROM BackfillRuns WHERE state = 'running' ORDER BY run_id LIMIT 1",
+                [],
+                BackfillRun::from_row
+            )
+                .optional()
+                .context("Failed to get running backfill!")
+        }).await
+    }
+    /// Records a month of a running backfill as pulled, moving on to
+    ///  `next_month`, or finishing the backfill if there's none
+    pub async fn record_backfill_month (
+        &self,
+        run_id: i64,
+        next_month: Option<&str>,
+        jobs_ingested: u64,
+        errors: u64,
+        last_error: Option<&str>
+    ) -> Result<()> {
+        let (next_month, last_error) = (next_month.map(String::from), last_error.map(String::from));
+        self.write(move |conn| {
+            // Cancelled backfills stay cancelled
+            conn.execute(
+                "UPDATE BackfillRuns SET
+                    next_month = ?2,
+                    state = CASE WHEN ?2 IS NULL THEN 'done' ELSE state END,
+                    months_done = months_done + 1,
+                    jobs_ingested = jobs_ingested + ?3,
+                    errors = errors + ?4,
+                    last_error = COALESCE(?5, last_error),
+                    updated_at = ?6
+                WHERE run_id = ?1 AND state = 'running'",
+                params![run_id, next_month, jobs_ingested, errors, last_error, chrono::Utc::now().timestamp()]
+            )
+                .context("Failed to record backfill progress!")?;
+
+            Ok(())
+        }).await
+    }
+    /// Cancels a running backfill, returning whether there was one
+    pub async fn cancel_backfill_run (
+        &self,
+        run_id: i64
+    ) -> Result<bool> {
+        self.write(move |conn| {
+            let cancelled = conn.execute(
+                "UPDATE BackfillRuns SET state = 'cancelled', updated_at = ?2 WHERE run_id = ?1 AND state = 'running'",
+                params![run_id, chrono::Utc::now().timestamp()]
+            )
+                .context("Failed to cancel backfill run!")?;
+
+            Ok(cancelled > 0)
+        }).await
+    }
+    /// Appends an entry to the audit log
+    pub async fn insert_audit_entry (
+        &self,
+        username: &str,
+        action: AuditAction,
+        target: Option<&str>,
+        detail: Option<&str>
+    ) -> Result<()> {
+        let (username, target, detail) = (username.to_owned(), target.map(String::from), detail.map(String::from));
+        self.write(move |conn| {
+            conn.execute(
+                "INSERT INTO AuditLog (timestamp, username, action, target, detail) VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![chrono::Utc::now().timestamp(), username, action.as_str(), target, detail]
+            )
+                .context("Failed to insert audit log entry!")?;
+
+            Ok(())
+        }).await
+    }
+    /// Audit log entries matching `filter`, newest first, and
+    ///  only the newest `limit` if given
+    pub async fn get_audit_entries (
+        &self,
+        filter: &AuditFilter,
+        limit: Option<u32>
+    ) -> Result<Vec<AuditEntry>> {
+        let filter = filter.clone();
+        self.read(move |conn| {
+            let mut stmt = conn.prepare(
+                "SELECT * FROM AuditLog
+                WHERE (?1 IS NULL OR username = ?1)
+                    AND (?2 IS NULL OR action = ?2)
+                    AND (?3 IS NULL OR timestamp >= ?3)
+                    AND (?4 IS NULL OR timestamp < ?4)
+                ORDER BY entry_id DESC
+                LIMIT ?5"
+            )?;
+            let rows = stmt.query_map(
+                params![
+                    filter.username,
+                    filter.action.map(|action| action.as_str()),
+                    filter.since,
+                    filter.until,
+                    limit.map(i64::from).unwrap_or(-1)
+                ],
+                AuditEntry::from_row
+            )
+                .context("Failed to get audit log entries!")?;
+
+            Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
+        }).await
+    }
+    /// Every user known to Hawkeye, including those who haven't
+    ///  logged in but have had jobs seen by `jobstat`
+    pub async fn get_known_users (
+        &self
+    ) -> Result<Vec<String>> {
+        self.read(move |conn| {
+            let mut stmt = conn.prepare("SELECT name FROM Users UNION SELECT DISTINCT owner FROM Jobs ORDER BY 1")?;
+            let rows = stmt.query_map([], |row| row.get::<_, String>(0))
+                .context("Failed to get known users!")?;
+
+            Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
+        }).await
+    }
+
+    /// The display timezone a user has chosen, if any
+    pub async fn get_user_timezone (
+        &self,
+        username: &str
+    ) -> Result<Option<Tz>> {
+        let username = username.to_owned();
+        self.read(move |conn| {
+            let timezone = conn.query_row(
+                "SELECT timezone FROM UserSettings WHERE name = ?1",
+                [username],
+                |row| row.get::<_, Option<String>>(0)
+            )
+                .optional()
+                .context("Failed to get user timezone!")?
+                .flatten();
+
+            Ok(timezone.and_then(|timezone| timezone.parse::<Tz>().ok()))
+        }).await
+    }
+    /// Sets the display timezone of a user, or clears it
+    ///  (to use the site's) if `None`
+    pub async fn set_user_timezone (
+        &self,
+        username: &str,
+        timezone: Option<Tz>
+    ) -> Result<()> {
+        let username = username.to_owned();
+        self.write(move |conn| {
+            conn.execute(
+                "INSERT INTO UserSettings (name, timezone) VALUES (?1, ?2)
+                    ON CONFLICT(name) DO UPDATE SET timezone = excluded.timezone",
+                params![username, timezone.map(|timezone| timezone.name())]
+            )
+                .context("Failed to set user timezone!")?;
+
+            Ok(())
+        }).await
     }
 
     pub async fn get_users (
         &self,
     ) -> Result<Vec<String>> {
-        let conn = self.conn.lock().await;
-        
+        self.read(move |conn| {
+            let mut stmt = conn.prepare("SELECT name FROM Users")?;
+            let rows = stmt.query_map([], |row| row.get::<_, String>(0))?;
 
-        let mut stmt = conn.prepare("SELECT name FROM Users")?;
-        let rows = stmt.query_map([], |row| row.get::<_, String>(0))?;
-    
-        Ok(rows.flatten().collect())
+            Ok(rows.flatten().collect())
+        }).await
     }
     
     pub async fn _get_user_groups (
         &self,
         username: &str,
     ) -> Result<Vec<String>> {
-        let conn = self.conn.lock().await;
-        
+        let username = username.to_owned();
+        self.read(move |conn| {
+            let mut stmt = conn.prepare("SELECT group_name FROM UserGroups WHERE user_name = ?1")?;
+            let rows = stmt.query_map([username], |row| row.get::<_, String>(0))?;
 
-        let mut stmt = conn.prepare("SELECT group_name FROM UserGroups WHERE user_name = ?1")?;
-        let rows = stmt.query_map([username], |row| row.get::<_, String>(0))?;
-    
-        Ok(rows.flatten().collect())
+            Ok(rows.flatten().collect())
+        }).await
     }
 
     pub async fn _is_user_able_to_view_stats (
         &self,
         user: &str,
-        pbs_id: i32,
+        admin_group: &str,
+        job_id: &str,
     ) -> Result<bool> {
-        let conn = self.conn.lock().await;
-
-        // Firstly, if the user is in the `hpc` group,
+        // Firstly, if the user is in the admin group,
         //  they are allowed to view advanced stats for
         //  any job.
-        if self._is_user_admin(user).await? {
+        if self._is_user_admin(user, admin_group).await? {
             return Ok(true);
         }
 
         // Note that a user is also allowed to view advanced 
         //  stats if the job was created by another user in
         //  the same group as the current user.
-        let mut stmt = conn.prepare("SELECT COUNT(*) FROM Jobs WHERE pbs_id = ?1 AND (owner = ?2 OR owner IN (SELECT user_name FROM UserGroups WHERE group_name IN (SELECT group_name FROM UserGroups WHERE user_name = ?2)))")?;
-        let count: i32 = stmt.query_row([pbs_id.to_string(), user.to_string()], |row| row.get(0))?;
+        let (user, job_id) = (user.to_owned(), job_id.to_owned());
+        self.read(move |conn| {
+            let mut stmt = conn.prepare("SELECT COUNT(*) FROM Jobs WHERE job_id = ?1 AND (owner = ?2 OR owner IN (SELECT user_name FROM UserGroups WHERE group_name IN (SELECT group_name FROM UserGroups WHERE user_name = ?2)))")?;
+            let count: i32 = stmt.query_row([&job_id, &user], |row| row.get(0))?;
 
-        Ok(count > 0)
+            Ok(count > 0)
+        }).await
     }
 
     pub async fn _is_user_in_group (
@@ -658,55 +1912,53 @@ impl DB {
         user: &str,
         group: &str,
     ) -> Result<bool> {
-        let conn = self.conn.lock().await;
+        let (user, group) = (user.to_owned(), group.to_owned());
+        self.read(move |conn| {
+            let mut stmt = conn.prepare("SELECT COUNT(*) FROM UserGroups WHERE user_name = ?1 AND group_name = ?2")?;
+            let count: i32 = stmt.query_row([user, group], |row| row.get(0))?;
 
-        let mut stmt = conn.prepare("SELECT COUNT(*) FROM UserGroups WHERE user_name = ?1 AND group_name = ?2")?;
-        let count: i32 = stmt.query_row([user, group], |row| row.get(0))?;
-    
-        Ok(count > 0)
+            Ok(count > 0)
+        }).await
     }
 
     pub async fn _is_user_admin (
         &self,
         user: &str,
+        admin_group: &str,
     ) -> Result<bool> {
-        self._is_user_in_group(user, "hpc").await
+        self._is_user_in_group(user, admin_group).await
     }
 
     pub async fn login (
         &self,
-        state:    &Arc<AppState>,
+        cluster:  &Cluster,
         username: &str,
         password: &str
     ) -> Result<LoginResult> {
-        let conn = self.conn.lock().await;
-
         match verify_login(
-            state,
-            &username,
-            &password
+            cluster,
+            username,
+            password
         )
             .await
             .context("Failed to verify login!")?
         {
             true => {
-                // Check if the user exists in the DB
-                let mut stmt = conn.prepare("SELECT COUNT(*) FROM Users WHERE name = ?1")?;
-                let count: i32 = stmt.query_row([username], |row| row.get(0))?;
-                drop(stmt);
-
                 // If the user doesn't exist, add them and
                 //  populate their groups
-                if count == 0 {
-                    conn.execute(
-                        "INSERT INTO Users (name) VALUES (?1)",
-                        [username],
+                let username = username.to_owned();
+                let created_new = self.write(move |conn| {
+                    let added = conn.execute(
+                        "INSERT OR IGNORE INTO Users (name) VALUES (?1)",
+                        [&username],
                     )?;
-                }
+
+                    Ok(added > 0)
+                }).await?;
 
                 Ok(LoginResult {
                     success: true,
-                    created_new: count == 0,
+                    created_new,
                 })
             },
             false => {
@@ -1,3 +1,4 @@
+mod config;
 mod parsing;
 mod db;
 mod remote;
@@ -5,37 +6,80 @@ mod daemons;
 mod routes;
 
 
+use config::{Config, LogFormat};
 use db::lib::*;
-use daemons::{groups::groups_daemon, jobs::{jobs_daemon, old_jobs_daemon}};
-use openssh::{KnownHosts, Session};
-use routes::AppState;
+use daemons::supervisor::Supervisor;
+use routes::{AppState, Cluster};
 
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
-use tokio::sync::RwLock;
+use anyhow::{Context, Result};
 use axum::{
     routing::{get, post}, Router
 };
 use tower_sessions::{cookie::Key, Expiry, MemoryStore, SessionManagerLayer};
 use tracing::info;
-use tracing_subscriber;
+use tracing_subscriber::{fmt::MakeWriter, layer::SubscriberExt, util::SubscriberInitExt, Layer, Registry};
 
 
-#[tokio::main]
-async fn main() -> ! {
-    // Initialize the logger
-    let file_appender = tracing_appender::rolling::daily("./logs", "daily.log");
-    let (non_blocking, _guard) = tracing_appender::non_blocking(file_appender);
-    tracing_subscriber::fmt()
-        .compact()
-        .with_writer(non_blocking)
+/// Connects to every configured cluster, in configuration order
+async fn connect_clusters ( config: &Config ) -> Result<Vec<Arc<Cluster>>> {
+    let mut clusters = Vec::new();
+    for cluster_config in config.clusters.iter() {
+        let cluster = Cluster::connect(cluster_config.clone())
+            .await
+            .with_context(|| format!("Failed to connect to cluster `{}`!", cluster_config.name))?;
+
+        clusters.push(Arc::new(cluster));
+    }
+
+    Ok(clusters)
+}
+/// How long the daemons have to reach a safe point on shutdown, which
+///  should be within the container's stop timeout (10s for `docker stop`)
+const DAEMON_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(8);
+/// How long closing the SSH sessions may take on shutdown, which
+///  with the above should still fit in the container's stop timeout
+const SSH_CLOSE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Formats logs as configured, for one destination
+fn log_layer <W> ( format: LogFormat, writer: W ) -> Box<dyn Layer<Registry> + Send + Sync>
+where
+    W: for<'w> MakeWriter<'w> + Send + Sync + 'static
+{
+    let layer = tracing_subscriber::fmt::layer()
+        .with_writer(writer)
         .with_file(true)
         .with_line_number(true)
         .with_thread_ids(true)
-        .with_target(false)
-        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
-        .init();
+        .with_target(false);
 
+    match format {
+        LogFormat::Text => layer.compact().boxed(),
+        LogFormat::Json => layer.json()
+            .with_current_span(true)
+            .with_span_list(true)
+            .boxed()
+    }
+}
+/// Waits for SIGTERM (i.e. `docker stop`) or SIGINT (i.e. Ctrl+C)
+async fn shutdown_signal ( ) {
+    let mut terminate = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+        .expect("Couldn't listen for SIGTERM!");
+
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => {},
+        _ = terminate.recv() => {}
+    }
+}
+/// Prints a startup error and exits, rather than panicking
+fn exit_with_error ( e: anyhow::Error ) -> ! {
+    eprintln!("[ Error: {e:#} ]");
+    std::process::exit(1);
+}
+
+#[tokio::main]
+async fn main() {
     // Write panics to `./logs/panic.log-<timestamp>`
     std::panic::set_hook(Box::new(|panic| {
         let panic_info = format!("{}", panic);
@@ -54,36 +98,63 @@ async fn main() -> ! {
             .expect("Failed to write panic info to file!");
     }));
 
+    // Load and print the configuration
+    let config = Config::load()
+        .unwrap_or_else(|e| exit_with_error(e));
+    eprintln!("[ Effective configuration: ]\n{}", config.render());
+
+    // Initialize the logger. The log file (if any) is written on
+    //  its own thread, and flushed when `log_guard` is dropped.
+    let mut log_layers = Vec::new();
+    let mut log_guard = None;
+    if config.logging.file {
+        let file_appender = tracing_appender::rolling::daily("./logs", "daily.log");
+        let (non_blocking, guard) = tracing_appender::non_blocking(file_appender);
+        log_layers.push(log_layer(config.logging.format, non_blocking));
+        log_guard = Some(guard);
+    }
+    if config.logging.stdout {
+        log_layers.push(log_layer(config.logging.format, std::io::stdout));
+    }
+    tracing_subscriber::registry()
+        .with(log_layers)
+        .with(tracing_subscriber::EnvFilter::from_default_env())
+        .init();
+
     // Create the shared state
-    let url_prefix = std::env::var("URL_PREFIX")
-        .unwrap_or(String::new());
-    let remote_username = std::env::var("REMOTE_USERNAME")
-        .expect("Missing `REMOTE_USERNAME` environment variable!");
-    let remote_hostname = std::env::var("REMOTE_HOSTNAME")
-        .expect("Missing `REMOTE_HOSTNAME` environment variable!");
+    let url_prefix = config.server.url_prefix.clone();
+    let clusters = connect_clusters(&config).await
+        .unwrap_or_else(|e| exit_with_error(e));
+    let db = DB::new(&config)
+        .context("Failed to establish connection to DB!")
+        .unwrap_or_else(|e| exit_with_error(e));
     let state: Arc<AppState> = Arc::new(AppState {
-        db: DB::new(
-            &std::env::var("DB_PATH")
-                .expect("Missing `DB_PATH` environment variable!")
-        ).expect("Failed to establish connection to DB!"),
-        url_prefix: url_prefix.clone(),
-
-        status: RwLock::new(None),
-        ssh_session: Arc::new(RwLock::new(Session::connect_mux(
-            &format!("{remote_username}@{remote_hostname}"),
-            KnownHosts::Strict
-        ).await.expect("Failed to connect to remote host!"))),
-
-        remote_username,
-        remote_hostname,
+        db,
+        daemons: Supervisor::new(&clusters),
+        clusters,
+        config
     });
     
     info!("[ Starting daemons... ]");
-    tokio::spawn(jobs_daemon(state.clone()));
-    tokio::spawn(old_jobs_daemon(state.clone()));
-    tokio::spawn(groups_daemon(state.clone()));
+    let mut daemons = Supervisor::start(&state);
     info!("[ Daemons started! ]");
 
+    // Start shutting down on the first signal, and stop
+    //  right away on the second
+    tokio::spawn({
+        let state = state.clone();
+        async move {
+            shutdown_signal().await;
+            eprintln!("[ Shutting down... ]");
+            info!("[ Shutting down... ]");
+            state.daemons.shutdown();
+
+            shutdown_signal().await;
+            eprintln!("[ Shutting down immediately! ]");
+            std::process::exit(1);
+        }
+    });
+
     // Create the Session store and layer
     let session_store = MemoryStore::default();
     // E.g. sessions expire after 30 minutes of inactivity
@@ -104,6 +175,12 @@ async fn main() -> ! {
     // Build the V1 API router
     let api_v1 = Router::new()
         .nest("/auth", auth_routes)
+        .route("/settings", post(routes::api::settings::update))
+        .route("/admin/backfill", post(routes::api::admin::start_backfill))
+        .route("/admin/backfill/{id}/cancel", post(routes::api::admin::cancel_backfill))
+        .route("/admin/daemons/{id}/run", post(routes::api::admin::run_daemon))
+        .route("/admin/daemons/{id}/pause", post(routes::api::admin::pause_daemon))
+        .route("/admin/daemons/{id}/resume", post(routes::api::admin::resume_daemon))
         .with_state(state.clone());
 
     // Nest the API into the general app router
@@ -115,24 +192,74 @@ async fn main() -> ! {
         .route(&(url_prefix.clone() + "/running"), get(routes::pages::running::running))
         .route(&(url_prefix.clone() + "/completed"), get(routes::pages::completed::completed))
         .route(&(url_prefix.clone() + "/search"), get(routes::pages::search::search))
+        .route(&(url_prefix.clone() + "/array"), get(routes::pages::array::array))
+        .route(&(url_prefix.clone() + "/users/{name}"), get(routes::pages::users::user))
+        .route(&(url_prefix.clone() + "/accounting"), get(routes::pages::accounting::accounting))
+        .route(&(url_prefix.clone() + "/accounting/csv"), get(routes::pages::accounting::accounting_csv))
+        .route(&(url_prefix.clone() + "/groups/{name}"), get(routes::pages::groups::group))
+        .route(&(url_prefix.clone() + "/settings"), get(routes::pages::settings::settings))
+        .route(&(url_prefix.clone() + "/admin/backfill"), get(routes::pages::admin::backfill))
+        .route(&(url_prefix.clone() + "/admin/daemons"), get(routes::pages::admin::daemons))
+        .route(&(url_prefix.clone() + "/admin/audit"), get(routes::pages::admin::audit))
+        .route(&(url_prefix.clone() + "/admin/audit/csv"), get(routes::pages::admin::audit_csv))
+        .route(&(url_prefix.clone() + "/healthz"), get(routes::health::healthz))
+        .route(&(url_prefix.clone() + "/readyz"), get(routes::health::readyz))
         .route(&(url_prefix.clone() + "/public/images/favicon.ico"), get(routes::get_favicon));
 
     if !url_prefix.is_empty() {
         app = app.route(&url_prefix, get(routes::pages::running::running));
     }
 
+    // The session layer goes outside so requests are traced with their user
     let app = app
+        .layer(axum::middleware::from_fn(routes::trace::trace_requests))
         .layer(session_layer)
         .with_state(state.clone());
 
-    // Start the server
-    let port = std::env::var("PORT").unwrap_or("5777".to_string());
-    loop {
+    // Start the server, which stops accepting requests on shutdown
+    //  and returns once those in flight have been answered
+    let port = state.config.server.port;
+    while !state.daemons.is_shutting_down() {
         eprintln!("[ Starting Hawkeye on {port}... ]");
         let listener = tokio::net::TcpListener::bind(&format!("0.0.0.0:{port}")).await
-            .expect("Couldn't start up listener!");
-        if let Err(e) = axum::serve(listener, app.clone()).await {
+            .with_context(|| format!("Couldn't start up listener on port {port}!"))
+            .unwrap_or_else(|e| exit_with_error(e));
+        let shutdown = {
+            let state = state.clone();
+            async move { state.daemons.shutdown_requested().await }
+        };
+        if let Err(e) = axum::serve(listener, app.clone())
+            .with_graceful_shutdown(shutdown)
+            .await
+        {
             eprintln!("[ Error: {} ]", e);
         }
     }
+
+    // Any ingest still running when the timeout hits is in a
+    //  transaction, so it's written or rolled back as a whole
+    eprintln!("[ Waiting for the daemons to stop... ]");
+    let stopped = tokio::time::timeout(DAEMON_SHUTDOWN_TIMEOUT, async {
+        while daemons.join_next().await.is_some() {}
+    }).await;
+    if stopped.is_err() {
+        eprintln!("[ The daemons didn't stop within {}s, aborting them! ]", DAEMON_SHUTDOWN_TIMEOUT.as_secs());
+        daemons.shutdown().await;
+    }
+
+    // Closing waits on the session's lock, which a reconnect can hold
+    let closed = tokio::time::timeout(SSH_CLOSE_TIMEOUT, async {
+        for cluster in state.clusters.iter() {
+            if let Err(e) = cluster.close_ssh_session().await {
+                eprintln!("[ Error: {e:#} ]");
+            }
+        }
+    }).await;
+    if closed.is_err() {
+        eprintln!("[ The SSH sessions didn't close within {}s, stopping anyway! ]", SSH_CLOSE_TIMEOUT.as_secs());
+    }
+
+    eprintln!("[ Hawkeye stopped ]");
+    info!("[ Hawkeye stopped ]");
+    drop(log_guard);
 }
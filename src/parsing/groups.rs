@@ -0,0 +1,96 @@
+use std::collections::{HashMap, HashSet};
+
+use anyhow::{Context, Result};
+
+/// A group from `getent group`, i.e. `hpc:x:1001:alice,bob`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GroupEntry<'a> {
+    pub name: &'a str,
+    pub gid: u32,
+    /// Supplementary members, which doesn't include the users
+    ///  who have this as their primary group
+    pub members: Vec<&'a str>
+}
+
+/// Parses the output of `getent group`
+pub fn parse_group_database ( output: &str ) -> Result<Vec<GroupEntry<'_>>> {
+    output.lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let mut fields = line.trim_end().split(':');
+            let name = fields.next()
+                .filter(|name| !name.is_empty())
+                .with_context(|| format!("Missing group name in `{line}`!"))?;
+            let gid = fields.nth(1)
+                .with_context(|| format!("Missing GID in `{line}`!"))?
+                .parse::<u32>()
+                .with_context(|| format!("Invalid GID in `{line}`!"))?;
+            let members = fields.next()
+                .unwrap_or("")
+                .split(',')
+                .filter(|member| !member.is_empty())
+                .collect();
+
+            Ok(GroupEntry { name, gid, members })
+        })
+        .collect()
+}
+/// Parses the output of `getent passwd` into each user's primary GID
+pub fn parse_primary_gids ( output: &str ) -> Result<HashMap<&str, u32>> {
+    output.lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let mut fields = line.split(':');
+            let name = fields.next()
+                .filter(|name| !name.is_empty())
+                .with_context(|| format!("Missing username in `{line}`!"))?;
+            let gid = fields.nth(2)
+                .with_context(|| format!("Missing GID in `{line}`!"))?
+                .parse::<u32>()
+                .with_context(|| format!("Invalid GID in `{line}`!"))?;
+
+            Ok((name, gid))
+        })
+        .collect()
+}
+/// The groups of each of `users`, ordered like the `groups` command:
+///  their primary group (their project) first, then their supplementary
+///  groups in the order of the group database.
+///
+/// Users without a primary GID (i.e. whose accounts were removed)
+///  are given no groups.
+pub fn user_memberships (
+    groups: &[GroupEntry],
+    primary_gids: &HashMap<&str, u32>,
+    users: &[String]
+) -> HashMap<String, Vec<String>> {
+    let group_names = groups.iter()
+        .map(|group| (group.gid, group.name))
+        .collect::<HashMap<u32, &str>>();
+    let mut supplementary: HashMap<&str, Vec<&str>> = HashMap::new();
+    for group in groups {
+        for member in group.members.iter() {
+            supplementary.entry(member)
+                .or_default()
+                .push(group.name);
+        }
+    }
+
+    users.iter()
+        .map(|user| {
+            let Some(primary_gid) = primary_gids.get(user.as_str()) else {
+                return (user.clone(), Vec::new());
+            };
+
+            let mut seen = HashSet::new();
+            let user_groups = group_names.get(primary_gid)
+                .into_iter()
+                .chain(supplementary.get(user.as_str()).into_iter().flatten())
+                .filter(|group| seen.insert(**group))
+                .map(|group| group.to_string())
+                .collect();
+
+            (user.clone(), user_groups)
+        })
+        .collect()
+}
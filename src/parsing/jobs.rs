@@ -1,145 +1,238 @@
-use std::collections::BTreeMap;
+use std::{borrow::Borrow, collections::BTreeMap};
+
+use anyhow::{Context, Result, bail};
+use chrono::NaiveDateTime;
+use chrono_tz::Tz;
+use tracing::{error, trace};
+
+use super::{
+    pbs::{column_at, parse_attribute_block, parse_record_fields, Attribute, ParseError, ParseErrorKind},
+    units::{Duration, Memory}
+};
+
+/// Parses a memory field, replacing it with its size in GB
+fn normalize_memory <K: Ord + Borrow<str>> (
+    entry: &mut BTreeMap<K, String>,
+    field: &str
+) -> Result<Option<Memory>> {
+    let Some(value) = entry.get_mut(field) else {
+        return Ok(None);
+    };
+
+    let memory = value.parse::<Memory>()
+        .with_context(|| format!("Couldn't unpack `{field}` field!"))?;
+    *value = format!("{:.2}", memory.gb());
+
+    Ok(Some(memory))
+}
+/// Parses a duration field, replacing it with its `HH:MM:SS` form
+fn normalize_duration <K: Ord + Borrow<str>> (
+    entry: &mut BTreeMap<K, String>,
+    field: &str
+) -> Result<Option<Duration>> {
+    let Some(value) = entry.get_mut(field) else {
+        return Ok(None);
+    };
 
-use anyhow::{Context, Result, bail, anyhow};
-use chrono::{DateTime, Utc};
-use tracing::{error, info};
+    let duration = value.parse::<Duration>()
+        .with_context(|| format!("Couldn't unpack `{field}` field!"))?;
+    *value = duration.to_string();
 
+    Ok(Some(duration))
+}
+/// Converts a PBS date (i.e. `Mon Jan 06 14:03:21 2025`), which
+///  is in the cluster's local time, to a UNIX timestamp
 #[tracing::instrument]
-pub fn convert_mem_to_f64 ( st: &str ) -> Result<f64> {
-    if let Ok(st) = st.parse() {
-        return Ok(st);
+pub fn date_to_unix_timestamp ( date_str: &str, timezone: Tz ) -> Result<u32, ParseError> {
+    let column = column_at(date_str, date_str.len() - date_str.trim_start().len());
+    let error = |kind| ParseError::new(kind, 1, column, date_str);
+
+    let naive = NaiveDateTime::parse_from_str(date_str.trim(), "%a %b %d %H:%M:%S %Y")
+        .map_err(|_| error(ParseErrorKind::InvalidDate))?;
+
+    // Times repeated when the clocks go back are taken as the
+    //  first, and times skipped when they go forward don't exist
+    let timestamp = naive.and_local_timezone(timezone)
+        .earliest()
+        .ok_or_else(|| error(ParseErrorKind::DateOutOfRange))?
+        .timestamp();
+
+    u32::try_from(timestamp)
+        .map_err(|_| error(ParseErrorKind::DateOutOfRange))
+}
+/// Which part of a PBS job array a job ID refers to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArrayIndex {
+    /// The array itself, i.e. `12345[]`
+    Parent,
+    /// A single subjob of the array, i.e. `12345[7]`
+    Subjob(u32)
+}
+/// A parsed PBS job identifier
+///
+/// Plain jobs look like `12345.server`, array parents like
+///  `12345[].server`, and array subjobs like `12345[7].server`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JobId {
+    pub sequence: u64,
+    pub array: Option<ArrayIndex>,
+    pub server: Option<String>
+}
+impl JobId {
+    /// The job ID without the server suffix (`12345`, `12345[]`
+    ///  or `12345[7]`), which is only unique per PBS server
+    pub fn short ( &self ) -> String {
+        match self.array {
+            None => self.sequence.to_string(),
+            Some(ArrayIndex::Parent) => format!("{}[]", self.sequence),
+            Some(ArrayIndex::Subjob(index)) => format!("{}[{index}]", self.sequence)
+        }
     }
-
-    if st.contains("kb") {
-        return Ok(st.split("kb")
-            .next()
-            .context("Invalid memory string!")?
-            .parse::<f64>()
-            .context("Couldn't convert memory string to f64!")?
-            * 0.0000009536743 );
-    } else if st.contains("mb") {
-        return Ok(st.split("mb")
-            .next()
-            .context("Invalid memory string!")?
-            .parse::<f64>()
-            .context("Couldn't convert memory string to f64!")?
-            * 0.0009765625 );
-    } else if st.contains("gb") {
-        return Ok(st.split("gb")
-            .next()
-            .context("Invalid memory string!")?
-            .parse::<f64>()
-            .context("Couldn't convert memory string to f64!")?);
-    } 
-
-    // Check if the last character is 'b', but the second
-    //  to last character is not a letter
-    //
-    // We return 0.0 in this case, as it indicates that the
-    //  requested memory is in bits, which does not easily
-    //  convert to gigabytes
-    if st.chars().last() == Some('b') && !st.chars().nth_back(1).unwrap().is_alphabetic() {
-        return Ok(0.0);
+    /// The full job ID as stored in the DB, including the
+    ///  server suffix when known (i.e. `12345[7].server`)
+    pub fn key ( &self ) -> String {
+        with_server(self.short(), self.server.as_deref())
     }
+    /// Fields describing this ID, to be merged into a parsed job
+    pub fn fields ( &self ) -> Vec<(&'static str, String)> {
+        let mut fields = vec!(
+            ("job_id", self.key()),
+            ("short_id", self.short()),
+            ("pbs_id", self.sequence.to_string()),
+        );
+        match self.array {
+            Some(ArrayIndex::Parent) => {
+                fields.push(("is_array", String::from("1")));
+            },
+            Some(ArrayIndex::Subjob(index)) => {
+                fields.push(("array_index", index.to_string()));
+                fields.push((
+                    "array_parent",
+                    with_server(format!("{}[]", self.sequence), self.server.as_deref())
+                ));
+            },
+            None => {}
+        }
 
-    // Check if the last character is 'b', but the second
-    //  to last character is a letter, and error out
-    //  stating that the type is not supported
-    if st.chars().last() == Some('b') && st.chars().nth_back(1).unwrap().is_alphabetic() {
-        error!("Recieved unusual memory size - {st}");
-        bail!("Recieved unusual memory size - {st}");
+        fields
     }
-    
-    error!("Recieved unusual memory input - {st}");
-    bail!("Recieved unusual memory input - {st}");
 }
-#[tracing::instrument]
-pub fn date_to_unix_timestamp(date_str: &str) -> Result<u32, String> {
-    // Define the input date format
-    let format = "%a %b %d %H:%M:%S %Y %z";
-
-    // Parse the input string into a DateTime<Utc>
-    let datetime = match DateTime::parse_from_str(
-        &format!("{} {}", date_str, "+0000"),
-        format
-    ) {
-        Ok(dt) => dt.with_timezone(&Utc),
-        Err(e) => {
-            error!(%e, "Failed to parse date!");
-            return Err(format!("Failed to parse date: {}", e))
-        },
-    };
-
-    // Convert the DateTime<Utc> to a UNIX timestamp
-    let timestamp = datetime.timestamp();
-
-    // Ensure the timestamp is within the range of u32
-    if timestamp < 0 {
-        error!("Timestamp is negative, cannot fit into u32");
-        return Err("Timestamp is negative, cannot fit into u32".to_string());
+fn with_server ( short: String, server: Option<&str> ) -> String {
+    match server {
+        Some(server) => format!("{short}.{server}"),
+        None => short
     }
-
-    Ok(timestamp as u32)
 }
+/// Strips the server suffix from a full job ID, for display
+pub fn short_job_id ( job_id: &str ) -> &str {
+    job_id.split('.')
+        .next()
+        .unwrap_or(job_id)
+}
+/// Parses a job ID, with any error's column relative to `raw`
 #[tracing::instrument]
-pub fn walltime_to_percentage(reserved: &str, used: &str) -> Result<f64, String> {
-    fn parse_time_to_seconds(time: &str) -> Result<u64, String> {
-        let parts: Vec<&str> = time.split(':').collect();
-        if parts.len() != 3 {
-            error!("Invalid time format: {}", time);
-            return Err(format!("Invalid time format: {}", time));
-        }
-        let hours: u64 = parts[0].parse().map_err(|_| format!("Invalid hours in: {}", time))?;
-        let minutes: u64 = parts[1].parse().map_err(|_| format!("Invalid minutes in: {}", time))?;
-        let seconds: u64 = parts[2].parse().map_err(|_| format!("Invalid seconds in: {}", time))?;
-        Ok(hours * 3600 + minutes * 60 + seconds)
+pub fn parse_job_id ( raw: &str ) -> Result<JobId, ParseError> {
+    let start = raw.len() - raw.trim_start().len();
+    let error = |kind, offset| ParseError::new(kind, 1, column_at(raw, start + offset), raw);
+
+    // Split off the server suffix, if there is one
+    let (id, server) = match raw.trim().split_once('.') {
+        Some((id, server)) => (id, Some(server.to_string())
+            .filter(|server| !server.is_empty())),
+        None => (raw.trim(), None)
+    };
+    if id.is_empty() {
+        return Err(error(ParseErrorKind::EmptyJobId, 0));
     }
 
-    let reserved_seconds = parse_time_to_seconds(reserved)?;
-    let used_seconds = parse_time_to_seconds(used)?;
+    let (sequence, array) = match id.split_once('[') {
+        Some((sequence, index)) => {
+            let index = index.strip_suffix(']')
+                .ok_or_else(|| error(ParseErrorKind::UnterminatedArrayIndex, sequence.len()))?;
+
+            let array = if index.is_empty() {
+                ArrayIndex::Parent
+            } else {
+                ArrayIndex::Subjob(index.parse::<u32>()
+                    .map_err(|_| error(ParseErrorKind::InvalidArrayIndex, sequence.len() + 1))?)
+            };
+
+            (sequence, Some(array))
+        },
+        None => (id, None)
+    };
 
-    if reserved_seconds == 0 {
-        error!("Reserved walltime cannot be zero.");
-        return Err("Reserved walltime cannot be zero.".to_string());
+    Ok(JobId {
+        sequence: sequence.parse::<u64>()
+            .map_err(|_| error(ParseErrorKind::InvalidSequence, 0))?,
+        array,
+        server
+    })
+}
+/// Counts the number of subjobs described by a PBS array
+///  index range, such as `1-10`, `0-99:2` or `1,3,5-7`
+pub fn count_array_indices ( indices: &str ) -> Option<u32> {
+    let mut total = 0u32;
+
+    for range in indices.split(',') {
+        let (range, step) = match range.split_once(':') {
+            Some((range, step)) => (range, step.parse::<u32>().ok()?.max(1)),
+            None => (range, 1)
+        };
+
+        let count = match range.split_once('-') {
+            Some((start, end)) => {
+                let start = start.trim().parse::<u32>().ok()?;
+                let end = end.trim().parse::<u32>().ok()?;
+                if end < start {
+                    return None;
+                }
+
+                ((end - start) / step).checked_add(1)?
+            },
+            None => {
+                range.trim().parse::<u32>().ok()?;
+
+                1
+            }
+        };
+        total = total.checked_add(count)?;
     }
 
-    Ok((used_seconds as f64 / reserved_seconds as f64) * 100.0)
+    Some(total)
 }
-#[tracing::instrument]
-pub fn jmanl_job_str_to_btree<'a>(
-    prelim: Vec<&'a str>,
-    job: &'a str
-) -> Result<BTreeMap<String, String>> {
+/// Parses a raw `jmanl` record, being `date;state;id;` then its fields
+#[tracing::instrument(skip(record), fields(len = record.len()))]
+pub fn jmanl_job_str_to_btree ( record: &str ) -> Result<BTreeMap<String, String>> {
     let mut entry = BTreeMap::new();
 
-    info!("[ Looking at the following job ]\n{job}");
-
-    entry.insert(
-        "job_state".to_string(),
-        prelim.get(1)
-            .context("Invalid `job_state` field!")?
-            .to_string()
-    );
-    entry.insert(
-        "job_id".to_string(),
-        prelim.get(2)
-            .context("Invalid `job_id` (1/2) field!")?
-            .split('.')
-            .next()
-            .context("Invalid `job_id` (2/2) field!")?
-            .to_string()
-    );
-
-    for field in job.split_ascii_whitespace() {
-        let field = field.trim_ascii_start();
-        let name = field.split("=")
-            .next()
-            .context("Invalid `name` field!")?;
-        let value = field.split("=")
-            .skip(1)
-            .collect::<Vec<&str>>()
-            .join("=");
-        info!("\t[ Got Field ]\n{name} - {value}");
+    trace!("[ Looking at the following job ]\n{record}");
+
+    // The `date;state;id;` fields, and where the next one starts
+    let mut prelim = Vec::new();
+    let mut rest = record;
+    for _ in 0..3 {
+        let (field, after) = rest.split_once(';')
+            .ok_or_else(|| ParseError::new(ParseErrorKind::MissingField, 1, column_at(record, record.len()), record))?;
+        prelim.push((field, record.len() - rest.len()));
+        rest = after;
+    }
+    let fields_start = record.len() - rest.len();
+
+    entry.insert("job_state".to_string(), prelim[1].0.to_string());
+    let (job_id, job_id_start) = prelim[2];
+    let job_id = parse_job_id(job_id)
+        .map_err(|e| e.shifted(1, column_at(record, job_id_start), record))
+        .context("Couldn't parse job ID!")?;
+    for (name, value) in job_id.fields() {
+        entry.insert(name.to_string(), value);
+    }
+
+    let fields = parse_record_fields(rest)
+        .map_err(|e| e.shifted(1, column_at(record, fields_start), record))
+        .context("Couldn't parse `jmanl` record fields!")?;
+    for (name, value) in fields {
+        trace!("\t[ Got Field ]\n{name} - {value}");
 
         if name == "start" {
             entry.insert("start_time".to_string(), value.to_string());
@@ -156,35 +249,16 @@ pub fn jmanl_job_str_to_btree<'a>(
         entry.insert(name.to_string(), value.to_string());
     }
 
-    info!("\t[ Converting Resources Used Memory Field... ]");
-    if let Some(entry) = entry.get_mut("resources_used.mem") {
-        *entry = convert_mem_to_f64(&(*entry))
-            .context("Couldn't unpack memory field!")?
-            .floor()
-            .to_string();
-    }
-
-    info!("\t[ Converting Resource List Memory Field... ]");
-    if let Some(entry) = entry.get_mut("Resource_List.mem") {
-        *entry = (*entry).split("gb")
-            .next()
-            .context("Invalid `Resource_List` field!")?
-            .to_string();
-    }
+    trace!("\t[ Converting Memory Fields... ]");
+    let used_mem = normalize_memory(&mut entry, "resources_used.mem")?
+        .context("Missing field 'resources_used.mem'")?;
+    let rsvd_mem = normalize_memory(&mut entry, "Resource_List.mem")?
+        .context("Missing field 'Resource_List.mem'")?;
 
-    info!("\n[ Calculating Memory Efficiency... ]");
-    let mem_efficiency = 
-        convert_mem_to_f64(&entry.get("resources_used.mem")
-            .context("Missing field 'resources_used.mem'")?)
-            .context("Couldn't unpack memory field!")?
-        /
-        convert_mem_to_f64(&entry.get("Resource_List.mem")
-            .context("Missing field 'Resource_used.mem'")?)
-            .context("Couldn't unpack memory field!")?
-        * 100f64;
-    entry.insert("mem_efficiency".to_string(), mem_efficiency.to_string());
+    trace!("\n[ Calculating Memory Efficiency... ]");
+    entry.insert("mem_efficiency".to_string(), used_mem.percent_of(rsvd_mem).to_string());
 
-    info!("\t[ Converting Resource List Nodes Field... ]");
+    trace!("\t[ Converting Resource List Nodes Field... ]");
     if let Some(exec_host_str) = entry.get_mut("exec_host") {
         let nodes = exec_host_str.split("+")
             .flat_map(|node| {
@@ -198,21 +272,25 @@ pub fn jmanl_job_str_to_btree<'a>(
         entry.insert("Nodes".to_string(), nodes.to_string());
     }
 
-    info!("\t[ Adding UNIX End Timestamp... ]");
+    trace!("\t[ Adding UNIX End Timestamp... ]");
     entry.insert("end_time".to_string(), entry.get("end")
         .context("Missing field 'end'")?
         .parse::<i64>()
         .context("Couldn't parse UNIX timestamp!")?
         .to_string());
 
-    info!("\t[ Calculating Walltime Efficiency... ]");
-    let walltime_efficiency = walltime_to_percentage(
-        &entry.get("Resource_List.walltime").context("Missing field 'Resource_List.walltime'")?,
-        &entry.get("resources_used.walltime").context("Missing field 'resources_used.walltime'")?
-    ).map_err(|e| anyhow!("Couldn't calculate walltime efficiency! Error: {e:?}"))?;
-    entry.insert("walltime_efficiency".to_string(), walltime_efficiency.to_string());
+    trace!("\t[ Calculating Walltime Efficiency... ]");
+    let rsvd_walltime = normalize_duration(&mut entry, "Resource_List.walltime")?
+        .context("Missing field 'Resource_List.walltime'")?;
+    let used_walltime = normalize_duration(&mut entry, "resources_used.walltime")?
+        .context("Missing field 'resources_used.walltime'")?;
+    normalize_duration(&mut entry, "resources_used.cput")?;
+    if rsvd_walltime.seconds() == 0 {
+        bail!("Reserved walltime cannot be zero!");
+    }
+    entry.insert("walltime_efficiency".to_string(), used_walltime.percent_of(rsvd_walltime).to_string());
 
-    info!("Calculating CPU Efficiency...");
+    trace!("Calculating CPU Efficiency...");
     let cpu_efficiency = 
     ( ( entry.get("resources_used.cpupercent")
         .unwrap_or(&String::from("0"))
@@ -226,43 +304,37 @@ pub fn jmanl_job_str_to_btree<'a>(
         * 100f64 ).min(100f64);
     entry.insert("cpu_efficiency".to_string(), cpu_efficiency.to_string());
 
-    info!("\t\t[ Done! ]");
+    trace!("\t\t[ Done! ]");
     Ok(entry)
 }
-#[tracing::instrument]
-pub fn jobstat_job_str_to_btree<'a>( job: &'a str ) -> Result<BTreeMap<&'a str, String>> {
+#[tracing::instrument(skip(job), fields(len = job.len()))]
+pub fn jobstat_job_str_to_btree<'a>( job: &'a str, timezone: Tz ) -> Result<BTreeMap<&'a str, String>> {
     let mut entry = BTreeMap::new();
 
-    info!("\n[ Looking at the following job ]\n{job}");
-    for (ind, field) in job.lines().enumerate() {
-        if ind == 0 {
-            entry.insert("job_id", field.to_string());
-            info!("Inserting for Job ID from ind 0 - {field}");
-            continue;
-        }
-        if field.starts_with("Nodes: ") {
-            continue;
-        }
+    trace!("\n[ Looking at the following job ]\n{job}");
+    let block = parse_attribute_block(job, &["Nodes: "])
+        .context("Couldn't parse `jobstat` job!")?;
+
+    trace!("Inserting for Job ID from header - {}", block.header);
+    let job_id = parse_job_id(block.header)
+        .map_err(|e| e.shifted(block.header_line, 1, block.header))
+        .context("Couldn't parse job ID!")?;
+    entry.extend(job_id.fields());
 
-        let field = field.trim_ascii_start();
-        info!("\t[ Analyzing Field ]\n{field} - {ind} - {field}");
-        let name = field.split(" = ")
-            .next()
-            .context("Invalid `name` field!")?;
-        let value = field.split(" = ")
-            .nth(1)
-            .context("Invalid `name` (missing value) field!")?;
+    for Attribute { name, value, line, column } in block.attributes {
+        trace!("\t[ Analyzing Field ]\n{name} - {line} - {value}");
 
         if name == "stime" {
             // Convert the start time to a UNIX timestamp
-            let timestamp = date_to_unix_timestamp(value)
-                .map_err(|e| anyhow!("Couldn't convert start time to UNIX timestamp! Error: {e:?}"))?;
+            let timestamp = date_to_unix_timestamp(&value, timezone)
+                .map_err(|e| e.shifted(line, column, job.lines().nth(line - 1).unwrap_or(&value)))
+                .context("Couldn't convert start time to UNIX timestamp!")?;
             entry.insert("start_time", timestamp.to_string());
             continue;
         }
 
         if name == "Job_Owner" {
-            info!("\t[ Reformatting Job Owner... ]");
+            trace!("\t[ Reformatting Job Owner... ]");
             let owner = value
                 .split("@")
                 .next()
@@ -274,59 +346,50 @@ pub fn jobstat_job_str_to_btree<'a>( job: &'a str ) -> Result<BTreeMap<&'a str,
             );
             continue;
         }
-        entry.insert(name, value.to_string());
+        entry.insert(name, value);
     }
 
     if let Some(state) = entry.get("job_state") {
         if state == "Q" {
-            info!("\t[ Job is in queue, inserting dummy values... ]");
+            trace!("\t[ Job is in queue, inserting dummy values... ]");
             entry.insert("resources_used.mem", "0".to_string());
             entry.insert("resources_used.walltime", "00:00:00".to_string());
             entry.insert("resources_used.cpupercent", "0".to_string());
             entry.insert("start_time", i32::MAX.to_string());
             entry.insert("Nodes", "None".to_string());
+        } else if state == "B" {
+            // Array parents which have begun don't report their own usage,
+            //  as it's tracked on each of their subjobs instead
+            trace!("\t[ Job is a running array, filling in missing values... ]");
+            entry.entry("resources_used.mem").or_insert("0".to_string());
+            entry.entry("resources_used.walltime").or_insert("00:00:00".to_string());
+            entry.entry("resources_used.cpupercent").or_insert("0".to_string());
+            entry.entry("start_time").or_insert(i32::MAX.to_string());
+            entry.entry("Nodes").or_insert("None".to_string());
         }
     } else {
         error!("Job state not found!");
         bail!("Job state not found!");
     }
 
-    info!("\t[ Converting Resources Used Memory Field... ]");
-    if let Some(entry) = entry.get_mut("resources_used.mem") {
-        *entry = convert_mem_to_f64(&(*entry))
-            .context("Couldn't unpack memory field!")?
-            .floor()
-            .to_string();
-    }
+    trace!("\t[ Converting Memory Fields... ]");
+    let used_mem = normalize_memory(&mut entry, "resources_used.mem")?
+        .unwrap_or_default();
+    let rsvd_mem = normalize_memory(&mut entry, "Resource_List.mem")?
+        .context("Missing field 'Resource_List.mem'")?;
 
-    info!("\t[ Converting Resource List Memory Field... ]");
-    if let Some(entry) = entry.get_mut("Resource_List.mem") {
-        *entry = (*entry).split("gb")
-            .next()
-            .context("Invalid `Resource_List` field!")?
-            .to_string();
-    }
+    trace!("\n[ Calculating Memory Efficiency... ]");
+    entry.insert("mem_efficiency", used_mem.percent_of(rsvd_mem).to_string());
 
-    info!("\n[ Calculating Memory Efficiency... ]");
-    let mem_efficiency = 
-        convert_mem_to_f64(&entry.get("resources_used.mem")
-            .unwrap_or(&String::from("0")))
-            .context("Couldn't unpack memory field!")?
-        /
-        convert_mem_to_f64(&entry.get("Resource_List.mem")
-            .context("Missing field 'Resource_used.mem'")?)
-            .context("Couldn't unpack memory field!")?
-        * 100f64;
-    entry.insert("mem_efficiency", mem_efficiency.to_string());
-
-    info!("\t[ Converting Resource List Nodes Field... ]");
-    let walltime_efficiency = walltime_to_percentage(
-        &entry.get("Resource_List.walltime").unwrap_or(&String::from("00:00:01")),
-        &entry.get("resources_used.walltime").unwrap_or(&String::from("00:00:01"))
-    ).map_err(|e| anyhow!("Couldn't calculate walltime efficiency! Error: {e:?}"))?;
-    entry.insert("walltime_efficiency", walltime_efficiency.to_string());
-
-    info!("\t[ Calculating CPU Efficiency... ]");
+    trace!("\t[ Calculating Walltime Efficiency... ]");
+    let rsvd_walltime = normalize_duration(&mut entry, "Resource_List.walltime")?
+        .unwrap_or(Duration::from_seconds(1));
+    let used_walltime = normalize_duration(&mut entry, "resources_used.walltime")?
+        .unwrap_or(Duration::from_seconds(1));
+    normalize_duration(&mut entry, "resources_used.cput")?;
+    entry.insert("walltime_efficiency", used_walltime.percent_of(rsvd_walltime).to_string());
+
+    trace!("\t[ Calculating CPU Efficiency... ]");
     let cpu_efficiency = 
     ( ( entry.get("resources_used.cpupercent")
         .unwrap_or(&String::from("0"))
@@ -341,4 +404,199 @@ pub fn jobstat_job_str_to_btree<'a>( job: &'a str ) -> Result<BTreeMap<&'a str,
     entry.insert("cpu_efficiency", cpu_efficiency.to_string());
 
     Ok(entry)
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::pbs::tests::{jmanl_records, jobstat_blocks};
+
+    #[test]
+    fn parses_plain_job_ids () {
+        let id = parse_job_id("12345.cm-pbs-01").unwrap();
+        assert_eq!(id, JobId { sequence: 12345, array: None, server: Some(String::from("cm-pbs-01")) });
+        assert_eq!(id.short(), "12345");
+        assert_eq!(id.key(), "12345.cm-pbs-01");
+
+        let id = parse_job_id(" 12345 ").unwrap();
+        assert_eq!(id.server, None);
+        assert_eq!(id.key(), "12345");
+
+        // A trailing dot has no server after it
+        assert_eq!(parse_job_id("12345.").unwrap().server, None);
+    }
+
+    #[test]
+    fn parses_array_job_ids () {
+        let parent = parse_job_id("123[].server").unwrap();
+        assert_eq!(parent.array, Some(ArrayIndex::Parent));
+        assert_eq!(parent.short(), "123[]");
+        assert_eq!(parent.key(), "123[].server");
+
+        let subjob = parse_job_id("123[4].server").unwrap();
+        assert_eq!(subjob.array, Some(ArrayIndex::Subjob(4)));
+        assert_eq!(subjob.short(), "123[4]");
+        assert_eq!(subjob.key(), "123[4].server");
+
+        let fields = subjob.fields();
+        assert!(fields.contains(&("array_index", String::from("4"))));
+        assert!(fields.contains(&("array_parent", String::from("123[].server"))));
+        assert!(parent.fields().contains(&("is_array", String::from("1"))));
+
+        assert_eq!(parse_job_id("123[]").unwrap().key(), "123[]");
+    }
+
+    #[test]
+    fn rejects_malformed_job_ids () {
+        for raw in ["", "   ", ".server", "abc.server", "123[.server", "123[4", "123[x].server", "123[-1].server", "-5.server", "99999999999999999999999.server"] {
+            assert!(parse_job_id(raw).is_err(), "`{raw}` should be rejected");
+        }
+    }
+
+    #[test]
+    fn shortens_job_ids () {
+        assert_eq!(short_job_id("123[4].server"), "123[4]");
+        assert_eq!(short_job_id("12345"), "12345");
+    }
+
+    #[test]
+    fn counts_array_indices () {
+        for (indices, count) in [
+            ("7", 1),
+            ("1-10", 10),
+            ("0-99", 100),
+            ("1-99:2", 50),
+            ("0-99:2", 50),
+            ("1-10:3", 4),
+            ("1-10:0", 10),
+            ("1,3,5-7", 5),
+            ("1-4,10-20:5", 7),
+            (" 1 - 3 ", 3),
+            ("5-5", 1)
+        ] {
+            assert_eq!(count_array_indices(indices), Some(count), "`{indices}`");
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_array_indices () {
+        for indices in ["", "a", "1-", "-3", "5-1", "1-10:x", "1,,3", ":2", "0-4294967295", "0-4294967294,1-2"] {
+            assert_eq!(count_array_indices(indices), None, "`{indices}`");
+        }
+    }
+
+    /// The `ParseError` somewhere in an error's chain
+    fn parse_error ( e: &anyhow::Error ) -> ParseError {
+        e.chain()
+            .find_map(|cause| cause.downcast_ref::<ParseError>())
+            .cloned()
+            .expect("No `ParseError` in the chain")
+    }
+
+    #[test]
+    fn locates_job_id_errors () {
+        for (raw, kind, column) in [
+            ("", ParseErrorKind::EmptyJobId, 1),
+            (".server", ParseErrorKind::EmptyJobId, 1),
+            ("  abc.server", ParseErrorKind::InvalidSequence, 3),
+            ("123[4.server", ParseErrorKind::UnterminatedArrayIndex, 4),
+            ("123[x].server", ParseErrorKind::InvalidArrayIndex, 5)
+        ] {
+            let e = parse_job_id(raw).unwrap_err();
+            assert_eq!((e.kind, e.line, e.column), (kind, 1, column), "`{raw}`");
+        }
+    }
+
+    #[test]
+    fn locates_date_errors () {
+        let timezone = chrono_tz::America::Chicago;
+        assert_eq!(date_to_unix_timestamp("Mon Jan 06 14:03:21 2025", timezone), Ok(1736193801));
+
+        let e = date_to_unix_timestamp("  Mon Jan 06 25:03:21 2025", timezone).unwrap_err();
+        assert_eq!((e.kind, e.column), (ParseErrorKind::InvalidDate, 3));
+        // Skipped when the clocks went forward
+        let e = date_to_unix_timestamp("Sun Mar 09 02:30:00 2025", timezone).unwrap_err();
+        assert_eq!(e.kind, ParseErrorKind::DateOutOfRange);
+        let e = date_to_unix_timestamp("Wed Dec 31 12:00:00 1969", timezone).unwrap_err();
+        assert_eq!(e.kind, ParseErrorKind::DateOutOfRange);
+    }
+
+    #[test]
+    fn locates_jmanl_record_errors () {
+        let record = "01/06/2025 14:03:21;E;12x.server;user=a";
+        let e = parse_error(&jmanl_job_str_to_btree(record).unwrap_err());
+        assert_eq!((e.kind, e.line, e.column), (ParseErrorKind::InvalidSequence, 1, 23));
+        assert_eq!(e.text, record);
+
+        let record = "01/06/2025 14:03:21;E;12.server;user=a jobname=\"unclosed";
+        let e = parse_error(&jmanl_job_str_to_btree(record).unwrap_err());
+        assert_eq!((e.kind, e.column), (ParseErrorKind::UnterminatedQuote, 48));
+
+        let e = parse_error(&jmanl_job_str_to_btree("01/06/2025 14:03:21;E").unwrap_err());
+        assert_eq!(e.kind, ParseErrorKind::MissingField);
+    }
+
+    #[test]
+    fn locates_jobstat_errors () {
+        let job = "\n12345.server\n    job_state = R\n    stime = Mon Jan 06 25:03:21 2025\n";
+        let e = parse_error(&jobstat_job_str_to_btree(job, chrono_tz::UTC).unwrap_err());
+        assert_eq!((e.kind, e.line, e.column), (ParseErrorKind::InvalidDate, 4, 13));
+        assert_eq!(e.text, "    stime = Mon Jan 06 25:03:21 2025");
+
+        let e = parse_error(&jobstat_job_str_to_btree("\n\n123[4.server\n    job_state = R\n", chrono_tz::UTC).unwrap_err());
+        assert_eq!((e.kind, e.line, e.column), (ParseErrorKind::UnterminatedArrayIndex, 3, 4));
+    }
+
+    #[test]
+    fn parses_jobstat_fixture () {
+        let jobs = jobstat_blocks()
+            .into_iter()
+            .map(|block| jobstat_job_str_to_btree(block, chrono_tz::America::Chicago).unwrap())
+            .collect::<Vec<_>>();
+
+        let running = &jobs[0];
+        assert_eq!(running["job_id"], "12345.cm-pbs-01");
+        assert_eq!(running["Job_Owner"], "alice");
+        assert_eq!(running["start_time"], "1736180142");
+        assert_eq!(running["Resource_List.mem"], "64.00");
+        assert_eq!(running["resources_used.walltime"], "03:49:18");
+        assert_eq!(running["cpu_efficiency"].parse::<f64>().unwrap(), 2950f64 / 32f64);
+
+        let queued = &jobs[1];
+        assert_eq!(queued["start_time"], i32::MAX.to_string());
+        assert_eq!(queued["Resource_List.mem"], "0.49");
+        assert_eq!(queued["Resource_List.walltime"], "01:30:00");
+
+        let parent = &jobs[2];
+        assert_eq!((parent["short_id"].as_str(), parent["is_array"].as_str()), ("12350[]", "1"));
+        assert_eq!(parent["resources_used.mem"], "0.00");
+        assert_eq!(count_array_indices(&parent["array_indices_submitted"]), Some(50));
+
+        let subjob = &jobs[3];
+        assert_eq!(subjob["array_parent"], "12350[].cm-pbs-01");
+        assert_eq!(subjob["array_index"], "7");
+    }
+
+    #[test]
+    fn parses_jmanl_fixture () {
+        let jobs = jmanl_records()
+            .into_iter()
+            .map(|record| jmanl_job_str_to_btree(record).unwrap())
+            .collect::<Vec<_>>();
+
+        assert_eq!(jobs[0]["job_id"], "12340.cm-pbs-01");
+        assert_eq!(jobs[0]["Job_Owner"], "alice");
+        assert_eq!(jobs[0]["Nodes"], "node003");
+        assert_eq!(jobs[0]["resources_used.mem"], "8.00");
+        assert_eq!(jobs[0]["end_time"], "1736176364");
+
+        assert_eq!(jobs[1]["Job_Name"], "long name; with spaces");
+        assert_eq!(jobs[1]["Nodes"], "node017,node018");
+        assert_eq!(jobs[1]["Resource_List.walltime"], "24:00:00");
+        assert_eq!(jobs[1]["resources_used.walltime"], "06:59:22");
+
+        assert_eq!(jobs[2]["array_parent"], "12350[].cm-pbs-01");
+        assert_eq!(jobs[2]["resources_used.walltime"], "00:41:10");
+        assert_eq!(jobs[2]["resources_used.mem"], "0.50");
+    }
+}
@@ -1 +1,4 @@
-pub mod jobs;
\ No newline at end of file
+pub mod groups;
+pub mod jobs;
+pub mod pbs;
+pub mod units;
\ No newline at end of file
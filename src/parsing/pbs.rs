@@ -0,0 +1,474 @@
+use std::fmt::Display;
+
+/// What was wrong with a piece of PBS output
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    /// The block had no header line (i.e. the job ID)
+    MissingHeader,
+    /// An attribute line had no ` = ` between its name and value
+    MissingSeparator,
+    /// An attribute had no name before its ` = ` or `=`
+    EmptyName,
+    /// A quoted value was never closed
+    UnterminatedQuote,
+    /// A continuation line came before any attribute
+    OrphanContinuation,
+    /// A job ID had nothing before its server suffix
+    EmptyJobId,
+    /// A job ID's sequence number wasn't a number
+    InvalidSequence,
+    /// A job ID's `[` was never closed
+    UnterminatedArrayIndex,
+    /// A job ID's array index wasn't a number
+    InvalidArrayIndex,
+    /// A date wasn't in PBS's `Mon Jan 06 14:03:21 2025` form
+    InvalidDate,
+    /// A date was skipped in the cluster's timezone (i.e. when
+    ///  the clocks went forward), or was before 1970
+    DateOutOfRange,
+    /// An accounting record was missing one of its `date;state;id;` fields
+    MissingField
+}
+impl Display for ParseErrorKind {
+    fn fmt ( &self, f: &mut std::fmt::Formatter<'_> ) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::MissingHeader => "Missing header line",
+            Self::MissingSeparator => "Missing ` = ` between attribute name and value",
+            Self::EmptyName => "Empty attribute name",
+            Self::UnterminatedQuote => "Unterminated quoted value",
+            Self::OrphanContinuation => "Continuation line without an attribute to continue",
+            Self::EmptyJobId => "Empty job ID",
+            Self::InvalidSequence => "Invalid job ID sequence number",
+            Self::UnterminatedArrayIndex => "Unterminated job array index",
+            Self::InvalidArrayIndex => "Invalid job array index",
+            Self::InvalidDate => "Invalid date",
+            Self::DateOutOfRange => "Date doesn't exist in the cluster's timezone, or is before 1970",
+            Self::MissingField => "Missing record field"
+        })
+    }
+}
+/// An error in PBS output, with where it happened.
+///
+/// `line` and `column` are 1-based, and relative to the
+///  start of the text given to the parser.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub kind: ParseErrorKind,
+    pub line: usize,
+    pub column: usize,
+    /// The offending line
+    pub text: String
+}
+impl ParseError {
+    pub(super) fn new ( kind: ParseErrorKind, line: usize, column: usize, text: &str ) -> Self {
+        Self {
+            kind,
+            line,
+            column,
+            text: text.to_string()
+        }
+    }
+    /// Moves an error found in part of a larger text (i.e. in
+    ///  an attribute's value) to where that part starts in it
+    pub(super) fn shifted ( mut self, line: usize, column: usize, text: &str ) -> Self {
+        if self.line == 1 {
+            self.column += column - 1;
+        }
+        self.line += line - 1;
+        self.text = text.to_string();
+
+        self
+    }
+}
+/// The 1-based column of a byte offset into `text`
+pub(super) fn column_at ( text: &str, offset: usize ) -> usize {
+    text[..offset].chars().count() + 1
+}
+impl Display for ParseError {
+    fn fmt ( &self, f: &mut std::fmt::Formatter<'_> ) -> std::fmt::Result {
+        write!(f, "{} at line {}, column {}: `{}`", self.kind, self.line, self.column, self.text)
+    }
+}
+impl std::error::Error for ParseError {}
+
+/// A `name = value` attribute of a `qstat -f` style block
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Attribute<'a> {
+    pub name: &'a str,
+    pub value: String,
+    /// The line the attribute started on
+    pub line: usize,
+    /// The column its value started at
+    pub column: usize
+}
+/// A `qstat -f` style block, i.e. one job of `jobstat -anL`:
+///
+/// ```text
+/// 12345.cm-pbs-01
+///     Job_Name = example
+///     Variable_List = PBS_O_HOME=/home/user,PBS_O_LANG=en_US.UTF-8,PBS_O_LOGNAME=
+/// <tab>user,PBS_O_WORKDIR=/home/user
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AttributeBlock<'a> {
+    /// The first line, i.e. the job ID
+    pub header: &'a str,
+    /// The line the header was on, after any blank lines
+    pub header_line: usize,
+    pub attributes: Vec<Attribute<'a>>
+}
+
+/// Removes the quotes around a value, if it's quoted
+fn unquote ( value: &str, line: usize, column: usize, text: &str ) -> Result<String, ParseError> {
+    let Some(quoted) = value.strip_prefix('"') else {
+        return Ok(value.to_string());
+    };
+
+    match quoted.strip_suffix('"') {
+        Some(inner) if !inner.ends_with('\\') || inner.ends_with("\\\\") => {
+            Ok(inner.replace("\\\"", "\""))
+        },
+        _ => Err(ParseError::new(ParseErrorKind::UnterminatedQuote, line, column, text))
+    }
+}
+
+/// Parses a block of `name = value` lines after a header line.
+///
+/// PBS wraps long values (such as `Variable_List` and `exec_vnode`)
+///  onto lines starting with a tab, which are joined back onto the
+///  value they continue. Blank lines, and lines starting with any
+///  of the `ignore` prefixes, are skipped.
+pub fn parse_attribute_block <'a> (
+    block: &'a str,
+    ignore: &[&str]
+) -> Result<AttributeBlock<'a>, ParseError> {
+    let mut lines = block.lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line.strip_suffix('\r').unwrap_or(line)))
+        .filter(|(_, line)| !line.trim().is_empty());
+
+    let (header_line, header) = lines.next()
+        .ok_or(ParseError::new(ParseErrorKind::MissingHeader, 1, 1, ""))?;
+
+    // Values are unquoted once all of their continuation lines are in
+    let mut raw_attributes: Vec<(Attribute, &str)> = Vec::new();
+    for (line_number, line) in lines {
+        if ignore.iter().any(|prefix| line.starts_with(prefix)) {
+            continue;
+        }
+
+        if let Some(continuation) = line.strip_prefix('\t') {
+            let (attribute, _) = raw_attributes.last_mut()
+                .ok_or(ParseError::new(ParseErrorKind::OrphanContinuation, line_number, 1, line))?;
+            attribute.value.push_str(continuation);
+            continue;
+        }
+
+        let indent = line.len() - line.trim_ascii_start().len();
+        let field = line.trim_ascii_start();
+        let (name, value) = field.split_once(" = ")
+            .or_else(|| field.strip_suffix(" =").map(|name| (name, "")))
+            .ok_or(ParseError::new(ParseErrorKind::MissingSeparator, line_number, indent + 1, line))?;
+        if name.trim().is_empty() {
+            return Err(ParseError::new(ParseErrorKind::EmptyName, line_number, indent + 1, line));
+        }
+
+        raw_attributes.push((
+            Attribute {
+                name: name.trim_end(),
+                value: value.to_string(),
+                line: line_number,
+                column: indent + name.len() + " = ".len() + 1
+            },
+            line
+        ));
+    }
+
+    let attributes = raw_attributes.into_iter()
+        .map(|(mut attribute, line)| {
+            attribute.value = unquote(&attribute.value, attribute.line, attribute.column, line)?;
+            Ok(attribute)
+        })
+        .collect::<Result<Vec<_>, ParseError>>()?;
+
+    Ok(AttributeBlock {
+        header,
+        header_line,
+        attributes
+    })
+}
+
+/// Parses the whitespace-separated `name=value` fields of an
+///  accounting record (the part after `date;state;id;`), where
+///  values may be quoted to contain whitespace.
+///
+/// Fields without an `=` are kept with an empty value.
+pub fn parse_record_fields ( record: &str ) -> Result<Vec<(&str, String)>, ParseError> {
+    let mut fields = Vec::new();
+    let mut chars = record.char_indices().peekable();
+
+    while let Some(&(start, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+
+        // Find the end of the field, skipping over quoted sections
+        let mut in_quotes: Option<usize> = None;
+        let mut end = record.len();
+        let mut escaped = false;
+        while let Some(&(i, c)) = chars.peek() {
+            if in_quotes.is_some() {
+                if escaped {
+                    escaped = false;
+                } else if c == '\\' {
+                    escaped = true;
+                } else if c == '"' {
+                    in_quotes = None;
+                }
+            } else if c == '"' {
+                in_quotes = Some(i);
+            } else if c.is_whitespace() {
+                end = i;
+                break;
+            }
+            chars.next();
+        }
+        if let Some(quote_start) = in_quotes {
+            return Err(ParseError::new(ParseErrorKind::UnterminatedQuote, 1, record[..quote_start].chars().count() + 1, record));
+        }
+
+        let field = &record[start..end];
+        let column = record[..start].chars().count() + 1;
+        let (name, value) = field.split_once('=')
+            .unwrap_or((field, ""));
+        if name.is_empty() {
+            return Err(ParseError::new(ParseErrorKind::EmptyName, 1, column, record));
+        }
+
+        let value_column = column + name.chars().count() + 1;
+        fields.push((name, unquote(value, 1, value_column, record)?));
+    }
+
+    Ok(fields)
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    const JOBSTAT: &str = include_str!("../../tests/fixtures/jobstat.txt");
+    const JMANL: &str = include_str!("../../tests/fixtures/jmanl.txt");
+
+    /// The job blocks of `jobstat` output, split like the jobs daemon does
+    pub(in crate::parsing) fn jobstat_blocks ( ) -> Vec<&'static str> {
+        JOBSTAT.split("--------------------\n")
+            .nth(1)
+            .unwrap()
+            .split("\n\n")
+            .collect()
+    }
+    /// The raw records of `jmanl` output
+    pub(in crate::parsing) fn jmanl_records ( ) -> Vec<&'static str> {
+        JMANL.split("Raw records::\n")
+            .nth(1)
+            .unwrap()
+            .lines()
+            .filter(|line| !line.is_empty())
+            .collect()
+    }
+    fn value <'a> ( block: &'a AttributeBlock, name: &str ) -> &'a str {
+        &block.attributes.iter()
+            .find(|attribute| attribute.name == name)
+            .unwrap_or_else(|| panic!("No `{name}` attribute"))
+            .value
+    }
+
+    #[test]
+    fn parses_jobstat_fixture () {
+        let blocks = jobstat_blocks()
+            .into_iter()
+            .map(|block| parse_attribute_block(block, &["Nodes: "]).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            blocks.iter().map(|block| block.header).collect::<Vec<_>>(),
+            ["12345.cm-pbs-01", "12346.cm-pbs-01", "12350[].cm-pbs-01", "12350[7].cm-pbs-01"]
+        );
+
+        let running = &blocks[0];
+        assert_eq!(running.attributes.len(), 20);
+        assert_eq!(value(running, "Job_Owner"), "alice@login01.cluster");
+        assert_eq!(value(running, "exec_vnode"), "(node017:ncpus=16:mem=33554432kb)+(node018:ncpus=16:mem=33554432kb)");
+        assert_eq!(
+            value(running, "Variable_List"),
+            "PBS_O_HOME=/home/alice,PBS_O_LANG=en_US.UTF-8,PBS_O_LOGNAME=alice,PBS_O_PATH=/usr/local/bin:/usr/bin:/bin,PBS_O_WORKDIR=/home/alice/sim,PBS_O_QUEUE=workq"
+        );
+        assert_eq!(value(running, "Submit_arguments"), "-N sim-run-7 \"run.pbs\"");
+        assert_eq!(value(running, "comment"), "Job run at Mon Jan 06 at 10:15 on (node017:ncpus=16)+(node018:ncpus=16)");
+        assert!(running.attributes.iter().all(|attribute| !attribute.name.starts_with("Nodes")));
+
+        assert_eq!(value(&blocks[2], "array_indices_submitted"), "1-99:2");
+    }
+
+    #[test]
+    fn joins_tab_continuations () {
+        let block = "1.server\n    exec_vnode = (node1:ncpus=4)+\n\t(node2:ncpus=4)+\n\t(node3:ncpus=4)\n    queue = workq\n";
+        let block = parse_attribute_block(block, &[]).unwrap();
+        assert_eq!(block.attributes.len(), 2);
+        assert_eq!(block.attributes[0].value, "(node1:ncpus=4)+(node2:ncpus=4)+(node3:ncpus=4)");
+        assert_eq!((block.attributes[0].line, block.attributes[0].column), (2, 18));
+        assert_eq!((block.attributes[1].line, block.attributes[1].value.as_str()), (5, "workq"));
+
+        // Continuations are kept verbatim, including leading spaces
+        let block = parse_attribute_block("1.server\n    Variable_List = A=1,B=\n\t two\n", &[]).unwrap();
+        assert_eq!(block.attributes[0].value, "A=1,B= two");
+    }
+
+    #[test]
+    fn unquotes_values () {
+        let block = "1.server\n    comment = \"Not Running: \\\"gpu\\\" busy\"\n    empty = \"\"\n    bare =\n    Job_Name = \"split\n\tname\"\n";
+        let block = parse_attribute_block(block, &[]).unwrap();
+        assert_eq!(block.attributes[0].value, "Not Running: \"gpu\" busy");
+        assert_eq!(block.attributes[1].value, "");
+        assert_eq!(block.attributes[2].value, "");
+        // Quotes are closed after any continuation lines
+        assert_eq!(block.attributes[3].value, "splitname");
+    }
+
+    #[test]
+    fn locates_block_errors () {
+        for (block, kind, line, column, text) in [
+            ("", ParseErrorKind::MissingHeader, 1, 1, ""),
+            ("\n  \n", ParseErrorKind::MissingHeader, 1, 1, ""),
+            ("1.server\n    queue = workq\n    broken\n", ParseErrorKind::MissingSeparator, 3, 5, "    broken"),
+            ("\n\n1.server\n  queue=workq\n", ParseErrorKind::MissingSeparator, 4, 3, "  queue=workq"),
+            ("1.server\n     = value\n", ParseErrorKind::MissingSeparator, 2, 6, "     = value"),
+            ("1.server\n    \t = value\n", ParseErrorKind::MissingSeparator, 2, 7, "    \t = value"),
+            ("1.server\n    \u{a0} = value\n", ParseErrorKind::EmptyName, 2, 5, "    \u{a0} = value"),
+            ("1.server\n\tcontinued\n", ParseErrorKind::OrphanContinuation, 2, 1, "\tcontinued"),
+            ("1.server\n    a = 1\n    comment = \"open\n", ParseErrorKind::UnterminatedQuote, 3, 15, "    comment = \"open"),
+            ("1.server\n    comment = \"ends \\\"\n", ParseErrorKind::UnterminatedQuote, 2, 15, "    comment = \"ends \\\"")
+        ] {
+            let e = parse_attribute_block(block, &[]).unwrap_err();
+            assert_eq!((e.kind, e.line, e.column, e.text.as_str()), (kind, line, column, text), "{block:?}");
+        }
+    }
+
+    #[test]
+    fn skips_blank_and_ignored_lines () {
+        let block = "\r\n12.server\r\n    queue = workq\r\n\r\nNodes: node1\r\n    Job_Name = x\r\n";
+        let block = parse_attribute_block(block, &["Nodes: "]).unwrap();
+        assert_eq!((block.header, block.header_line), ("12.server", 2));
+        assert_eq!(
+            block.attributes.iter().map(|attribute| (attribute.name, attribute.value.as_str(), attribute.line)).collect::<Vec<_>>(),
+            [("queue", "workq", 3), ("Job_Name", "x", 6)]
+        );
+    }
+
+    #[test]
+    fn parses_jmanl_fixture () {
+        let records = jmanl_records();
+        assert_eq!(records.len(), 3);
+
+        for record in records {
+            let fields_start = record.match_indices(';').nth(2).unwrap().0 + 1;
+            let fields = parse_record_fields(&record[fields_start..]).unwrap();
+            assert!(fields.iter().any(|(name, _)| *name == "end"), "{record}");
+        }
+
+        let record = jmanl_records()[1];
+        let fields = parse_record_fields(record.splitn(4, ';').nth(3).unwrap()).unwrap();
+        assert!(fields.contains(&("jobname", String::from("long name; with spaces"))));
+        assert!(fields.contains(&("Resource_List.walltime", String::from("1:00:00:00"))));
+    }
+
+    #[test]
+    fn locates_record_errors () {
+        for (record, kind, column) in [
+            ("user=a jobname=\"open", ParseErrorKind::UnterminatedQuote, 16),
+            ("user=a =b", ParseErrorKind::EmptyName, 8),
+            ("user=\"é\" =b", ParseErrorKind::EmptyName, 10),
+            ("a=\"x\\\"", ParseErrorKind::UnterminatedQuote, 3)
+        ] {
+            let e = parse_record_fields(record).unwrap_err();
+            assert_eq!((e.kind, e.line, e.column, e.text.as_str()), (kind, 1, column, record), "{record:?}");
+        }
+
+        assert_eq!(
+            parse_record_fields("  flag a=\"x y\" b=").unwrap(),
+            [("flag", String::new()), ("a", String::from("x y")), ("b", String::new())]
+        );
+    }
+
+    /// An attribute value, as the pieces PBS wraps it into
+    fn wrapped_value ( ) -> impl Strategy<Value = Vec<String>> {
+        // Continuations can't be blank (which would be skipped), and the
+        //  whole value can't open a quote (which would be unquoted)
+        ("([!-~][ -~]{0,30})?", prop::collection::vec("[!-~][ -~]{0,30}", 0..4))
+            .prop_map(|(first, rest)| std::iter::once(first).chain(rest).collect::<Vec<String>>())
+            .prop_filter("Quoted values are unquoted", |pieces| !pieces.concat().starts_with('"'))
+    }
+
+    proptest! {
+        #[test]
+        fn round_trips_attribute_blocks (
+            header in "[0-9]{1,8}(\\[[0-9]{0,3}\\])?\\.[a-z0-9-]{1,12}",
+            attributes in prop::collection::vec(("[A-Za-z_][A-Za-z0-9_.]{0,20}", 1usize..8, wrapped_value()), 0..12)
+        ) {
+            let mut block = format!("{header}\n");
+            for (name, indent, pieces) in &attributes {
+                block += &format!("{}{name} = {}\n", " ".repeat(*indent), pieces[0]);
+                for piece in &pieces[1..] {
+                    block += &format!("\t{piece}\n");
+                }
+            }
+
+            let parsed = parse_attribute_block(&block, &[]).unwrap();
+            prop_assert_eq!(parsed.header, header.as_str());
+            prop_assert_eq!(parsed.attributes.len(), attributes.len());
+            for (attribute, (name, _, pieces)) in parsed.attributes.iter().zip(&attributes) {
+                prop_assert_eq!(attribute.name, name.as_str());
+                prop_assert_eq!(&attribute.value, &pieces.concat());
+            }
+        }
+
+        #[test]
+        fn round_trips_record_fields (
+            fields in prop::collection::vec(("[A-Za-z_][A-Za-z0-9_.]{0,20}", prop_oneof![
+                "[a-zA-Z0-9_.:/,()+*=-]{0,20}".prop_map(|value| (value.clone(), value)),
+                "[ -\\[\\]-~]{0,20}".prop_map(|value| (format!("\"{}\"", value.replace('"', "\\\"")), value))
+            ]), 0..12),
+            separator in "[ \t]{1,3}"
+        ) {
+            let record = fields.iter()
+                .map(|(name, (raw, _))| format!("{name}={raw}"))
+                .collect::<Vec<_>>()
+                .join(&separator);
+
+            let parsed = parse_record_fields(&record).unwrap();
+            prop_assert_eq!(parsed.len(), fields.len());
+            for ((name, value), (expected_name, (_, expected_value))) in parsed.iter().zip(&fields) {
+                prop_assert_eq!(*name, expected_name.as_str());
+                prop_assert_eq!(value, expected_value);
+            }
+        }
+
+        #[test]
+        fn never_panics_on_blocks ( block in "(\\PC|[\t\r\n\"= ]){0,200}" ) {
+            if let Err(e) = parse_attribute_block(&block, &["Nodes: "]) {
+                prop_assert!(e.line >= 1 && e.column >= 1);
+                prop_assert!(e.line <= block.lines().count().max(1));
+            }
+        }
+
+        #[test]
+        fn never_panics_on_records ( record in "(\\PC|[\t\"=\\\\ ]){0,200}" ) {
+            if let Err(e) = parse_record_fields(&record) {
+                prop_assert_eq!(e.line, 1);
+                prop_assert!(e.column >= 1 && e.column <= record.chars().count());
+            }
+        }
+    }
+}
@@ -0,0 +1,289 @@
+use std::{fmt::Display, str::FromStr};
+
+/// A PBS value which couldn't be parsed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnitError {
+    /// What the value should have been, i.e. `memory`
+    pub kind: &'static str,
+    pub input: String
+}
+impl Display for UnitError {
+    fn fmt ( &self, f: &mut std::fmt::Formatter<'_> ) -> std::fmt::Result {
+        write!(f, "Invalid {} `{}`", self.kind, self.input)
+    }
+}
+impl std::error::Error for UnitError {}
+
+/// The size of a PBS word, in bytes
+const WORD_BYTES: u64 = 8;
+const GB_BYTES: f64 = (1u64 << 30) as f64;
+
+/// An amount of memory, parsed from a PBS size such as `50gb`, `1024kb`,
+///  `4tb`, `512mw` (words) or `1048576` (bytes).
+///
+/// Like PBS, units are powers of 1024 and case insensitive.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Memory {
+    bytes: u64
+}
+impl Memory {
+    pub fn gb ( &self ) -> f64 {
+        self.bytes as f64 / GB_BYTES
+    }
+    /// How much of `total` this is, as a percentage
+    pub fn percent_of ( &self, total: Memory ) -> f64 {
+        self.bytes as f64 * 100f64 / total.bytes.max(1) as f64
+    }
+}
+impl FromStr for Memory {
+    type Err = UnitError;
+
+    fn from_str ( st: &str ) -> Result<Self, Self::Err> {
+        let error = || UnitError { kind: "memory", input: st.to_string() };
+
+        let lowercase = st.trim().to_ascii_lowercase();
+        let unit_start = lowercase.find(|c: char| !c.is_ascii_digit() && c != '.')
+            .unwrap_or(lowercase.len());
+        let (amount, unit) = lowercase.split_at(unit_start);
+
+        let amount = amount.parse::<f64>()
+            .map_err(|_| error())?;
+        let (scale, unit_bytes) = match unit.chars().next() {
+            Some(prefix @ ('k' | 'm' | 'g' | 't' | 'p')) => (
+                match prefix {
+                    'k' => 1u64 << 10,
+                    'm' => 1 << 20,
+                    'g' => 1 << 30,
+                    't' => 1 << 40,
+                    _ => 1 << 50
+                },
+                &unit[1..]
+            ),
+            _ => (1, unit)
+        };
+        let unit_bytes = match unit_bytes {
+            "" | "b" => 1,
+            "w" => WORD_BYTES,
+            _ => return Err(error())
+        };
+
+        // Casting would quietly saturate sizes beyond a `u64`
+        let bytes = amount * (scale * unit_bytes) as f64;
+        if !bytes.is_finite() || bytes >= u64::MAX as f64 {
+            return Err(error());
+        }
+
+        Ok(Self {
+            bytes: bytes as u64
+        })
+    }
+}
+
+/// A length of time, parsed from a PBS duration of `[[[days:]hours:]minutes:]seconds`,
+///  where the seconds may be fractional.
+///
+/// Displayed as `HH:MM:SS`, where the hours may exceed a day.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Duration {
+    seconds: u64
+}
+impl Duration {
+    pub fn from_seconds ( seconds: u64 ) -> Self {
+        Self { seconds }
+    }
+    pub fn seconds ( &self ) -> u64 {
+        self.seconds
+    }
+    pub fn hours ( &self ) -> f64 {
+        self.seconds as f64 / 3600f64
+    }
+    /// How much of `total` this is, as a percentage
+    pub fn percent_of ( &self, total: Duration ) -> f64 {
+        self.seconds as f64 * 100f64 / total.seconds.max(1) as f64
+    }
+    /// Formats the duration as `DD:HH:MM:SS`
+    pub fn with_days ( &self ) -> String {
+        format!(
+            "{:02}:{:02}:{:02}:{:02}",
+            self.seconds / 86400,
+            self.seconds / 3600 % 24,
+            self.seconds / 60 % 60,
+            self.seconds % 60
+        )
+    }
+}
+impl std::ops::Add for Duration {
+    type Output = Duration;
+
+    fn add ( self, other: Duration ) -> Duration {
+        Duration::from_seconds(self.seconds.saturating_add(other.seconds))
+    }
+}
+impl FromStr for Duration {
+    type Err = UnitError;
+
+    fn from_str ( st: &str ) -> Result<Self, Self::Err> {
+        let error = || UnitError { kind: "duration", input: st.to_string() };
+
+        let parts = st.trim().split(':').collect::<Vec<&str>>();
+        if parts.len() > 4 {
+            return Err(error());
+        }
+
+        let (seconds, larger_parts) = parts.split_last()
+            .ok_or_else(error)?;
+        let seconds = seconds.parse::<f64>()
+            .ok()
+            .filter(|seconds| seconds.is_finite() && *seconds >= 0f64 && *seconds < u64::MAX as f64)
+            .ok_or_else(error)?;
+
+        // Minutes, hours, then days, where too many of any is an error
+        //  rather than a panic (or a wrapped total in release builds)
+        let mut total = seconds as u64;
+        for (part, scale) in larger_parts.iter().rev().zip([60, 60 * 60, 60 * 60 * 24]) {
+            total = part.parse::<u64>()
+                .ok()
+                .and_then(|part| part.checked_mul(scale))
+                .and_then(|part| total.checked_add(part))
+                .ok_or_else(error)?;
+        }
+
+        Ok(Self { seconds: total })
+    }
+}
+impl Display for Duration {
+    fn fmt ( &self, f: &mut std::fmt::Formatter<'_> ) -> std::fmt::Result {
+        write!(
+            f,
+            "{:02}:{:02}:{:02}",
+            self.seconds / 3600,
+            self.seconds / 60 % 60,
+            self.seconds % 60
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_memory () {
+        const KB: u64 = 1 << 10;
+        const MB: u64 = 1 << 20;
+        const GB: u64 = 1 << 30;
+        const TB: u64 = 1 << 40;
+
+        for (input, bytes) in [
+            ("0", 0),
+            ("1048576", MB),
+            (" 512 ", 512),
+            ("512b", 512),
+            ("512B", 512),
+            ("1kb", KB),
+            ("1KB", KB),
+            ("1k", KB),
+            ("1K", KB),
+            ("1048576kb", GB),
+            ("3mb", 3 * MB),
+            ("3MB", 3 * MB),
+            ("3m", 3 * MB),
+            ("2gb", 2 * GB),
+            ("2Gb", 2 * GB),
+            ("2g", 2 * GB),
+            ("2G", 2 * GB),
+            ("4tb", 4 * TB),
+            ("4TB", 4 * TB),
+            ("4t", 4 * TB),
+            ("1pb", 1 << 50),
+            ("1w", WORD_BYTES),
+            ("1W", WORD_BYTES),
+            ("2kw", 2 * KB * WORD_BYTES),
+            ("2KW", 2 * KB * WORD_BYTES),
+            ("512mw", 512 * MB * WORD_BYTES),
+            ("1gw", GB * WORD_BYTES),
+            ("1tw", TB * WORD_BYTES),
+            ("1.5gb", 3 * GB / 2),
+            ("0.5k", 512)
+        ] {
+            assert_eq!(input.parse::<Memory>().map(|memory| memory.bytes), Ok(bytes), "`{input}`");
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_memory () {
+        for input in ["", "gb", "b", "-1gb", "10 gb", "10xb", "10gbb", "10bw", "10kbx", "1.2.3gb", "1e3kb", "."] {
+            assert_eq!(
+                input.parse::<Memory>(),
+                Err(UnitError { kind: "memory", input: input.to_string() }),
+                "`{input}`"
+            );
+        }
+    }
+
+    #[test]
+    fn measures_memory () {
+        let memory = "512mb".parse::<Memory>().unwrap();
+        assert_eq!(memory.gb(), 0.5);
+        assert_eq!(memory.percent_of("2gb".parse().unwrap()), 25f64);
+        // An empty reservation doesn't divide by zero
+        assert_eq!(Memory::default().percent_of(Memory::default()), 0f64);
+    }
+
+    #[test]
+    fn parses_durations () {
+        for (input, seconds) in [
+            ("0", 0),
+            ("45", 45),
+            ("45.9", 45),
+            ("0.5", 0),
+            ("5:07", 5 * 60 + 7),
+            ("90:00", 90 * 60),
+            ("01:02:03", 3600 + 2 * 60 + 3),
+            ("1:2:3", 3600 + 2 * 60 + 3),
+            ("100:00:00", 100 * 3600),
+            ("00:00:59.99", 59),
+            (" 12:00:00 ", 12 * 3600),
+            ("2:01:02:03", 2 * 86400 + 3600 + 2 * 60 + 3),
+            ("00:00:00:00", 0),
+            ("1:00:00:00.5", 86400)
+        ] {
+            assert_eq!(input.parse::<Duration>().map(|duration| duration.seconds()), Ok(seconds), "`{input}`");
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_durations () {
+        for input in ["", ":", "::5", "a", "-1", "inf", "NaN", "1:2:3:4:5", "01:-2:00", "01:02:xx", "1:2.5:00", "1.5:00:00", "01:02:03 extra"] {
+            assert_eq!(
+                input.parse::<Duration>(),
+                Err(UnitError { kind: "duration", input: input.to_string() }),
+                "`{input}`"
+            );
+        }
+    }
+
+    #[test]
+    fn formats_durations () {
+        let duration = Duration::from_seconds(93784);
+        assert_eq!(duration.to_string(), "26:03:04");
+        assert_eq!(duration.with_days(), "01:02:03:04");
+        assert_eq!(Duration::default().to_string(), "00:00:00");
+        assert_eq!((duration + Duration::from_seconds(56)).to_string(), "26:04:00");
+        assert_eq!(Duration::from_seconds(1800).hours(), 0.5);
+        assert_eq!(Duration::from_seconds(30).percent_of(Duration::from_seconds(120)), 25f64);
+        assert_eq!(Duration::from_seconds(30).percent_of(Duration::default()), 3000f64);
+    }
+
+    #[test]
+    fn rejects_overflowing_durations () {
+        for input in ["999999999999999999:00:00:00", "213503982334602:00:00:00", "213503982334601:23:59:59", "0:0:307445734561825861:0", "18446744073709551615:00", "1e30"] {
+            assert!(input.parse::<Duration>().is_err(), "`{input}`");
+        }
+        assert!("1e400gb".parse::<Memory>().is_err());
+        assert!("99999999999pb".parse::<Memory>().is_err());
+
+        let most = Duration::from_seconds(u64::MAX);
+        assert_eq!((most + Duration::from_seconds(1)).seconds(), u64::MAX);
+    }
+}
@@ -1,27 +1,24 @@
-use std::sync::Arc;
-
 use anyhow::{Result, Context, anyhow};
 use tracing::error;
 
-use crate::routes::AppState;
+use crate::routes::Cluster;
 
-#[tracing::instrument]
+#[tracing::instrument(skip(password))]
 pub async fn verify_login (
-    state:    &Arc<AppState>,
+    cluster:  &Cluster,
     username: &str,
     password: &str
 ) -> Result<bool> {
     // Verify the SSH session
-    state.verify_ssh_session().await
+    cluster.verify_ssh_session().await
         .context("Couldn't verify SSH session!")?;
 
     // Build our command
-    let session = state
-        .ssh_session
-        .read()
-        .await;
+    let session = cluster
+        .ssh_session()
+        .await?;
     let mut session_command = session
-        .command("/opt/metis/el8/contrib/admin/batchmon/verify_login.sh");
+        .command(&cluster.verify_login_script);
     session_command.arg(username);
     session_command.arg(password);
 
@@ -1,24 +1,35 @@
-use std::sync::Arc;
-
 use anyhow::{Context, Result, bail};
+use tracing::warn;
 
-use crate::routes::AppState;
+use crate::routes::Cluster;
 
 pub async fn remote_command (
-    state: &Arc<AppState>,
+    cluster: &Cluster,
 
     command: &str,
     args: Vec<&str>,
     use_script: bool
+) -> Result<String> {
+    remote_command_allowing(cluster, command, args, use_script, &[0]).await
+}
+/// Runs a command like `remote_command`, but also accepts the given
+///  non-zero exit codes (i.e. `getent` exits with 2 when some of the
+///  keys it was given don't exist, but still prints the others)
+pub async fn remote_command_allowing (
+    cluster: &Cluster,
+
+    command: &str,
+    args: Vec<&str>,
+    use_script: bool,
+    allowed_codes: &[i32]
 ) -> Result<String> {
     // Verify the SSH session
-    state.verify_ssh_session().await
+    cluster.verify_ssh_session().await
         .context("Couldn't verify SSH session!")?;
 
-    let session = state
-        .ssh_session
-        .read()
-        .await;
+    let session = cluster
+        .ssh_session()
+        .await?;
     let mut session_command = if !use_script {
         let mut session_command = session
             .command(command);
@@ -27,9 +38,12 @@ pub async fn remote_command (
         }
         session_command
     } else {
+        // `-e` returns the command's exit status rather than
+        //  `script`'s own, which is checked below
         let mut session_command = session
             .command("script");
         session_command.arg("-q");
+        session_command.arg("-e");
         session_command.arg("-c");
         session_command.arg(format!(
             "{command} {}",
@@ -51,9 +65,13 @@ pub async fn remote_command (
     let stderr = String::from_utf8(output.stderr)
         .context("Server `stderr` was not valid UTF-8")?;
 
-    // Treat any error output as fatal
+    // Only a failing exit status is fatal, as PBS commands
+    //  also print warnings to `stderr` when they succeed
+    if !output.status.code().is_some_and(|code| allowed_codes.contains(&code)) {
+        bail!("Server command `{command}` failed ({}) with `stderr`: {stderr}", output.status);
+    }
     if !stderr.is_empty() {
-        bail!("Server had `stderr`: {stderr}");
+        warn!("Server command `{command}` had `stderr`: {stderr}");
     }
 
     // Return as successful
@@ -0,0 +1,161 @@
+use axum::{
+    extract::{Form, Path, State}, http::StatusCode, response::{IntoResponse, Redirect}
+};
+use chrono::NaiveDate;
+use serde::Deserialize;
+use tower_sessions::Session;
+use std::sync::Arc;
+use tracing::{error, info, warn};
+
+use crate::{
+    daemons::backfill::{month_of, months_between},
+    routes::{pages::is_admin, AppState}
+};
+
+/// The logged in user, if they're an admin
+async fn admin_username (
+    app: &AppState,
+    session: &Session
+) -> Result<String, (StatusCode, String)> {
+    let username = session.get::<String>("username")
+        .await
+        .map_err(|e| {
+            error!(%e, "Couldn't get username from session!");
+            (StatusCode::UNAUTHORIZED, "Couldn't get username from session!".to_string())
+        })?
+        .ok_or((StatusCode::UNAUTHORIZED, "You are not logged in!".to_string()))?;
+
+    if !is_admin(app, &app.db.get_groups_cache().await, &username) {
+        warn!("[ Non-admin `{username}` tried to use an admin endpoint! ]");
+        return Err((StatusCode::FORBIDDEN, "Only admins may do this!".to_string()));
+    }
+
+    Ok(username)
+}
+
+#[derive(Deserialize, Debug)]
+pub struct BackfillRequest {
+    cluster: String,
+    /// Comma or whitespace separated, or empty for every known user
+    users: String,
+    /// `YYYY-MM-DD`
+    since: String
+}
+#[tracing::instrument]
+pub async fn start_backfill (
+    State(app): State<Arc<AppState>>,
+    session: Session,
+    Form(payload): Form<BackfillRequest>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let username = admin_username(&app, &session).await?;
+
+    if app.cluster(&payload.cluster).is_none() {
+        return Err((StatusCode::BAD_REQUEST, "Unknown cluster!".to_string()));
+    }
+    let since = NaiveDate::parse_from_str(payload.since.trim(), "%Y-%m-%d")
+        .map_err(|_| (StatusCode::BAD_REQUEST, "Invalid date, expected `YYYY-MM-DD`!".to_string()))?;
+    let users = payload.users
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|user| !user.is_empty())
+        .map(|user| user.to_string())
+        .collect::<Vec<String>>();
+    if users.iter().any(|user| !user.chars().all(|c| c.is_ascii_alphanumeric() || "._-".contains(c))) {
+        return Err((StatusCode::BAD_REQUEST, "Invalid username!".to_string()));
+    }
+
+    let first_month = month_of(chrono::Utc::now().with_timezone(&app.config.site.timezone).date_naive());
+    if since > first_month {
+        return Err((StatusCode::BAD_REQUEST, "The backfill date must not be after this month!".to_string()));
+    }
+    let run_id = app.db
+        .create_backfill_run(
+            &payload.cluster,
+            &users,
+            &since.to_string(),
+            &first_month.to_string(),
+            months_between(since, first_month),
+            &username
+        )
+        .await
+        .map_err(|e| {
+            error!(%e, "Couldn't create backfill run!");
+            (StatusCode::INTERNAL_SERVER_ERROR, "Couldn't create backfill run!".to_string())
+        })?;
+    info!("[ `{username}` queued backfill {run_id} on {} back to {since} ]", payload.cluster);
+
+    Ok(Redirect::to(&(app.config.server.url_prefix.clone() + "/admin/backfill?started=true")))
+}
+#[tracing::instrument]
+pub async fn cancel_backfill (
+    State(app): State<Arc<AppState>>,
+    Path(run_id): Path<i64>,
+    session: Session,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let username = admin_username(&app, &session).await?;
+
+    let cancelled = app.db
+        .cancel_backfill_run(run_id)
+        .await
+        .map_err(|e| {
+            error!(%e, "Couldn't cancel backfill run!");
+            (StatusCode::INTERNAL_SERVER_ERROR, "Couldn't cancel backfill run!".to_string())
+        })?;
+    if !cancelled {
+        return Err((StatusCode::NOT_FOUND, "No running backfill with that ID!".to_string()));
+    }
+    info!("[ `{username}` cancelled backfill {run_id} ]");
+
+    Ok(Redirect::to(&(app.config.server.url_prefix.clone() + "/admin/backfill")))
+}
+
+/// What to do with a daemon
+#[derive(Debug, Clone, Copy)]
+enum DaemonAction {
+    Run,
+    Pause,
+    Resume
+}
+async fn control_daemon (
+    app: &AppState,
+    session: &Session,
+    id: usize,
+    action: DaemonAction
+) -> Result<Redirect, (StatusCode, String)> {
+    let username = admin_username(app, session).await?;
+
+    let daemon = app.daemons
+        .daemon(id)
+        .ok_or((StatusCode::NOT_FOUND, "No daemon with that ID!".to_string()))?;
+    match action {
+        DaemonAction::Run => daemon.run_now(),
+        DaemonAction::Pause => daemon.pause(),
+        DaemonAction::Resume => daemon.resume()
+    }
+    info!("[ `{username}` used {action:?} on the {} daemon ]", daemon.name);
+
+    Ok(Redirect::to(&(app.config.server.url_prefix.clone() + "/admin/daemons")))
+}
+#[tracing::instrument]
+pub async fn run_daemon (
+    State(app): State<Arc<AppState>>,
+    Path(id): Path<usize>,
+    session: Session,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    control_daemon(&app, &session, id, DaemonAction::Run).await
+}
+#[tracing::instrument]
+pub async fn pause_daemon (
+    State(app): State<Arc<AppState>>,
+    Path(id): Path<usize>,
+    session: Session,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    control_daemon(&app, &session, id, DaemonAction::Pause).await
+}
+#[tracing::instrument]
+pub async fn resume_daemon (
+    State(app): State<Arc<AppState>>,
+    Path(id): Path<usize>,
+    session: Session,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    control_daemon(&app, &session, id, DaemonAction::Resume).await
+}
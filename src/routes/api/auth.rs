@@ -7,14 +7,27 @@ use std::sync::Arc;
 use tokio::task::JoinSet;
 use tracing::{error, warn};
 
-use crate::{daemons::{groups::grab_group_thread, jobs::grab_old_jobs_thread}, routes::AppState};
+use crate::{daemons::{groups::grab_group_thread, jobs::grab_old_jobs_thread}, db::lib::AuditAction, routes::AppState};
 
-#[derive(Deserialize, Debug)]
+/// How much of a failed login's username is audited, as it's
+///  whatever the client sent rather than a real account
+const MAX_AUDITED_USERNAME_LENGTH: usize = 64;
+
+#[derive(Deserialize)]
 pub struct LoginRequest {
     one: String, // Username (labelled as "one" to avoid autofill)
     two: String, // Password (labelled as "two" to avoid autofill)
 }
-#[tracing::instrument]
+// Never log the password
+impl std::fmt::Debug for LoginRequest {
+    fn fmt ( &self, f: &mut std::fmt::Formatter<'_> ) -> std::fmt::Result {
+        f.debug_struct("LoginRequest")
+            .field("one", &self.one)
+            .field("two", &"<redacted>")
+            .finish()
+    }
+}
+#[tracing::instrument(skip(app, session, payload), fields(username = %payload.one))]
 pub async fn login (
     State(app): State<Arc<AppState>>,
     session: Session,
@@ -27,10 +40,12 @@ pub async fn login (
     let username = &payload.one;
     let password = &payload.two;
 
+    // Accounts are shared across clusters, so logins are
+    //  verified against the primary cluster
     let login_result = app
         .db
         .login(
-            &app,
+            app.primary_cluster(),
             username,
             password
         )
@@ -45,12 +60,18 @@ pub async fn login (
         let mut tasks = JoinSet::new();
         
         tasks.spawn(grab_group_thread(app.clone(), username.to_string()));
-        tasks.spawn(grab_old_jobs_thread(app.clone(), username.to_string()));
+        for cluster in app.clusters.iter() {
+            let (app, cluster, username) = (app.clone(), cluster.clone(), username.to_string());
+            tasks.spawn(async move {
+                grab_old_jobs_thread(app, cluster, username).await
+                    .map(|_| ())
+            });
+        }
         
         tasks.join_all().await;
     }
 
-    let url_prefix = app.url_prefix.clone();
+    let url_prefix = app.config.server.url_prefix.clone();
     
     match login_result.success {
         true => {
@@ -64,28 +85,40 @@ pub async fn login (
                     error!(%e, "Couldn't insert username into session!");
                     (StatusCode::INTERNAL_SERVER_ERROR, "Couldn't insert username into session!".to_string())
                 })?;
-
-            drop(login_result);
+            app.audit(username, AuditAction::Login, None, None).await;
 
             Ok(Redirect::to(&(url_prefix + "/")))
         },
         false => {
-            drop(login_result);
-
             // If not verified or an error, you can respond with an error page/JSON
             // Here we'll just return a plain text error
             warn!("[ Invalid login! ]");
+            let audited_username = username.chars()
+                .take(MAX_AUDITED_USERNAME_LENGTH)
+                .collect::<String>();
+            app.audit(&audited_username, AuditAction::LoginFailed, None, None).await;
             Ok(Redirect::to(&(url_prefix + "/login?invalid=true")))
         }
     }
 }
 
 pub async fn logout (
+    State(app): State<Arc<AppState>>,
     session: Session,
 ) -> Result<(), (StatusCode, String)> {
+    // Read before the session's gone
+    let username = session.get::<String>("username")
+        .await
+        .ok()
+        .flatten();
+
     // Clear the entire session
     match session.delete().await {
         Ok(_) => {
+            if let Some(username) = username {
+                app.audit(&username, AuditAction::Logout, None, None).await;
+            }
+
             Ok(())
         },
         Err(_) => {
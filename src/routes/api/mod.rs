@@ -1 +1,3 @@
-pub mod auth;
\ No newline at end of file
+pub mod admin;
+pub mod auth;
+pub mod settings;
\ No newline at end of file
@@ -0,0 +1,49 @@
+use axum::{
+    extract::{Form, State}, http::StatusCode, response::{IntoResponse, Redirect}
+};
+use chrono_tz::Tz;
+use serde::Deserialize;
+use tower_sessions::Session;
+use std::sync::Arc;
+use tracing::{error, warn};
+
+use crate::routes::AppState;
+
+#[derive(Deserialize, Debug)]
+pub struct SettingsRequest {
+    /// An IANA timezone name, or empty for the site's
+    timezone: String
+}
+#[tracing::instrument]
+pub async fn update (
+    State(app): State<Arc<AppState>>,
+    session: Session,
+    Form(payload): Form<SettingsRequest>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let username = session.get::<String>("username")
+        .await
+        .map_err(|e| {
+            error!(%e, "Couldn't get username from session!");
+            (StatusCode::UNAUTHORIZED, "Couldn't get username from session!".to_string())
+        })?
+        .ok_or((StatusCode::UNAUTHORIZED, "You are not logged in!".to_string()))?;
+
+    let timezone = match payload.timezone.trim() {
+        "" => None,
+        timezone => Some(timezone.parse::<Tz>()
+            .map_err(|e| {
+                warn!(%e, "Invalid timezone!");
+                (StatusCode::BAD_REQUEST, "Invalid timezone!".to_string())
+            })?)
+    };
+
+    app.db
+        .set_user_timezone(&username, timezone)
+        .await
+        .map_err(|e| {
+            error!(%e, "Couldn't set user timezone!");
+            (StatusCode::INTERNAL_SERVER_ERROR, "Couldn't set user timezone!".to_string())
+        })?;
+
+    Ok(Redirect::to(&(app.config.server.url_prefix.clone() + "/settings?saved=true")))
+}
@@ -0,0 +1,119 @@
+use std::{sync::Arc, time::Duration};
+
+use anyhow::Context;
+use axum::{extract::State, http::StatusCode, Json};
+use serde::Serialize;
+use tracing::warn;
+
+use super::AppState;
+
+/// How long the SSH and DB checks may take before they fail
+const CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Serialize, Debug)]
+pub struct Health {
+    status: &'static str
+}
+/// Whether the process is alive, for liveness probes
+pub async fn healthz ( ) -> Json<Health> {
+    Json(Health { status: "ok" })
+}
+
+/// Why a readiness check failed
+struct Failure {
+    /// Returned by `/readyz`, so mustn't mention hosts or paths
+    summary: String,
+    /// Only logged, as it can
+    error: String
+}
+impl Failure {
+    fn new ( summary: &str, error: String ) -> Self {
+        Self { summary: summary.to_string(), error }
+    }
+    /// A failure whose summary says all there is to log
+    fn public ( summary: String ) -> Self {
+        Self { error: summary.clone(), summary }
+    }
+}
+/// One of the checks behind `/readyz`
+#[derive(Serialize, Debug)]
+pub struct Check {
+    name: String,
+    /// `ok` or `failed`
+    status: &'static str,
+    detail: String
+}
+impl Check {
+    fn new ( name: String, result: Result<String, Failure> ) -> Self {
+        match result {
+            Ok(detail) => Self { name, status: "ok", detail },
+            Err(Failure { summary, error }) => {
+                warn!("[ Readiness check `{name}` failed! {error} ]");
+                Self { name, status: "failed", detail: summary }
+            }
+        }
+    }
+}
+#[derive(Serialize, Debug)]
+pub struct Readiness {
+    ready: bool,
+    checks: Vec<Check>
+}
+/// Whether Hawkeye can serve fresh data, for readiness probes and uptime
+///  monitors. Checks that the DB answers, and that each cluster's SSH
+///  session is alive and its jobs daemon has succeeded recently.
+#[tracing::instrument]
+pub async fn readyz (
+    State(app): State<Arc<AppState>>
+) -> (StatusCode, Json<Readiness>) {
+    let mut checks = Vec::new();
+
+    let db = match tokio::time::timeout(CHECK_TIMEOUT, app.db.ping()).await {
+        Ok(Ok(())) => Ok(String::from("Answered a query")),
+        Ok(Err(e)) => Err(Failure::new("Query failed", format!("{e:#}"))),
+        Err(_) => Err(Failure::public(format!("Didn't answer within {}s", CHECK_TIMEOUT.as_secs())))
+    };
+    checks.push(Check::new(String::from("db"), db));
+
+    let now = chrono::Utc::now().timestamp();
+    let stale_after = app.config.daemons.jobs_period * app.config.daemons.stale_after_periods;
+    for cluster in app.clusters.iter() {
+        // Only checked, as reconnecting is left to the daemons. The session
+        //  is only locked for writing to reconnect or close it, and a probe
+        //  waiting on that would queue behind it, so it counts as ready.
+        let ssh = match cluster.ssh_session.try_read() {
+            Ok(session) => {
+                let check = async {
+                    session.as_ref()
+                        .context("Session is closed")?
+                        .check().await
+                        .context("Session check failed")
+                };
+                match tokio::time::timeout(CHECK_TIMEOUT, check).await {
+                    Ok(Ok(())) => Ok(String::from("Session is alive")),
+                    Ok(Err(e)) => Err(Failure::new("Session check failed", format!("{e:#}"))),
+                    Err(_) => Err(Failure::public(format!("Session check didn't finish within {}s", CHECK_TIMEOUT.as_secs())))
+                }
+            },
+            Err(_) => Ok(String::from("Session is being reconnected"))
+        };
+        checks.push(Check::new(format!("ssh ({})", cluster.name), ssh));
+
+        let last_success = app.daemons
+            .jobs_daemon(&cluster.name)
+            .and_then(|daemon| daemon.status().last_success);
+        let jobs = match last_success {
+            Some(last_success) if now - last_success <= stale_after as i64 => Ok(format!("Last succeeded {}s ago", now - last_success)),
+            Some(last_success) => Err(Failure::public(format!("Last succeeded {}s ago, over the {stale_after}s allowed", now - last_success))),
+            None => Err(Failure::public(String::from("Hasn't succeeded yet")))
+        };
+        checks.push(Check::new(format!("jobs daemon ({})", cluster.name), jobs));
+    }
+
+    let ready = checks.iter().all(|check| check.status == "ok");
+
+    (
+        if ready { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE },
+        Json(Readiness { ready, checks })
+    )
+}
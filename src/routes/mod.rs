@@ -2,17 +2,21 @@ use std::sync::Arc;
 
 use axum::response::IntoResponse;
 use axum::http::StatusCode;
-use anyhow::{Result, Context, anyhow};
+use anyhow::{Result, Context, anyhow, bail};
 use openssh::Session;
 use tokio::io::AsyncReadExt;
 use axum::http::header;
-use tokio::sync::RwLock;
+use tokio::sync::{RwLock, RwLockReadGuard};
 use tracing::error;
 use backoff::ExponentialBackoff;
 
+use crate::{config::{ClusterConfig, Config}, daemons::supervisor::Supervisor, db::lib::AuditAction};
+
 
 pub mod api;
+pub mod health;
 pub mod pages;
+pub mod trace;
 
 #[derive(Debug, Clone, Copy)]
 pub struct ClusterStatus {
@@ -22,31 +26,86 @@ pub struct ClusterStatus {
     pub used_cpus:   u32,
     pub total_gpus:  u32,
     pub used_gpus:   u32,
+    pub running_jobs: u32,
+    pub queued_jobs:  u32,
 }
 
+/// A PBS cluster monitored by this deployment, reached over its own SSH session
 #[derive(Debug)]
-pub struct AppState {
+pub struct Cluster {
+    pub name: String,
     pub remote_username: String,
     pub remote_hostname: String,
-    pub db: super::DB,
-    pub url_prefix: String,
+    /// Regex matching the PBS server suffix of this cluster's job IDs
+    ///  (i.e. `cm-.+-.+` for IDs like `12345.cm-pbs-01`)
+    pub server_pattern: String,
+    pub verify_login_script: String,
+    /// The timezone PBS reports local times in
+    pub timezone: chrono_tz::Tz,
 
-    pub ssh_session: Arc<RwLock<Session>>,
+    /// `None` once closed on shutdown
+    pub ssh_session: RwLock<Option<Session>>,
     pub status:      RwLock<Option<ClusterStatus>>
 }
-impl AppState {
+impl Cluster {
+    pub async fn connect (
+        config: ClusterConfig
+    ) -> Result<Self> {
+        let ClusterConfig {
+            name,
+            remote_username,
+            remote_hostname,
+            server_pattern,
+            verify_login_script,
+            timezone
+        } = config;
+
+        let session = Session::connect_mux(
+            &format!("{remote_username}@{remote_hostname}"),
+            openssh::KnownHosts::Strict
+        ).await
+            .with_context(|| format!("Failed to connect to remote host for cluster `{name}`!"))?;
+
+        Ok(Self {
+            name,
+            remote_username,
+            remote_hostname,
+            server_pattern,
+            verify_login_script,
+            timezone,
+
+            ssh_session: RwLock::new(Some(session)),
+            status: RwLock::new(None)
+        })
+    }
+    /// The SSH session, unless it's been closed
+    pub async fn ssh_session ( &self ) -> Result<RwLockReadGuard<'_, Session>> {
+        RwLockReadGuard::try_map(self.ssh_session.read().await, |session| session.as_ref())
+            .map_err(|_| anyhow!("The SSH session for cluster `{}` is closed!", self.name))
+    }
+    /// Checks the SSH session, reconnecting it if the check fails
+    ///
+    /// The check is made under a read lock, so that commands on a healthy
+    ///  session run concurrently; the write lock is only taken to reconnect.
     pub async fn verify_ssh_session(&self) -> Result<()> {
+        let Err(e) = self.ssh_session().await?.check().await else {
+            return Ok(());
+        };
+        error!(%e, cluster = %self.name, "SSH session check failed, attempting to reconnect...");
+
         let mut session = self.ssh_session.write().await;
+        let Some(session) = session.as_mut() else {
+            bail!("The SSH session for cluster `{}` is closed!", self.name);
+        };
 
-        if let Err(e) = session.check().await {
-            error!(%e, "SSH session check failed, attempting to reconnect...");
-            
+        // Another task may have reconnected while this one waited for the lock
+        if session.check().await.is_err() {
             *session = backoff::future::retry(ExponentialBackoff::default(), || async {
                 Ok(Session::connect_mux(
                     &format!("{}@{}", self.remote_username, self.remote_hostname),
                     openssh::KnownHosts::Strict
                 ).await.map_err(|e| {
-                    error!(%e, "Failed to reconnect SSH session!");
+                    error!(%e, cluster = %self.name, "Failed to reconnect SSH session!");
                     anyhow!("Failed to reconnect SSH session! Error: {e:?}")
                 })?)
             }).await
@@ -55,6 +114,55 @@ impl AppState {
 
         Ok(())
     }
+    /// Closes the SSH session (and its mux master), so
+    ///  that no socket is left behind on shutdown
+    pub async fn close_ssh_session ( &self ) -> Result<()> {
+        let session = self.ssh_session.write().await.take();
+        if let Some(session) = session {
+            session.close().await
+                .with_context(|| format!("Failed to close SSH session for cluster `{}`!", self.name))?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub struct AppState {
+    /// Every monitored cluster, in configuration order. The first
+    ///  is the primary cluster, which is used for logins and groups.
+    pub clusters: Vec<Arc<Cluster>>,
+    pub db: super::DB,
+    pub config: Config,
+    pub daemons: Supervisor,
+}
+impl AppState {
+    pub fn primary_cluster ( &self ) -> &Arc<Cluster> {
+        self.clusters.first()
+            .expect("At least one cluster must be configured!")
+    }
+    pub fn cluster ( &self, name: &str ) -> Option<&Arc<Cluster>> {
+        self.clusters.iter()
+            .find(|cluster| cluster.name == name)
+    }
+    pub fn cluster_names ( &self ) -> Vec<String> {
+        self.clusters.iter()
+            .map(|cluster| cluster.name.clone())
+            .collect()
+    }
+    /// Appends to the audit log. A failed write is logged rather
+    ///  than returned, so it never fails the request being audited.
+    pub async fn audit (
+        &self,
+        username: &str,
+        action: AuditAction,
+        target: Option<&str>,
+        detail: Option<&str>
+    ) {
+        if let Err(e) = self.db.insert_audit_entry(username, action, target, detail).await {
+            error!("Couldn't write {} by {username} to the audit log! {e:?}", action.as_str());
+        }
+    }
 }
 /*
 struct HtmlTemplate<T>(T);
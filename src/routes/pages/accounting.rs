@@ -0,0 +1,339 @@
+use super::super::AppState;
+use crate::config::{Config, Grouping, SiteConfig};
+use super::{cluster_label, date_query_to_timestamp, display_timezone, duration_field_to_hours, is_admin, parse_cluster_query, timestamp_field_to_month, try_render_template, PageType};
+
+use std::{collections::{BTreeMap, HashMap, HashSet}, sync::Arc};
+
+use anyhow::Result;
+use chrono_tz::Tz;
+use axum::{
+    extract::{Query, State}, http::{header, HeaderValue}, response::Response
+};
+use tower_sessions::Session;
+use axum::http::StatusCode;
+use askama::Template;
+use tracing::{error, info};
+
+
+/// Parses the `by` query, attributing usage to projects by default
+fn grouping_query ( params: &HashMap<String, String> ) -> Grouping {
+    match params.get("by").map(|st| st.as_str()) {
+        Some("group") => Grouping::Group,
+        _ => Grouping::Project
+    }
+}
+/// Resources reserved by a set of jobs, and their charge
+#[derive(Debug, Default, Clone)]
+pub(super) struct Usage {
+    pub jobs: usize,
+    pub core_hours: f64,
+    pub gpu_hours: f64,
+    pub mem_gb_hours: f64,
+    pub charge: f64
+}
+impl Usage {
+    /// Adds a job's reserved resources over its elapsed walltime,
+    ///  charged at the configured rates for its queue
+    pub(super) fn add_job ( &mut self, config: &Config, job: &BTreeMap<String, String> ) {
+        let field = |field: &str| -> f64 {
+            job.get(field)
+                .and_then(|st| st.parse::<f64>().ok())
+                .unwrap_or(0f64)
+        };
+        let hours = duration_field_to_hours(job.get("used_walltime").map(|st| st.as_str()).unwrap_or("")) as f64;
+        let core_hours = field("req_cpus") * hours;
+        let gpu_hours = field("req_gpus") * hours;
+        let mem_gb_hours = field("req_mem") * hours;
+
+        self.jobs += 1;
+        self.core_hours += core_hours;
+        self.gpu_hours += gpu_hours;
+        self.mem_gb_hours += mem_gb_hours;
+        if let Some(rates) = config.queue_rates(job.get("queue").map(|st| st.as_str()).unwrap_or("")) {
+            self.charge += core_hours * rates.core_hour
+                + gpu_hours * rates.gpu_hour
+                + mem_gb_hours * rates.mem_gb_hour;
+        }
+    }
+}
+/// Pairs each job with the group (or project) it's attributed to, from
+///  the rows of `DB::get_accounting_jobs`
+pub(super) fn attribute_jobs <'a> (
+    groups_cache: &HashMap<String, Vec<String>>,
+    jobs: &'a [BTreeMap<String, String>],
+    grouping: Grouping
+) -> Vec<(String, &'a BTreeMap<String, String>)> {
+    let mut seen_jobs = HashSet::new();
+    jobs.iter()
+        .filter_map(|job| {
+            let name = match grouping {
+                Grouping::Group => {
+                    match job.get("group_name").map(|st| st.as_str()) {
+                        None | Some("") => String::from("no group"),
+                        Some(group) => group.to_string()
+                    }
+                },
+                Grouping::Project => {
+                    // There's a row per group of the owner, but
+                    //  each job is charged to one project only
+                    if !seen_jobs.insert((job.get("cluster"), job.get("job_id"))) {
+                        return None;
+                    }
+
+                    job.get("owner")
+                        .and_then(|owner| groups_cache.get(owner))
+                        .and_then(|groups| groups.first())
+                        .cloned()
+                        .unwrap_or(String::from("no project"))
+                }
+            };
+
+            Some((name, job))
+        })
+        .collect()
+}
+/// Sums usage per month (in `timezone`) and group (or project)
+fn usage_by_month (
+    config: &Config,
+    groups_cache: &HashMap<String, Vec<String>>,
+    jobs: &[BTreeMap<String, String>],
+    grouping: Grouping,
+    timezone: Tz
+) -> BTreeMap<(String, String), Usage> {
+    let mut usage: BTreeMap<(String, String), Usage> = BTreeMap::new();
+    for (name, job) in attribute_jobs(groups_cache, jobs, grouping) {
+        let month = timestamp_field_to_month(job.get("end_time").map(|st| st.as_str()).unwrap_or(""), timezone);
+
+        usage.entry((month, name))
+            .or_default()
+            .add_job(config, job);
+    }
+
+    usage
+}
+
+/// A row of the accounting report
+#[derive(Debug)]
+struct AccountingRow {
+    month: String,
+    name: String,
+    usage: Usage
+}
+/// The accounting report visible to the current user
+#[derive(Debug)]
+struct AccountingReport {
+    rows: Vec<AccountingRow>,
+    total: Usage
+}
+/// Builds the accounting report for the `date`, `by` and `cluster`
+///  queries, limited to the user's own groups unless they're an admin
+async fn build_report (
+    app: &AppState,
+    params: &HashMap<String, String>,
+    username: &str,
+    grouping: Grouping,
+    timezone: Tz
+) -> Result<AccountingReport, (StatusCode, String)> {
+    let cluster_query = parse_cluster_query(app, params)?;
+    let adjusted_timestamp = date_query_to_timestamp(
+        Some(params.get("date").unwrap_or(&String::from("year")))
+    );
+
+    let jobs = app.db
+        .get_accounting_jobs(adjusted_timestamp, cluster_query.as_ref())
+        .await
+        .map_err(|e| {
+            error!(%e, "Couldn't get accounting jobs!");
+            (StatusCode::INTERNAL_SERVER_ERROR, "Couldn't get accounting jobs!".to_string())
+        })?;
+    let groups_cache = app.db
+        .get_groups_cache()
+        .await;
+    let visible_groups = (!is_admin(app, &groups_cache, username))
+        .then(|| groups_cache.get(username).cloned().unwrap_or_default());
+
+    let is_visible = |name: &String| visible_groups.as_ref().is_none_or(|groups| groups.contains(name));
+
+    let rows = usage_by_month(&app.config, &groups_cache, &jobs, grouping, timezone)
+        .into_iter()
+        .filter(|((_, name), _)| is_visible(name))
+        .map(|((month, name), usage)| AccountingRow { month, name, usage })
+        .collect();
+
+    // By group, a job is in the row of each of its owner's
+    //  groups, but is only counted once toward the total
+    let mut total = Usage::default();
+    let mut counted_jobs = HashSet::new();
+    for (name, job) in attribute_jobs(&groups_cache, &jobs, grouping) {
+        if is_visible(&name) && counted_jobs.insert((job.get("cluster"), job.get("job_id"))) {
+            total.add_job(&app.config, job);
+        }
+    }
+
+    Ok(AccountingReport { rows, total })
+}
+/// Gets the logged in user, if any
+async fn session_username (
+    session: &Session
+) -> Result<Option<String>, (StatusCode, String)> {
+    session.get::<String>("username")
+        .await
+        .map_err(|e| {
+            error!(%e, "Couldn't get username from session!");
+            (StatusCode::UNAUTHORIZED, "Couldn't get username from session!".to_string())
+        })
+}
+
+#[derive(Template, Debug)]
+#[template(path = "pages/accounting.html")]
+struct AccountingPageTemplate<'a> {
+    username: Option<String>,
+    title: String,
+    header: String,
+    alert: Option<String>,
+    jobs: Vec<BTreeMap<String, String>>,
+
+    report: Option<AccountingReport>,
+    grouping: Grouping,
+    date_query: String,
+    currency: &'a str,
+    clusters: Vec<String>,
+    cluster_query: Option<String>,
+    site: &'a SiteConfig,
+    url_prefix: &'a str,
+
+    page_type: PageType
+}
+#[tracing::instrument]
+pub async fn accounting(
+    State(app): State<Arc<AppState>>,
+    Query(params): Query<HashMap<String, String>>,
+    session: Session,
+) -> Result<Response, (StatusCode, String)> {
+    info!("[ Got request to build accounting page...]");
+
+    let username = session_username(&session).await?;
+    let grouping = grouping_query(&params);
+    let date_query = params.get("date")
+        .map(|st| st.to_lowercase())
+        .unwrap_or(String::from("year"));
+    let cluster_query = parse_cluster_query(&app, &params)?;
+    let timezone = display_timezone(&app, username.as_deref()).await;
+
+    let report = match username {
+        Some(ref username) => Some(build_report(&app, &params, username, grouping, timezone).await?),
+        None => None
+    };
+    let cluster_label = cluster_label(&app, cluster_query.as_ref());
+
+    let template = AccountingPageTemplate {
+        alert: match (&username, &report) {
+            (None, _) => Some("You are not logged in!".to_string()),
+            (Some(_), Some(report)) if report.rows.is_empty() => Some("No usage found!".to_string()),
+            _ => None
+        },
+        username,
+        title: format!("Accounting - {}", app.config.site.name),
+        header: format!("Usage Accounting by {} on {cluster_label} (Months in {timezone})", grouping.label()),
+        jobs: Vec::new(),
+
+        report,
+        grouping,
+        date_query,
+        currency: &app.config.accounting.currency,
+        clusters: app.cluster_names(),
+        cluster_query,
+        site: &app.config.site,
+        url_prefix: &app.config.server.url_prefix,
+
+        page_type: PageType::Accounting
+    };
+
+    try_render_template(&template)
+}
+/// Quotes a CSV field if needed, and stops spreadsheets from running
+///  it as a formula (i.e. a failed login's username of `=HYPERLINK(...)`)
+pub(super) fn csv_field ( field: &str ) -> String {
+    let field = if field.starts_with(['=', '+', '-', '@', '\t', '\r']) {
+        format!("'{field}")
+    } else {
+        field.to_string()
+    };
+
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field
+    }
+}
+#[tracing::instrument]
+pub async fn accounting_csv(
+    State(app): State<Arc<AppState>>,
+    Query(params): Query<HashMap<String, String>>,
+    session: Session,
+) -> Result<Response, (StatusCode, String)> {
+    info!("[ Got request to export accounting CSV...]");
+
+    let username = session_username(&session).await?
+        .ok_or((StatusCode::UNAUTHORIZED, "You are not logged in!".to_string()))?;
+    let grouping = grouping_query(&params);
+    let timezone = display_timezone(&app, Some(&username)).await;
+    let report = build_report(&app, &params, &username, grouping, timezone).await?;
+
+    let mut csv = format!("month,{},jobs,core_hours,gpu_hours,mem_gb_hours,charge\n", grouping.as_str());
+    for row in report.rows.iter() {
+        csv += &format!(
+            "{},{},{},{:.2},{:.2},{:.2},{:.2}\n",
+            csv_field(&row.month),
+            csv_field(&row.name),
+            row.usage.jobs,
+            row.usage.core_hours,
+            row.usage.gpu_hours,
+            row.usage.mem_gb_hours,
+            row.usage.charge
+        );
+    }
+
+    let filename = format!("attachment; filename=\"accounting-by-{}-{}.csv\"", grouping.as_str(), chrono::Utc::now().with_timezone(&timezone).format("%Y-%m-%d"));
+    Response::builder()
+        .header(header::CONTENT_TYPE, HeaderValue::from_static("text/csv; charset=utf-8"))
+        .header(
+            header::CONTENT_DISPOSITION,
+            HeaderValue::from_str(&filename)
+                .map_err(|e| {
+                    error!(%e, "Invalid CSV filename!");
+                    (StatusCode::INTERNAL_SERVER_ERROR, "Invalid CSV filename!".to_string())
+                })?
+        )
+        .body(csv.into())
+        .map_err(|err| {
+            error!(%err, "Failed to build response!");
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to build response!".to_string())
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csv_fields_are_quoted_when_needed () {
+        assert_eq!(csv_field("cs-101"), "cs-101");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_field("two\nlines"), "\"two\nlines\"");
+    }
+    #[test]
+    fn csv_fields_are_never_formulas () {
+        assert_eq!(csv_field("=1+1"), "'=1+1");
+        assert_eq!(csv_field("+1"), "'+1");
+        assert_eq!(csv_field("-1"), "'-1");
+        assert_eq!(csv_field("@SUM(A1)"), "'@SUM(A1)");
+        assert_eq!(csv_field("\tx"), "'\tx");
+        assert_eq!(csv_field("\rx"), "\"'\rx\"");
+        assert_eq!(
+            csv_field("=HYPERLINK(\"http://evil\",\"x\")"),
+            "\"'=HYPERLINK(\"\"http://evil\"\",\"\"x\"\")\""
+        );
+    }
+}
@@ -0,0 +1,457 @@
+use super::super::AppState;
+use crate::{config::SiteConfig, daemons::supervisor::DaemonStatus, db::lib::{AuditAction, AuditEntry, AuditFilter, BackfillRun}};
+use super::{accounting::csv_field, display_timezone, is_admin, try_render_template, PageType};
+
+use std::{collections::{BTreeMap, HashMap}, sync::Arc};
+
+use anyhow::Result;
+use axum::{
+    extract::{Query, State}, http::{header, HeaderValue}, response::Response
+};
+use chrono_tz::Tz;
+use tower_sessions::Session;
+use axum::http::StatusCode;
+use askama::Template;
+use tracing::{error, info};
+
+
+/// How many past backfills are listed
+const BACKFILL_HISTORY: u32 = 25;
+/// How many audit log entries are listed, with the rest left to the export
+const AUDIT_PAGE_LIMIT: u32 = 500;
+
+/// Formats a timestamp for the admin pages
+fn format_time ( timestamp: i64, timezone: Tz ) -> String {
+    chrono::DateTime::from_timestamp(timestamp, 0)
+        .map(|date_time| date_time.with_timezone(&timezone).format("%b %e, %Y at %l:%M%p").to_string())
+        .unwrap_or_default()
+}
+
+/// A backfill, ready to show
+#[derive(Debug)]
+struct BackfillRow {
+    run: BackfillRun,
+    users: String,
+    /// The month being pulled, as `YYYY-MM`
+    current_month: String,
+    percent: f32,
+    created: String,
+    updated: String
+}
+impl BackfillRow {
+    fn new ( run: BackfillRun, timezone: Tz ) -> Self {
+        Self {
+            users: if run.users.is_empty() {
+                String::from("All users")
+            } else {
+                run.users.join(", ")
+            },
+            current_month: run.next_month.as_ref()
+                .filter(|_| run.state == "running")
+                .map(|month| month.chars().take(7).collect())
+                .unwrap_or(String::from("-")),
+            percent: run.months_done as f32 * 100f32 / run.months_total.max(1) as f32,
+            created: format_time(run.created_at, timezone),
+            updated: format_time(run.updated_at, timezone),
+            run
+        }
+    }
+}
+
+#[derive(Template, Debug)]
+#[template(path = "pages/admin_backfill.html")]
+struct BackfillPageTemplate<'a> {
+    username: Option<String>,
+    title: String,
+    header: String,
+    alert: Option<String>,
+    jobs: Vec<BTreeMap<String, String>>,
+
+    is_admin: bool,
+    started: bool,
+    backfills: Vec<BackfillRow>,
+    default_since: &'a str,
+    cluster_names: Vec<String>,
+    clusters: Vec<String>,
+    cluster_query: Option<String>,
+    site: &'a SiteConfig,
+    url_prefix: &'a str,
+
+    page_type: PageType
+}
+#[tracing::instrument]
+pub async fn backfill(
+    State(app): State<Arc<AppState>>,
+    Query(params): Query<HashMap<String, String>>,
+    session: Session,
+) -> Result<Response, (StatusCode, String)> {
+    info!("[ Got request to build backfill page...]");
+
+    let username = session.get::<String>("username")
+        .await
+        .map_err(|e| {
+            error!(%e, "Couldn't get username from session!");
+            (StatusCode::UNAUTHORIZED, "Couldn't get username from session!".to_string())
+        })?;
+    let groups_cache = app.db
+        .get_groups_cache()
+        .await;
+    let is_admin = username.as_ref()
+        .is_some_and(|username| is_admin(&app, &groups_cache, username));
+    let timezone = display_timezone(&app, username.as_deref()).await;
+
+    let backfills = if is_admin {
+        app.db
+            .get_backfill_runs(BACKFILL_HISTORY)
+            .await
+            .map_err(|e| {
+                error!(%e, "Couldn't get backfill runs!");
+                (StatusCode::INTERNAL_SERVER_ERROR, "Couldn't get backfill runs!".to_string())
+            })?
+            .into_iter()
+            .map(|run| BackfillRow::new(run, timezone))
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    let template = BackfillPageTemplate {
+        alert: if username.is_none() {
+                Some("You are not logged in!".to_string())
+            } else if !is_admin {
+                Some("Only admins may backfill job history!".to_string())
+            } else {
+                None
+            },
+        username,
+        title: format!("Backfill - {}", app.config.site.name),
+        header: String::from("Job History Backfill"),
+        jobs: Vec::new(),
+
+        is_admin,
+        started: params.get("started").is_some_and(|st| st == "true"),
+        backfills,
+        default_since: &app.config.backfill.since,
+        cluster_names: app.cluster_names(),
+        clusters: Vec::new(),
+        cluster_query: None,
+        site: &app.config.site,
+        url_prefix: &app.config.server.url_prefix,
+
+        page_type: PageType::Admin
+    };
+
+    try_render_template(&template)
+}
+
+/// A daemon, ready to show
+#[derive(Debug)]
+struct DaemonRow {
+    id: usize,
+    name: String,
+    paused: bool,
+    status: DaemonStatus,
+    last_start: String,
+    last_success: String,
+    last_duration: String,
+    last_items: String
+}
+
+#[derive(Template, Debug)]
+#[template(path = "pages/admin_daemons.html")]
+struct DaemonsPageTemplate<'a> {
+    username: Option<String>,
+    title: String,
+    header: String,
+    alert: Option<String>,
+    jobs: Vec<BTreeMap<String, String>>,
+
+    is_admin: bool,
+    daemons: Vec<DaemonRow>,
+    clusters: Vec<String>,
+    cluster_query: Option<String>,
+    site: &'a SiteConfig,
+    url_prefix: &'a str,
+
+    page_type: PageType
+}
+#[tracing::instrument]
+pub async fn daemons(
+    State(app): State<Arc<AppState>>,
+    session: Session,
+) -> Result<Response, (StatusCode, String)> {
+    info!("[ Got request to build daemons page...]");
+
+    let username = session.get::<String>("username")
+        .await
+        .map_err(|e| {
+            error!(%e, "Couldn't get username from session!");
+            (StatusCode::UNAUTHORIZED, "Couldn't get username from session!".to_string())
+        })?;
+    let groups_cache = app.db
+        .get_groups_cache()
+        .await;
+    let is_admin = username.as_ref()
+        .is_some_and(|username| is_admin(&app, &groups_cache, username));
+    let timezone = display_timezone(&app, username.as_deref()).await;
+
+    let daemons = if is_admin {
+        app.daemons
+            .daemons()
+            .iter()
+            .enumerate()
+            .map(|(id, daemon)| {
+                let status = daemon.status();
+                let never = || String::from("Never");
+
+                DaemonRow {
+                    id,
+                    name: daemon.name.clone(),
+                    paused: daemon.is_paused(),
+                    last_start: status.last_start.map(|timestamp| format_time(timestamp, timezone)).unwrap_or_else(never),
+                    last_success: status.last_success.map(|timestamp| format_time(timestamp, timezone)).unwrap_or_else(never),
+                    last_duration: status.last_duration.map(|seconds| format!("{seconds:.1}s")).unwrap_or(String::from("-")),
+                    last_items: status.last_items.map(|items| items.to_string()).unwrap_or(String::from("-")),
+                    status
+                }
+            })
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    let template = DaemonsPageTemplate {
+        alert: if username.is_none() {
+                Some("You are not logged in!".to_string())
+            } else if !is_admin {
+                Some("Only admins may manage the daemons!".to_string())
+            } else {
+                None
+            },
+        username,
+        title: format!("Daemons - {}", app.config.site.name),
+        header: String::from("Daemons"),
+        jobs: Vec::new(),
+
+        is_admin,
+        daemons,
+        clusters: Vec::new(),
+        cluster_query: None,
+        site: &app.config.site,
+        url_prefix: &app.config.server.url_prefix,
+
+        page_type: PageType::Admin
+    };
+
+    try_render_template(&template)
+}
+
+/// The start of a `YYYY-MM-DD` date in `timezone`, as a timestamp,
+///  or of the day after it if `next_day`
+fn date_query_to_day_start ( date_query: &str, timezone: Tz, next_day: bool ) -> Option<i64> {
+    let date = chrono::NaiveDate::parse_from_str(date_query, "%Y-%m-%d").ok()?;
+    let date = if next_day { date.succ_opt()? } else { date };
+
+    date.and_hms_opt(0, 0, 0)?
+        .and_local_timezone(timezone)
+        .earliest()
+        .map(|date_time| date_time.timestamp())
+}
+/// Parses the `user`, `action`, `from` and `to` queries, with both
+///  dates inclusive and in `timezone`
+fn audit_filter ( params: &HashMap<String, String>, timezone: Tz ) -> AuditFilter {
+    let query = |name: &str| params.get(name)
+        .map(|st| st.trim())
+        .filter(|st| !st.is_empty());
+
+    AuditFilter {
+        username: query("user").map(|st| st.to_string()),
+        action: query("action").and_then(AuditAction::parse),
+        since: query("from").and_then(|date| date_query_to_day_start(date, timezone, false)),
+        until: query("to").and_then(|date| date_query_to_day_start(date, timezone, true))
+    }
+}
+/// Gets the logged in user, failing unless they're an admin
+async fn require_admin (
+    app: &AppState,
+    session: &Session
+) -> Result<String, (StatusCode, String)> {
+    let username = session.get::<String>("username")
+        .await
+        .map_err(|e| {
+            error!(%e, "Couldn't get username from session!");
+            (StatusCode::UNAUTHORIZED, "Couldn't get username from session!".to_string())
+        })?
+        .ok_or((StatusCode::UNAUTHORIZED, "You are not logged in!".to_string()))?;
+    let groups_cache = app.db
+        .get_groups_cache()
+        .await;
+    if !is_admin(app, &groups_cache, &username) {
+        return Err((StatusCode::FORBIDDEN, "Only admins may view the audit log!".to_string()));
+    }
+
+    Ok(username)
+}
+
+/// An audit log entry, ready to show
+#[derive(Debug)]
+struct AuditRow {
+    entry: AuditEntry,
+    time: String
+}
+
+#[derive(Template, Debug)]
+#[template(path = "pages/admin_audit.html")]
+struct AuditPageTemplate<'a> {
+    username: Option<String>,
+    title: String,
+    header: String,
+    alert: Option<String>,
+    jobs: Vec<BTreeMap<String, String>>,
+
+    is_admin: bool,
+    entries: Vec<AuditRow>,
+    truncated: bool,
+    actions: Vec<String>,
+    user_query: String,
+    action_query: String,
+    from_query: String,
+    to_query: String,
+    clusters: Vec<String>,
+    cluster_query: Option<String>,
+    site: &'a SiteConfig,
+    url_prefix: &'a str,
+
+    page_type: PageType
+}
+#[tracing::instrument]
+pub async fn audit(
+    State(app): State<Arc<AppState>>,
+    Query(params): Query<HashMap<String, String>>,
+    session: Session,
+) -> Result<Response, (StatusCode, String)> {
+    info!("[ Got request to build audit log page...]");
+
+    let username = session.get::<String>("username")
+        .await
+        .map_err(|e| {
+            error!(%e, "Couldn't get username from session!");
+            (StatusCode::UNAUTHORIZED, "Couldn't get username from session!".to_string())
+        })?;
+    let groups_cache = app.db
+        .get_groups_cache()
+        .await;
+    let is_admin = username.as_ref()
+        .is_some_and(|username| is_admin(&app, &groups_cache, username));
+    let timezone = display_timezone(&app, username.as_deref()).await;
+
+    let entries = if is_admin {
+        app.db
+            .get_audit_entries(&audit_filter(&params, timezone), Some(AUDIT_PAGE_LIMIT))
+            .await
+            .map_err(|e| {
+                error!(%e, "Couldn't get audit log!");
+                (StatusCode::INTERNAL_SERVER_ERROR, "Couldn't get audit log!".to_string())
+            })?
+            .into_iter()
+            .map(|entry| AuditRow {
+                time: format_time(entry.timestamp, timezone),
+                entry
+            })
+            .collect()
+    } else {
+        Vec::new()
+    };
+    let query = |name: &str| params.get(name)
+        .cloned()
+        .unwrap_or_default();
+
+    let template = AuditPageTemplate {
+        alert: if username.is_none() {
+                Some("You are not logged in!".to_string())
+            } else if !is_admin {
+                Some("Only admins may view the audit log!".to_string())
+            } else if entries.is_empty() {
+                Some("No entries found!".to_string())
+            } else {
+                None
+            },
+        username,
+        title: format!("Audit Log - {}", app.config.site.name),
+        header: format!("Audit Log (Times in {timezone})"),
+        jobs: Vec::new(),
+
+        is_admin,
+        truncated: entries.len() as u32 >= AUDIT_PAGE_LIMIT,
+        entries,
+        actions: AuditAction::ALL.iter()
+            .map(|action| action.as_str().to_string())
+            .collect(),
+        user_query: query("user"),
+        action_query: query("action"),
+        from_query: query("from"),
+        to_query: query("to"),
+        clusters: Vec::new(),
+        cluster_query: None,
+        site: &app.config.site,
+        url_prefix: &app.config.server.url_prefix,
+
+        page_type: PageType::Admin
+    };
+
+    try_render_template(&template)
+}
+#[tracing::instrument]
+pub async fn audit_csv(
+    State(app): State<Arc<AppState>>,
+    Query(params): Query<HashMap<String, String>>,
+    session: Session,
+) -> Result<Response, (StatusCode, String)> {
+    info!("[ Got request to export audit log CSV...]");
+
+    let username = require_admin(&app, &session).await?;
+    let timezone = display_timezone(&app, Some(&username)).await;
+    let entries = app.db
+        .get_audit_entries(&audit_filter(&params, timezone), None)
+        .await
+        .map_err(|e| {
+            error!(%e, "Couldn't get audit log!");
+            (StatusCode::INTERNAL_SERVER_ERROR, "Couldn't get audit log!".to_string())
+        })?;
+
+    // Times are exported as both UTC timestamps and in the
+    //  admin's timezone, so they're unambiguous either way
+    let mut csv = String::from("entry_id,timestamp,time,username,action,target,detail\n");
+    for entry in entries.iter() {
+        let time = chrono::DateTime::from_timestamp(entry.timestamp, 0)
+            .map(|date_time| date_time.with_timezone(&timezone).to_rfc3339())
+            .unwrap_or_default();
+        csv += &format!(
+            "{},{},{},{},{},{},{}\n",
+            entry.entry_id,
+            entry.timestamp,
+            csv_field(&time),
+            csv_field(&entry.username),
+            csv_field(&entry.action),
+            csv_field(entry.target.as_deref().unwrap_or("")),
+            csv_field(entry.detail.as_deref().unwrap_or(""))
+        );
+    }
+
+    let filename = format!("attachment; filename=\"audit-log-{}.csv\"", chrono::Utc::now().with_timezone(&timezone).format("%Y-%m-%d"));
+    Response::builder()
+        .header(header::CONTENT_TYPE, HeaderValue::from_static("text/csv; charset=utf-8"))
+        .header(
+            header::CONTENT_DISPOSITION,
+            HeaderValue::from_str(&filename)
+                .map_err(|e| {
+                    error!(%e, "Invalid CSV filename!");
+                    (StatusCode::INTERNAL_SERVER_ERROR, "Invalid CSV filename!".to_string())
+                })?
+        )
+        .body(csv.into())
+        .map_err(|err| {
+            error!(%err, "Failed to build response!");
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to build response!".to_string())
+        })
+}
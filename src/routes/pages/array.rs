@@ -0,0 +1,173 @@
+use crate::parsing::jobs::{parse_job_id, short_job_id, ArrayIndex};
+use super::super::AppState;
+use crate::{config::SiteConfig, db::lib::AuditAction};
+use super::{display_timezone, parse_cluster_query, sort_build_parse, try_render_template, Toolkit, TableEntry, TableStat, TableStatType, PageType};
+
+use std::{collections::{BTreeMap, HashMap}, sync::Arc};
+
+use anyhow::Result;
+use axum::{
+    extract::{Query, State}, response::Response
+};
+use tower_sessions::Session;
+use axum::http::StatusCode;
+use askama::Template;
+use tracing::{error, info};
+
+
+#[derive(Template, Debug)]
+#[template(path = "pages/array.html")]
+struct ArrayPageTemplate<'a> {
+    username: Option<String>,
+    needs_login: bool,
+    title: String,
+    header: String,
+    alert: Option<String>,
+    jobs: Vec<BTreeMap<String, String>>,
+    table_entries: Vec<TableEntry>,
+
+    parent: Option<BTreeMap<String, String>>,
+    clusters: Vec<String>,
+    cluster_query: Option<String>,
+    site: &'a SiteConfig,
+    url_prefix: &'a str,
+
+    page_type: PageType,
+    toolkit: Toolkit
+}
+#[tracing::instrument]
+pub async fn array(
+    State(app): State<Arc<AppState>>,
+    Query(params): Query<HashMap<String, String>>,
+    session: Session,
+) -> Result<Response, (StatusCode, String)> {
+    info!("[ Got request to build array page...]");
+
+    // Unpack username and query parameters
+    let username = session.get::<String>("username")
+        .await
+        .map_err(|e| {
+            error!(%e, "Couldn't get username from session!");
+            (StatusCode::UNAUTHORIZED, "Couldn't get username from session!".to_string())
+        })?;
+    let id_query = params.get("id")
+        .map(|id| {
+            parse_job_id(id)
+                .map_err(|e| {
+                    error!(%e, "Failed to parse ID!");
+                    (StatusCode::BAD_REQUEST, "Failed to parse ID!".to_string())
+                })
+        })
+        .transpose()?;
+    let cluster = parse_cluster_query(&app, &params)?
+        .unwrap_or_else(|| app.primary_cluster().name.clone());
+    let timezone = display_timezone(&app, username.as_deref()).await;
+
+    // Accept the ID of any subjob, but always show the whole array
+    let array_id = id_query.map(|mut id| {
+        id.array = Some(ArrayIndex::Parent);
+        id.key()
+    });
+
+    // Get the array and its subjobs
+    let (mut parent, mut jobs) = match (&username, &array_id) {
+        (Some(_), Some(array_id)) => {
+            let parent = app.db
+                .get_job(&cluster, array_id)
+                .await
+                .map_err(|e| {
+                    error!(%e, "Couldn't get job array!");
+                    (StatusCode::NOT_FOUND, "Couldn't get job array!".to_string())
+                })?;
+            let subjobs = app.db
+                .get_array_subjobs(&cluster, array_id)
+                .await
+                .map_err(|e| {
+                    error!(%e, "Couldn't get array subjobs!");
+                    (StatusCode::INTERNAL_SERVER_ERROR, "Couldn't get array subjobs!".to_string())
+                })?;
+
+            (Some(parent), subjobs)
+        },
+        _ => (None, vec!())
+    };
+    if let (Some(username), Some(array_id), Some(parent)) = (&username, &array_id, &parent) {
+        let owner = parent.get("owner").map(|st| st.as_str());
+        app.audit(username, AuditAction::ViewArray, owner, Some(&format!("{array_id} on {cluster}"))).await;
+    }
+
+    // Tweak data to be presentable and add tooltips for efficiencies
+    let groups_cache = app.db
+        .get_groups_cache()
+        .await;
+    if let Some(ref mut parent) = parent {
+        let mut parents = vec!(parent.clone());
+        sort_build_parse(
+            &app.config,
+            groups_cache.clone(),
+            vec!(TableStat::ArrayProgress),
+            &mut parents,
+            &HashMap::new(),
+            username.clone(),
+            timezone
+        );
+        if let Some(built_parent) = parents.pop() {
+            *parent = built_parent;
+        }
+    }
+    let (table_entries, errors) = sort_build_parse(
+        &app.config,
+        groups_cache,
+        vec!(
+            TableStat::JobID,
+            TableStat::Status,
+            TableStat::StartTime,
+            TableStat::EndTime,
+            TableStat::CpuTime,
+            TableStat::UsedMem,
+            TableStat::ElapsedWalltimeColored,
+            TableStat::CpuEfficiency,
+            TableStat::MemEfficiency,
+            TableStat::ExitStatus,
+            TableStat::More
+        ),
+
+        &mut jobs,
+        &params,
+        username.clone(),
+        timezone
+    );
+    let url_prefix = &app.config.server.url_prefix;
+
+    // Build the template
+    let template = ArrayPageTemplate {
+        jobs,
+        alert: if username.is_none() {
+                Some("You are not logged in!".to_string())
+            } else if array_id.is_none() {
+                Some("No job array ID provided!".to_string())
+            } else {
+                errors
+            },
+        username,
+        needs_login: true,
+        title: format!("Job Array - {}", app.config.site.name),
+        header: match array_id {
+            Some(ref array_id) => format!("Subjobs of Job Array {} on {cluster}", short_job_id(array_id)),
+            None => String::from("Job Array")
+        },
+
+        table_entries,
+
+        parent,
+        clusters: vec!(),
+        cluster_query: None,
+        site: &app.config.site,
+        url_prefix,
+
+        toolkit: Toolkit,
+        page_type: PageType::Array
+    };
+
+    try_render_template(&template)
+}
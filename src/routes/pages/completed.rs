@@ -1,5 +1,6 @@
 use super::super::AppState;
-use super::{sort_build_parse, try_render_template, Toolkit, TableEntry, TableStat, TableStatType, PageType};
+use crate::{config::SiteConfig, db::lib::AuditAction};
+use super::{cluster_label, date_query_to_timestamp, display_timezone, parse_cluster_query, with_cluster_column, sort_build_parse, try_render_template, Toolkit, TableEntry, TableStat, TableStatType, PageType};
 
 use std::{collections::{BTreeMap, HashMap}, sync::Arc};
 
@@ -27,6 +28,9 @@ struct CompletedPageTemplate<'a> {
 
     user_query: Option<String>,
     date_query: Option<String>,
+    clusters: Vec<String>,
+    cluster_query: Option<String>,
+    site: &'a SiteConfig,
     url_prefix: &'a str,
 
     page_type: PageType,
@@ -47,29 +51,14 @@ pub async fn completed(
             error!(%e, "Couldn't get username from session!");
             (StatusCode::UNAUTHORIZED, "Couldn't get username from session!".to_string())
         })?;
-    let user_query = params.get("user")
-        .and_then(|st| Some(st.to_owned()))
+    let user_query = params.get("user").map(|st| st.to_owned())
         .or(username.clone());
-    let date_query = params.get("date")
-        .and_then(|st| Some(st.to_owned()));
-
-    // Convert our date query to a timestamp, using `month`
-    //  by default. Options are `day`, `month`, `year`, `all` (10 years)
-    let adjusted_timestamp = match date_query {
-        Some(ref date_query) => {
-            let date_query = date_query.to_lowercase();
-            let now = chrono::Local::now();
-
-            match date_query.as_str() {
-                "day" => now.timestamp() - 86400,
-                "month" => now.timestamp() - 2592000,
-                "year" => now.timestamp() - 31536000,
-                "all" => now.timestamp() - 315360000,
-                _ => now.timestamp() - 2592000
-            }
-        },
-        None => chrono::Local::now().timestamp() - 2592000
-    };
+    let date_query = params.get("date").map(|st| st.to_owned());
+    let cluster_query = parse_cluster_query(&app, &params)?;
+    let timezone = display_timezone(&app, username.as_deref()).await;
+
+    // Convert our date query to a timestamp, using `month` by default
+    let adjusted_timestamp = date_query_to_timestamp(date_query.as_ref());
 
     // Make it a human readable date
     let adjusted_date = chrono::DateTime::from_timestamp(adjusted_timestamp, 0)
@@ -78,10 +67,12 @@ pub async fn completed(
 
             (StatusCode::INTERNAL_SERVER_ERROR, "Couldn't convert timestamp to date!".to_string())
         })?;
-    let adjusted_date = adjusted_date.format("%b %e, %Y at %l:%M%p").to_string();
+    let adjusted_date = adjusted_date.with_timezone(&timezone)
+        .format("%b %e, %Y at %l:%M%p %Z")
+        .to_string();
 
     // Get the jobs
-    let mut jobs = if let Some(_) = username {    
+    let mut jobs = if username.is_some() {    
         app.db
             .get_user_jobs(
                 &user_query.clone().expect("Unreachable"),
@@ -89,6 +80,7 @@ pub async fn completed(
                 None,
                 None,
                 None, 
+                cluster_query.as_ref(),
                 Some(&adjusted_timestamp.to_string())
             )
             .await
@@ -99,14 +91,19 @@ pub async fn completed(
     } else {
         vec!()
     };
+    if let (Some(username), Some(user)) = (&username, params.get("user")) {
+        let detail = format!("Since {adjusted_date} on {}", cluster_label(&app, cluster_query.as_ref()));
+        app.audit(username, AuditAction::ViewCompleted, Some(user), Some(&detail)).await;
+    }
 
     // Tweak data to be presentable and add tooltips for efficiencies
     let groups_cache = app.db
         .get_groups_cache()
         .await;
     let (table_entries, errors) = sort_build_parse(
+        &app.config,
         groups_cache,
-        vec!(
+        with_cluster_column(&app, vec!(
             TableStat::JobID,
             TableStat::JobOwner,
             TableStat::RsvdCpus,
@@ -120,14 +117,17 @@ pub async fn completed(
             TableStat::CpuEfficiency,
             TableStat::MemEfficiency,
             TableStat::ExitStatus,
+            TableStat::ArrayProgress,
             TableStat::More
-        ),
+        )),
 
         &mut jobs,
         &params,
-        username.clone()
+        username.clone(),
+        timezone
     );
-    let url_prefix = &app.url_prefix;
+    let url_prefix = &app.config.server.url_prefix;
+    let cluster_label = cluster_label(&app, cluster_query.as_ref());
 
     // Build the template
     let template = CompletedPageTemplate {
@@ -139,21 +139,25 @@ pub async fn completed(
             },
         username,
         needs_login: true,
-        title: format!("Completed Jobs - CRCD Batchmon"),
+        title: format!("Completed Jobs - {}", app.config.site.name),
         header: if let Some(ref user_query) = user_query {
             format!(
-                "Completed Jobs for '{}' on Metis - Since {}",
+                "Completed Jobs for '{}' on {} - Since {}",
                 user_query,
+                cluster_label,
                 adjusted_date
             )
         } else {
-            String::from("Completed Jobs on Metis")
+            format!("Completed Jobs on {cluster_label}")
         },
 
         table_entries,
 
         user_query,
         date_query,
+        clusters: app.cluster_names(),
+        cluster_query,
+        site: &app.config.site,
         url_prefix,
 
         toolkit:Toolkit,
@@ -0,0 +1,224 @@
+use super::super::AppState;
+use crate::{config::{Allocation, Grouping, SiteConfig}, db::lib::AuditAction};
+use super::{accounting::{attribute_jobs, Usage}, is_admin, try_render_template, PageType};
+
+use std::{collections::{BTreeMap, HashMap}, sync::Arc};
+
+use anyhow::{Context, Result};
+use axum::{
+    extract::{Path, State}, response::Response
+};
+use tower_sessions::Session;
+use axum::http::StatusCode;
+use askama::Template;
+use tracing::{error, info};
+
+
+/// Consumption of one resource of an allocation, day by day
+#[derive(Debug)]
+struct BurnDown {
+    resource: &'static str,
+    allocated: f64,
+    used: f64,
+    percent: f64,
+    days: Vec<String>,
+    remaining: Vec<f64>,
+    /// Where `remaining` would be if used evenly over the period
+    ideal: Vec<f64>
+}
+/// An allocation and how much of it has been used so far
+#[derive(Debug)]
+struct AllocationStatus {
+    start: String,
+    end: String,
+    by: Grouping,
+    burn_downs: Vec<BurnDown>
+}
+impl AllocationStatus {
+    /// Builds the burn-downs from the rows of `DB::get_accounting_jobs`
+    ///  since the allocation started, with days in the site's timezone
+    ///  (in which allocation periods are defined)
+    fn new (
+        app: &AppState,
+        groups_cache: &HashMap<String, Vec<String>>,
+        allocation: &Allocation,
+        jobs: &[BTreeMap<String, String>]
+    ) -> Result<Self> {
+        let (start, end) = allocation.dates()?;
+        let timezone = app.config.site.timezone;
+        let (_, end_timestamp) = allocation.period(timezone)?;
+
+        // Sum the usage of each day of the allocation
+        let mut daily_usage: BTreeMap<chrono::NaiveDate, Usage> = BTreeMap::new();
+        for (name, job) in attribute_jobs(groups_cache, jobs, allocation.by) {
+            let end_time = job.get("end_time")
+                .and_then(|st| st.parse::<i64>().ok())
+                .unwrap_or(0);
+            if name != allocation.group || end_time >= end_timestamp {
+                continue;
+            }
+
+            let day = chrono::DateTime::from_timestamp(end_time, 0)
+                .context("Invalid job end time!")?
+                .with_timezone(&timezone)
+                .date_naive();
+            daily_usage.entry(day)
+                .or_default()
+                .add_job(&app.config, job);
+        }
+
+        let today = chrono::Utc::now().with_timezone(&timezone).date_naive();
+        let total_days = (end - start).num_days() as f64 + 1f64;
+        let days = start.iter_days()
+            .take_while(|day| *day <= end.min(today))
+            .collect::<Vec<_>>();
+
+        let burn_downs = [
+            ("Core-Hours", allocation.core_hours, (|usage: &Usage| usage.core_hours) as fn(&Usage) -> f64),
+            ("GPU-Hours", allocation.gpu_hours, |usage: &Usage| usage.gpu_hours)
+        ].into_iter()
+            .filter(|(_, allocated, _)| *allocated > 0f64)
+            .map(|(resource, allocated, used_by)| {
+                let mut used = 0f64;
+                let mut remaining = Vec::new();
+                let mut ideal = Vec::new();
+                for (i, day) in days.iter().enumerate() {
+                    used += daily_usage.get(day).map(used_by).unwrap_or(0f64);
+                    remaining.push(allocated - used);
+                    ideal.push(allocated * (1f64 - (i as f64 + 1f64) / total_days));
+                }
+
+                BurnDown {
+                    resource,
+                    allocated,
+                    used,
+                    percent: used * 100f64 / allocated,
+                    days: days.iter().map(|day| day.to_string()).collect(),
+                    remaining,
+                    ideal
+                }
+            })
+            .collect();
+
+        Ok(Self {
+            start: allocation.start.clone(),
+            end: allocation.end.clone(),
+            by: allocation.by,
+            burn_downs
+        })
+    }
+}
+
+#[derive(Template, Debug)]
+#[template(path = "pages/group.html")]
+struct GroupPageTemplate<'a> {
+    username: Option<String>,
+    title: String,
+    header: String,
+    alert: Option<String>,
+    jobs: Vec<BTreeMap<String, String>>,
+
+    group_query: String,
+    allocations: Vec<AllocationStatus>,
+    warnings: Vec<String>,
+    clusters: Vec<String>,
+    cluster_query: Option<String>,
+    site: &'a SiteConfig,
+    url_prefix: &'a str,
+
+    page_type: PageType
+}
+#[tracing::instrument]
+pub async fn group(
+    State(app): State<Arc<AppState>>,
+    Path(group_query): Path<String>,
+    session: Session,
+) -> Result<Response, (StatusCode, String)> {
+    info!("[ Got request to build group page...]");
+
+    let username = session.get::<String>("username")
+        .await
+        .map_err(|e| {
+            error!(%e, "Couldn't get username from session!");
+            (StatusCode::UNAUTHORIZED, "Couldn't get username from session!".to_string())
+        })?;
+    let groups_cache = app.db
+        .get_groups_cache()
+        .await;
+
+    // Only members of the group (and admins) may see its allocations
+    let is_member = username.as_ref()
+        .is_some_and(|username| {
+            is_admin(&app, &groups_cache, username)
+                || groups_cache.get(username).is_some_and(|groups| groups.contains(&group_query))
+        });
+
+    let mut allocations = Vec::new();
+    if let Some(username) = username.as_ref().filter(|_| is_member) {
+        app.audit(username, AuditAction::ViewGroup, Some(&group_query), None).await;
+    }
+    if is_member {
+        for allocation in app.config.group_allocations(&group_query) {
+            let build_status = async {
+                let (start_timestamp, _) = allocation.period(app.config.site.timezone)?;
+                let jobs = app.db
+                    .get_accounting_jobs(start_timestamp, None)
+                    .await
+                    .context("Couldn't get accounting jobs!")?;
+
+                AllocationStatus::new(&app, &groups_cache, allocation, &jobs)
+            };
+
+            allocations.push(build_status.await.map_err(|e| {
+                error!(%e, "Couldn't build allocation status!");
+                (StatusCode::INTERNAL_SERVER_ERROR, "Couldn't build allocation status!".to_string())
+            })?);
+        }
+    }
+
+    // Warn once the highest threshold passed, if any
+    let warnings = allocations.iter()
+        .flat_map(|allocation| allocation.burn_downs.iter().map(move |burn_down| (allocation, burn_down)))
+        .filter_map(|(allocation, burn_down)| {
+            let threshold = app.config.allocations.warn_at.iter()
+                .rev()
+                .find(|threshold| burn_down.percent >= **threshold)?;
+
+            Some(format!(
+                "{group_query} has used {:.1}% of its {} allocation for {} to {} (over {threshold}%)",
+                burn_down.percent,
+                burn_down.resource,
+                allocation.start,
+                allocation.end
+            ))
+        })
+        .collect::<Vec<_>>();
+
+    let template = GroupPageTemplate {
+        alert: if username.is_none() {
+                Some("You are not logged in!".to_string())
+            } else if !is_member {
+                Some("You are not a member of this group!".to_string())
+            } else if allocations.is_empty() {
+                Some("No allocations are configured for this group.".to_string())
+            } else {
+                None
+            },
+        username,
+        title: format!("{group_query} - {}", app.config.site.name),
+        header: format!("Allocations for '{group_query}'"),
+        jobs: Vec::new(),
+
+        group_query,
+        allocations,
+        warnings,
+        clusters: Vec::new(),
+        cluster_query: None,
+        site: &app.config.site,
+        url_prefix: &app.config.server.url_prefix,
+
+        page_type: PageType::Group
+    };
+
+    try_render_template(&template)
+}
@@ -1,4 +1,4 @@
-use crate::routes::AppState;
+use crate::{config::SiteConfig, routes::AppState};
 
 use super::try_render_template;
 
@@ -19,6 +19,8 @@ struct LoginPageTemplate<'a> {
     title: String,
     username: Option<String>,
     failed: bool,
+    cluster_name: &'a str,
+    site: &'a SiteConfig,
     url_prefix: &'a str
 }
 #[tracing::instrument]
@@ -29,17 +31,16 @@ pub async fn login(
 ) -> Result<Response, (StatusCode, String)> {
     info!("[ Got request to build login page...]");
 
-    let url_prefix = &app.url_prefix;
+    let url_prefix = &app.config.server.url_prefix;
 
     let template = LoginPageTemplate {
-        title: "Login - CRCD Batchmon".to_string(),
+        title: format!("Login - {}", app.config.site.name),
         username: None,
-        failed: params.get("invalid")
-            .and_then(|st| {
-                Some(st.parse::<bool>()
+        failed: params.get("invalid").map(|st| st.parse::<bool>()
                     .unwrap_or(false))
-            })
             .unwrap_or(false),
+        cluster_name: &app.primary_cluster().name,
+        site: &app.config.site,
         url_prefix
     };
 
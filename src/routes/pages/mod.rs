@@ -1,37 +1,58 @@
 use std::collections::{BTreeMap, HashMap};
 use axum::{http::{self, StatusCode}, response::Response};
-use tracing::{error, info};
+use tracing::{error, info, warn};
 use anyhow::{Context, Result};
+use chrono_tz::Tz;
+
+use crate::{config::{Config, Metric, Severity, SiteConfig}, parsing::units::Duration};
 
 pub mod running;
 pub mod login;
 pub mod completed;
 pub mod search;
 pub mod stats;
+pub mod array;
+pub mod users;
+pub mod accounting;
+pub mod groups;
+pub mod settings;
+pub mod admin;
 
 #[derive(Clone, Debug)]
 enum PageType {
     Running,
     Completed,
     Search,
-    Stats
+    Stats,
+    Array,
+    User,
+    Accounting,
+    Group,
+    Admin
+}
+impl PageType {
+    /// Whether the page is a list of jobs, which notes when it's empty
+    fn lists_jobs ( &self ) -> bool {
+        !matches!(self, PageType::Stats | PageType::Accounting | PageType::Group | PageType::Admin)
+    }
 }
 #[derive(Clone, Debug)]
 enum TableStatType {
     Default,
 
-    ColoredRG,  // Red to Green
-    ColoredYGR, // Yellow to Green to Red
+    Efficiency, // Coloured by the configured efficiency rules
 
     JobID,
     JobName(usize),
     JobOwner,
     ExitStatus,
+    ArrayProgress,
     More
 }
 #[derive(Clone, Debug)]
 enum TableStat {
     JobID,
+    Cluster,
     JobOwner,
     JobName(usize),
     JobProject,
@@ -53,8 +74,8 @@ enum TableStat {
     MemEfficiency,
     NodesChunks,
     ExitStatus,
+    ArrayProgress,
     More,
-    #[allow(dead_code)]
     Custom {
         name: String,
         tooltip: String,
@@ -67,7 +88,9 @@ enum TableStat {
 impl TableStat {
     fn adjust_job (
         &self,
+        config: &Config,
         group_cache: &HashMap<String, Vec<String>>,
+        timezone: Tz,
         job: &mut BTreeMap<String, String>
     ) -> Result<()> {
         match self {
@@ -79,9 +102,8 @@ impl TableStat {
                     String::from("project"),
                     group_cache.get(owner)
                         .unwrap_or(&Vec::new())
-                        .into_iter()
-                        .next()
-                        .and_then(|st| Some(st.to_owned()))
+                        .iter()
+                        .next().map(|st| st.to_owned())
                         .unwrap_or(String::from("no project"))
                 );
             }
@@ -92,7 +114,7 @@ impl TableStat {
                 if start_time_str_ref == "2147483647" {
                     *start_time_str_ref = String::from("Not Started");
                 } else {
-                    timestamp_field_to_date(start_time_str_ref);
+                    timestamp_field_to_date(start_time_str_ref, timezone);
                 }
             },
             TableStat::EndTime => {
@@ -102,7 +124,7 @@ impl TableStat {
                 if end_time_str_ref == "2147483647" {
                     *end_time_str_ref = String::from("Not Ended");
                 } else {
-                    timestamp_field_to_date(end_time_str_ref);
+                    timestamp_field_to_date(end_time_str_ref, timezone);
                 }
             },
             TableStat::UsedMemPerCore => {
@@ -141,16 +163,15 @@ impl TableStat {
                     )
                 );
             },
-            TableStat::RsvdGpus => {
-                if job.get("req_gpus").is_none() {
+            TableStat::RsvdGpus
+                if job.get("req_gpus").is_none() => {
                     job.insert(String::from("req_gpus"), String::from("0"));
-                }
-            },
+                },
             TableStat::CpuEfficiency | TableStat::MemEfficiency => {
-                add_efficiency_tooltips(job);
+                add_efficiency_tooltips(job, config);
             },
             TableStat::ElapsedWalltime | TableStat::ElapsedWalltimeColored => {
-                add_efficiency_tooltips(job);
+                add_efficiency_tooltips(job, config);
 
                 let walltime_efficiency_ref = job.get_mut("walltime_efficiency")
                     .context("Failed to get walltime efficiency!")?;
@@ -159,6 +180,9 @@ impl TableStat {
                     *walltime_efficiency_ref = format!("{}", walltime_efficiency.ceil());
                 }
             },
+            TableStat::ArrayProgress => {
+                add_array_progress(job);
+            },
             TableStat::Custom { .. } => {
                 // Do nothing
             },
@@ -169,9 +193,10 @@ impl TableStat {
     }
     fn ensure_needed_field (
         &self,
+        site: &SiteConfig,
         job: &mut BTreeMap<String, String>
     ) -> Result<()> {
-        let value = Into::<TableEntry>::into(self.clone()).value;
+        let value = self.clone().into_entry(site).value;
 
         if job.get(&value).is_none() {
             return Err(anyhow::anyhow!("Field '{}' not found in job!", value));
@@ -180,17 +205,27 @@ impl TableStat {
         Ok(())
     }
 }
-impl Into<TableEntry> for TableStat {
-    fn into ( self ) -> TableEntry {
+impl TableStat {
+    fn into_entry ( self, site: &SiteConfig ) -> TableEntry {
+        let docs_url = &site.docs_url;
+
         match self {
             TableStat::JobID => TableEntry {
                 name: String::from("Job ID"),
-                tooltip: String::from("<b>PBS Job ID</b>"),
+                tooltip: String::from("<b>PBS Job ID</b><br><br>Job arrays are shown as <code>ID[]</code>, and their subjobs as <code>ID[index]</code>"),
                 sort_by: Some(String::from("pbs_id")),
-                value: String::from("pbs_id"),
+                value: String::from("display_id"),
                 value_unit: None,
                 stat_type: TableStatType::JobID
             },
+            TableStat::Cluster => TableEntry {
+                name: String::from("Cluster"),
+                tooltip: String::from("<b>The cluster the job was submitted to</b>"),
+                sort_by: Some(String::from("cluster")),
+                value: String::from("cluster"),
+                value_unit: None,
+                stat_type: TableStatType::Default
+            },
             TableStat::JobOwner => TableEntry {
                 name: String::from("Job Owner"),
                 tooltip: String::from("<b>The UNIX Username of the Job Owner</b>"),
@@ -325,23 +360,23 @@ impl Into<TableEntry> for TableStat {
                 sort_by: Some(String::from("walltime_efficiency")),
                 value: String::from("walltime_efficiency"),
                 value_unit: None,
-                stat_type: TableStatType::ColoredYGR
+                stat_type: TableStatType::Efficiency
             },
             TableStat::CpuEfficiency => TableEntry {
                 name: String::from("CPU Usage"),
-                tooltip: String::from("<b>CPU Usage Efficiency</b><br><br>The integral load of all CPUs in use divided by the number of reserved CPUs, in %.<br><br>If low (indicated by the reddish background), consider a <a href=\"https://www.niu.edu/crcd/current-users/getting-started/queue-commands-job-management.shtml#jobcontrol\">workflow optimization</a>."),
+                tooltip: format!("<b>CPU Usage Efficiency</b><br><br>The integral load of all CPUs in use divided by the number of reserved CPUs, in %.<br><br>If low (indicated by the coloured background), consider a <a href=\"{docs_url}#jobcontrol\">workflow optimization</a>."),
                 sort_by: Some(String::from("cpu_efficiency")),
                 value: String::from("cpu_efficiency"),
                 value_unit: None,
-                stat_type: TableStatType::ColoredRG
+                stat_type: TableStatType::Efficiency
             },
             TableStat::MemEfficiency => TableEntry {
                 name: String::from("Memory Usage"),
-                tooltip: String::from("<b>Memory Usage Efficiency</b><br><br>The total amount of memory in use divided by the amount of reserved memory, in %.<br><br>If low (indicated by the reddish background), consider a <a href=\"https://www.niu.edu/crcd/current-users/getting-started/queue-commands-job-management.shtml#jobcontrol\">workflow optimization</a>."),
+                tooltip: format!("<b>Memory Usage Efficiency</b><br><br>The total amount of memory in use divided by the amount of reserved memory, in %.<br><br>If low (indicated by the coloured background), consider a <a href=\"{docs_url}#jobcontrol\">workflow optimization</a>."),
                 sort_by: Some(String::from("mem_efficiency")),
                 value: String::from("mem_efficiency"),
                 value_unit: None,
-                stat_type: TableStatType::ColoredRG
+                stat_type: TableStatType::Efficiency
             },
             TableStat::NodesChunks => TableEntry {
                 name: String::from("Nodes/Chunks"),
@@ -359,11 +394,19 @@ impl Into<TableEntry> for TableStat {
                 value_unit: None,
                 stat_type: TableStatType::ExitStatus
             },
+            TableStat::ArrayProgress => TableEntry {
+                name: String::from("Array Progress"),
+                tooltip: String::from("<b>Job Array Progress</b><br><br>The number of finished subjobs out of the total, for job arrays.<br><br>Click the count to view every subjob."),
+                sort_by: None,
+                value: String::from("array_progress"),
+                value_unit: None,
+                stat_type: TableStatType::ArrayProgress
+            },
             TableStat::More => TableEntry {
                 name: String::from("More"),
                 tooltip: String::from("<b>More Information</b>"),
                 sort_by: None,
-                value: String::from("pbs_id"),
+                value: String::from("job_id"),
                 value_unit: None,
                 stat_type: TableStatType::More
             },
@@ -389,11 +432,118 @@ struct TableEntry {
     stat_type: TableStatType,
 }
 
+// Query helper functions
+/// Validates the `cluster` query parameter against the configured clusters.
+/// 
+/// Returns `None` when unset (or `all`), meaning every cluster should be shown.
+fn parse_cluster_query (
+    app: &super::AppState,
+    params: &HashMap<String, String>
+) -> Result<Option<String>, (StatusCode, String)> {
+    match params.get("cluster").map(|st| st.as_str()) {
+        None | Some("all") => Ok(None),
+        Some(cluster) => {
+            if app.cluster(cluster).is_none() {
+                warn!("Unknown cluster requested: {cluster}");
+                return Err((StatusCode::BAD_REQUEST, format!("Unknown cluster `{cluster}`!")));
+            }
+
+            Ok(Some(cluster.to_string()))
+        }
+    }
+}
+/// Label for the selected cluster(s), for use in page headers
+fn cluster_label (
+    app: &super::AppState,
+    cluster_query: Option<&String>
+) -> String {
+    match cluster_query {
+        Some(cluster) => format!("{cluster} Cluster"),
+        None if app.clusters.len() == 1 => format!("{} Cluster", app.primary_cluster().name),
+        None => String::from("All Clusters")
+    }
+}
+/// Shows the `Cluster` column (after `Job ID`) when monitoring more than one cluster
+fn with_cluster_column (
+    app: &super::AppState,
+    mut stats: Vec<TableStat>
+) -> Vec<TableStat> {
+    if app.clusters.len() > 1 {
+        let position = stats.iter()
+            .position(|stat| matches!(stat, TableStat::JobID))
+            .map(|i| i + 1)
+            .unwrap_or(0);
+        stats.insert(position, TableStat::Cluster);
+    }
+
+    stats
+}
+
+/// Whether `username` is a member of the configured admin group
+pub(crate) fn is_admin (
+    app: &super::AppState,
+    groups_cache: &HashMap<String, Vec<String>>,
+    username: &str
+) -> bool {
+    groups_cache.get(username)
+        .is_some_and(|groups| groups.contains(&app.config.site.admin_group))
+}
+/// The timezone to show `username` times in, being the one
+///  they've chosen or otherwise the site's
+async fn display_timezone (
+    app: &super::AppState,
+    username: Option<&str>
+) -> Tz {
+    let Some(username) = username else {
+        return app.config.site.timezone;
+    };
+
+    match app.db.get_user_timezone(username).await {
+        Ok(timezone) => timezone.unwrap_or(app.config.site.timezone),
+        Err(e) => {
+            warn!(%e, "Couldn't get user timezone, using the site's!");
+            app.config.site.timezone
+        }
+    }
+}
+/// Converts a `date` query (`day`, `month`, `year`, or `all` for 10
+///  years) into the earliest timestamp to show, defaulting to a month
+fn date_query_to_timestamp ( date_query: Option<&String> ) -> i64 {
+    let now = chrono::Utc::now().timestamp();
+
+    match date_query.map(|st| st.to_lowercase()).as_deref() {
+        Some("day") => now - 86400,
+        Some("year") => now - 31536000,
+        Some("all") => now - 315360000,
+        _ => now - 2592000
+    }
+}
+
 // Field helper functions
-fn timestamp_field_to_date ( timestamp_field: &mut String ) {
+/// Formats a timestamp field as its month in `timezone`, i.e. `2025-03`
+fn timestamp_field_to_month ( timestamp_field: &str, timezone: Tz ) -> String {
+    timestamp_field.parse::<i64>().ok()
+        .and_then(|timestamp| chrono::DateTime::from_timestamp(timestamp, 0))
+        .map(|date_time| date_time.with_timezone(&timezone).format("%Y-%m").to_string())
+        .unwrap_or(String::from("Unknown"))
+}
+/// Formats the current time in `timezone`, for page headers
+fn now_in_timezone ( timezone: Tz ) -> String {
+    chrono::Utc::now()
+        .with_timezone(&timezone)
+        .format("%b %e, %Y at %l:%M%p %Z")
+        .to_string()
+}
+/// Parses a PBS `HH:MM:SS` duration into hours
+fn duration_field_to_hours ( duration_field: &str ) -> f32 {
+    duration_field.parse::<Duration>()
+        .map(|duration| duration.hours() as f32)
+        .unwrap_or(0f32)
+}
+fn timestamp_field_to_date ( timestamp_field: &mut String, timezone: Tz ) {
     let timestamp_i64 = timestamp_field.parse::<i64>().unwrap_or(0);
     *timestamp_field = if let Some(date_time) = chrono::DateTime::from_timestamp(timestamp_i64, 0) {
-        date_time.with_timezone(&chrono::Local)
+        date_time.with_timezone(&timezone)
             .format("%b %e, %Y at %l:%M%p")
             .to_string()
     } else {
@@ -407,7 +557,7 @@ struct Toolkit;
 impl Toolkit {
     pub fn total_successful_jobs (
         &self,
-        jobs: &Vec<BTreeMap<String, String>>
+        jobs: &[BTreeMap<String, String>]
     ) -> usize {
         jobs.iter()
             .filter(|job| job.get("exit_status").unwrap_or(&"1".to_string()) == "0")
@@ -415,43 +565,13 @@ impl Toolkit {
     }
     pub fn total_cpu_time (
         &self,
-        jobs: &Vec<BTreeMap<String, String>>
+        jobs: &[BTreeMap<String, String>]
     ) -> String {
-        let mut total_hours = 0;
-        let mut total_minutes: i32 = 0;
-        let mut total_seconds: i32 = 0;
-
-        for job in jobs.iter() {
-            let time = job.get("used_cpu_time")
-                .and_then(|st| Some(st.to_owned()))
-                .unwrap_or(String::from("0"));
-            
-            if let Some(hours) = time.split(':').nth(0) {
-                total_hours += hours.parse::<i32>().unwrap_or(0)
-            } else {
-                continue;
-            }
-            if let Some(minutes) = time.split(':').nth(1) {
-                total_minutes += minutes.parse::<i32>().unwrap_or(0)
-            } else {
-                continue;
-            }
-            if let Some(seconds) = time.split(':').nth(2) {
-                total_seconds += seconds.parse::<i32>().unwrap_or(0)
-            } else {
-                continue;
-            }
-        }
-
-        total_minutes += total_seconds / 60;
-        total_seconds %= 60;
-        total_hours += total_minutes / 60;
-        total_minutes %= 60;
-
-        let total_days = total_hours / 24;
-        total_hours %= 24;
-
-        format!("{:02}:{:02}:{:02}:{:02}", total_days, total_hours, total_minutes, total_seconds)
+        jobs.iter()
+            .filter_map(|job| job.get("used_cpu_time"))
+            .filter_map(|time| time.parse::<Duration>().ok())
+            .fold(Duration::default(), |total, time| total + time)
+            .with_days()
     }
     pub fn to_i32 ( &self, num: &&String ) -> Result<i32> {
         Ok(num.parse::<f64>()
@@ -473,6 +593,14 @@ impl Toolkit {
             (*name_field).clone()
         }
     }
+    pub fn short_job_id ( &self, job_id: &str ) -> String {
+        crate::parsing::jobs::short_job_id(job_id).to_string()
+    }
+    pub fn efficiency_color ( &self, job: &BTreeMap<String, String>, field: &str ) -> String {
+        job.get(&format!("{field}_color"))
+            .cloned()
+            .unwrap_or_else(|| Severity::Warning.color().to_string())
+    }
     pub fn get_field ( &self, job: &BTreeMap<String, String>, field: &str ) -> Result<String> {
         job.get(field)
             .ok_or_else(|| anyhow::anyhow!("Field '{}' not found in job!", field))
@@ -491,20 +619,17 @@ fn sort_jobs (
         return;
     }
 
-    let sort_query = sort_query
-        .and_then(|st| Some(st.as_str()))
-        .and_then(|st| {
-            if !authenticated {
-                if st == "owner" {
-                    return Some("pbs_id");
+    let sort_query = sort_query.map(|st| st.as_str())
+        .map(|st| {
+            if !authenticated
+                && st == "owner" {
+                    return "pbs_id";
                 }
-            }
 
-            Some(st)
+            st
         })
         .unwrap_or("pbs_id");
-    let reverse_query = reverse_query
-        .and_then(|st| Some(st.as_str()))
+    let reverse_query = reverse_query.map(|st| st.as_str())
         .unwrap_or("false")
         .parse::<bool>()
         .unwrap_or(false);
@@ -512,11 +637,9 @@ fn sort_jobs (
     info!("Sorting jobs by {} in reverse: {}", sort_query, reverse_query);
 
     jobs.sort_by(|a, b| {
-        let a = a.get(sort_query)
-            .and_then(|st| Some(st.as_str()))
+        let a = a.get(sort_query).map(|st| st.as_str())
             .unwrap_or("0");
-        let b = b.get(sort_query)
-            .and_then(|st| Some(st.as_str()))
+        let b = b.get(sort_query).map(|st| st.as_str())
             .unwrap_or("0");
 
         // First, try to parse to a float
@@ -524,33 +647,11 @@ fn sort_jobs (
             return a.partial_cmp(&b).unwrap();
         }
 
-        // Second, if the sort query is 'req_walltime' or 'used_walltime', sort by HH:MM:SS
+        // Second, if the sort query is a duration, sort by its length
         if sort_query == "req_walltime" || sort_query == "used_walltime" || sort_query == "used_cpu_time" {
-            let a = a.split(':').collect::<Vec<&str>>();
-            let b = b.split(':').collect::<Vec<&str>>();
-
-            if a.len() != 3 || b.len() != 3 {
+            if let (Ok(a), Ok(b)) = (a.parse::<Duration>(), b.parse::<Duration>()) {
                 return a.cmp(&b);
             }
-
-            let a = a.iter()
-                .map(|st| st.parse::<i32>().unwrap_or(0))
-                .collect::<Vec<i32>>();
-            let b = b.iter()
-                .map(|st| st.parse::<i32>().unwrap_or(0))
-                .collect::<Vec<i32>>();
-
-            if a.len() != 3 || b.len() != 3 {
-                return a.cmp(&b);
-            }
-
-            if a[0] != b[0] {
-                return a[0].cmp(&b[0]);
-            } else if a[1] != b[1] {
-                return a[1].cmp(&b[1]);
-            } else {
-                return a[2].cmp(&b[2]);
-            }
         }
 
         a.cmp(b)
@@ -560,62 +661,77 @@ fn sort_jobs (
         jobs.reverse();
     }
 }
-fn add_efficiency_tooltips ( job: &mut BTreeMap<String, String> ) {
-    let cpu_efficiency = job.get("cpu_efficiency")
-        .and_then(|st| Some(st.parse::<f32>().unwrap_or(0f32)) )
-        .unwrap_or(0f32);
-    let mem_efficiency = job.get("mem_efficiency")
-        .and_then(|st| Some(st.parse::<f32>().unwrap_or(0f32)) )
-        .unwrap_or(0f32);
-    let walltime_efficiency = job.get("walltime_efficiency")
-        .and_then(|st| Some(st.parse::<f32>().unwrap_or(0f32)) )
-        .unwrap_or(0f32);
+/// Adds the tooltip and background colour for each efficiency,
+///  judged by the configured rules for the job's queue
+fn add_efficiency_tooltips ( job: &mut BTreeMap<String, String>, config: &Config ) {
+    let queue = job.get("queue").cloned().unwrap_or_default();
+
+    for metric in [Metric::Cpu, Metric::Mem, Metric::Walltime] {
+        let field = match metric {
+            Metric::Cpu => "cpu_efficiency",
+            Metric::Mem => "mem_efficiency",
+            Metric::Walltime => "walltime_efficiency"
+        };
+        let efficiency = job.get(field).map(|st| st.parse::<f32>().unwrap_or(0f32))
+            .unwrap_or(0f32);
+        let band = config.efficiency_band(metric, &queue, efficiency);
 
-    job.insert(
-        String::from("cpu_efficiency_tooltip"),
-        format!("<b>CPU Efficiency: {cpu_efficiency:.2}%</b>") + &if cpu_efficiency < 75f32 {
-            String::from("<br><br>")
-            + match cpu_efficiency {
-                x if x < 50f32 => "The job had a low CPU load, consider reserving fewer CPUs.",
-                x if x < 75f32 => "The job is using the CPU somewhat efficiently.",
-                _ => "Abnormal CPU usage!"
-            }
-            + "<br><br>"
-            + "See the <a href=\"https://www.niu.edu/crcd/current-users/getting-started/queue-commands-job-management.shtml#jobcontrol\">CRCD docs</a> for more information."
-        } else {
-            String::from("")
+        let mut tooltip = format!("<b>{} Efficiency: {efficiency:.2}%</b>", metric.label());
+        if let Some(band) = band.filter(|band| !band.advice.is_empty()) {
+            let docs_url = band.docs_url.clone()
+                .unwrap_or_else(|| format!("{}#jobcontrol", config.site.docs_url));
+
+            tooltip += &format!("<br><br>{}<br><br>See the <a href=\"{docs_url}\">docs</a> for more information.", band.advice);
         }
-    );
+
+        job.insert(format!("{field}_tooltip"), tooltip);
+        job.insert(
+            format!("{field}_color"),
+            band.map(|band| band.severity)
+                .unwrap_or(Severity::Good)
+                .color()
+                .to_string()
+        );
+    }
+}
+fn add_array_progress ( job: &mut BTreeMap<String, String> ) {
+    if job.get("is_array").is_none_or(|st| st != "1") {
+        job.insert(String::from("array_progress"), String::from("-"));
+        return;
+    }
+
+    let count = |job: &BTreeMap<String, String>, field: &str| -> u32 {
+        job.get(field)
+            .and_then(|st| st.parse::<u32>().ok())
+            .unwrap_or(0)
+    };
+    let total = count(job, "array_total");
+    let done = count(job, "array_done");
+    let running = count(job, "array_running");
+    let queued = count(job, "array_queued");
+    let failed = count(job, "array_failed");
+
     job.insert(
-        String::from("mem_efficiency_tooltip"),
-        format!("<b>Memory Efficiency: {mem_efficiency:.2}%</b>") + &if mem_efficiency < 75f32 {
-            String::from("<br><br>")
-            + match mem_efficiency {
-                x if x < 50f32 => "The job had low memory utilization; consider reserving less memory.",
-                x if x < 75f32 => "The job is using the memory somewhat efficiently. If you are using a GPU, this is okay.",
-                _ => "Abnormal memory usage!"
-            }
-            + "<br><br>"
-            + "See the <a href=\"https://www.niu.edu/crcd/current-users/getting-started/queue-commands-job-management.shtml#jobcontrol\">CRCD docs</a> for more information."
-        } else {
-            String::from("")
-        }
+        String::from("array_progress"),
+        format!("{}/{total}", done + failed)
     );
     job.insert(
-        String::from("walltime_efficiency_tooltip"),
-        format!("<b>Walltime Efficiency: {walltime_efficiency:.2}%</b>") + &if walltime_efficiency < 50f32 || walltime_efficiency >= 80f32 {
-            String::from("<br><br>")
-            + match walltime_efficiency {
-                x if x < 50f32 => "The job took significantly less time than requested; please consider decreasing the requirement. The accurate estimation of the walltime needed is essential for the job starting time and will allow a better schedule of maintenance tasks.",
-                x if x >= 80f32 => "The job is potentially using too much walltime, consider allocating more for breathing room to avoid having the job killed.",
-                _ => "Abnormal walltime usage!"
-            }
-            + "<br><br>"
-            + "See the <a href=\"https://www.niu.edu/crcd/current-users/getting-started/queue-commands-job-management.shtml#jobcontrol\">CRCD docs</a> for more information."
-        } else {
-            String::from("")
-        }
+        String::from("array_progress_tooltip"),
+        format!("<b>Subjobs: {total}</b><br><br>Done: {done}<br>Running: {running}<br>Queued: {queued}<br>Failed: {failed}")
     );
+
+    // Widths of each segment of the progress bar, in %
+    for (field, amount) in [
+        ("array_done_pct", done),
+        ("array_running_pct", running),
+        ("array_queued_pct", queued),
+        ("array_failed_pct", failed)
+    ] {
+        job.insert(
+            String::from(field),
+            format!("{:.2}", amount as f32 * 100f32 / total.max(1) as f32)
+        );
+    }
 }
 fn signal_to_str_suffix ( 
     signal: i32
@@ -634,7 +750,7 @@ fn signal_to_str_suffix (
         _ => ""
     }
 }
-fn add_exit_status_tooltip ( job: &mut BTreeMap<String, String> ) {
+fn add_exit_status_tooltip ( job: &mut BTreeMap<String, String>, docs_url: &str ) {
     let exit_status = if let Some(exit_status) = job.get("exit_status") {
         exit_status
     } else {
@@ -703,7 +819,7 @@ fn add_exit_status_tooltip ( job: &mut BTreeMap<String, String> ) {
             String::new()
         } + 
         "<br><br>" +
-        "<a href=\"https://crcd.niu.edu/crcd/current-users/getting-started/queue-commands-job-management.shtml#exitstatus\">More information on PBS exit codes</a>"
+        &format!("<a href=\"{docs_url}#exitstatus\">More information on PBS exit codes</a>")
     );
 }
 fn try_render_template <T: ?Sized + askama::Template> (
@@ -731,12 +847,14 @@ fn try_render_template <T: ?Sized + askama::Template> (
 }
 #[tracing::instrument]
 fn sort_build_parse (
+    config: &Config,
     groups_cache: HashMap<String, Vec<String>>,
     table_stats: Vec<TableStat>,
 
     jobs: &mut Vec<BTreeMap<String, String>>,
     params: &HashMap<String, String>,
-    username: Option<String>
+    username: Option<String>,
+    timezone: Tz
 ) -> (
     Vec<TableEntry>, // Table entries
     Option<String>,  // Error string
@@ -753,13 +871,13 @@ fn sort_build_parse (
     let mut errors = Vec::new();
     for job_ref in jobs.iter_mut() {
         // Add tooltip for exit status
-        add_exit_status_tooltip(job_ref);
+        add_exit_status_tooltip(job_ref, &config.site.docs_url);
 
         for table_stat in table_stats.iter() {
-            if let Err(e) = table_stat.adjust_job(&groups_cache, job_ref) {
+            if let Err(e) = table_stat.adjust_job(config, &groups_cache, timezone, job_ref) {
                 errors.push(e);
             }
-            if let Err(e) = table_stat.ensure_needed_field(job_ref) {
+            if let Err(e) = table_stat.ensure_needed_field(&config.site, job_ref) {
                 errors.push(e);
             }
         }
@@ -792,7 +910,7 @@ fn sort_build_parse (
 
     (
         table_stats.into_iter()
-            .map(|table_stat| table_stat.into() )
+            .map(|table_stat| table_stat.into_entry(&config.site) )
             .collect(),
         (!errors.trim().is_empty()).then_some(errors)
     )
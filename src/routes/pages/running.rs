@@ -1,12 +1,14 @@
 use crate::routes::ClusterStatus;
 
 use super::super::AppState;
-use super::{try_render_template, TableEntry, TableStat, TableStatType, Toolkit, PageType, sort_build_parse};
+use crate::config::SiteConfig;
+use super::{cluster_label, display_timezone, now_in_timezone, parse_cluster_query, with_cluster_column, try_render_template, TableEntry, TableStat, TableStatType, Toolkit, PageType, sort_build_parse};
 
 use std::collections::HashMap;
 use std::{collections::BTreeMap, sync::Arc};
 
 use anyhow::Result;
+use chrono_tz::Tz;
 use axum::extract::Query;
 use axum::response::Response;
 use axum::{
@@ -17,6 +19,52 @@ use tower_sessions::Session;
 use askama::Template;
 use tracing::{info, error};
 
+/// Parses the `history` query into how far back to chart cluster
+///  status, defaulting to a day
+fn history_query_to_timestamp ( history_query: &str ) -> i64 {
+    let days = match history_query {
+        "week" => 7,
+        "month" => 30,
+        _ => 1
+    };
+
+    chrono::Utc::now().timestamp() - days * 24 * 60 * 60
+}
+/// The recorded status snapshots of a cluster, ready to chart
+#[derive(Debug)]
+struct StatusHistory {
+    cluster: String,
+    times: Vec<String>,
+    nodes_percent: Vec<f32>,
+    cpus_percent: Vec<f32>,
+    gpus_percent: Vec<f32>,
+    running_jobs: Vec<u32>,
+    queued_jobs: Vec<u32>
+}
+impl StatusHistory {
+    fn new ( cluster: String, snapshots: Vec<(i64, ClusterStatus)>, timezone: Tz ) -> Self {
+        let percent = |used: u32, total: u32| -> f32 {
+            used as f32 * 100f32 / total.max(1) as f32
+        };
+
+        Self {
+            cluster,
+            times: snapshots.iter()
+                .map(|(timestamp, _)| {
+                    chrono::DateTime::from_timestamp(*timestamp, 0)
+                        .map(|time| time.with_timezone(&timezone).format("%b %e %H:%M").to_string())
+                        .unwrap_or_default()
+                })
+                .collect(),
+            nodes_percent: snapshots.iter().map(|(_, status)| percent(status.used_nodes, status.total_nodes)).collect(),
+            cpus_percent: snapshots.iter().map(|(_, status)| percent(status.used_cpus, status.total_cpus)).collect(),
+            gpus_percent: snapshots.iter().map(|(_, status)| percent(status.used_gpus, status.total_gpus)).collect(),
+            running_jobs: snapshots.iter().map(|(_, status)| status.running_jobs).collect(),
+            queued_jobs: snapshots.iter().map(|(_, status)| status.queued_jobs).collect()
+        }
+    }
+}
+
 #[derive(Template, Debug)]
 #[template(path = "pages/running.html")]
 struct RunningPageTemplate<'a> {
@@ -28,7 +76,12 @@ struct RunningPageTemplate<'a> {
     jobs: Vec<BTreeMap<String, String>>,
     table_entries: Vec<TableEntry>,
 
-    cluster_status: Option<ClusterStatus>,
+    cluster_statuses: Vec<(String, Option<ClusterStatus>)>,
+    status_histories: Vec<StatusHistory>,
+    history_query: String,
+    clusters: Vec<String>,
+    cluster_query: Option<String>,
+    site: &'a SiteConfig,
     url_prefix: &'a str,
 
     toolkit: Toolkit,
@@ -49,15 +102,21 @@ pub async fn running(
             error!(%e, "Couldn't get username from session!");
             (StatusCode::UNAUTHORIZED, "Couldn't get username from session!".to_string())
         })?;
+    let cluster_query = parse_cluster_query(&app, &params)?;
+    let history_query = params.get("history")
+        .map(|st| st.to_lowercase())
+        .unwrap_or(String::from("day"));
+    let timezone = display_timezone(&app, username.as_deref()).await;
 
     // Get all running jobs
     let mut jobs = app.db
         .get_all_jobs(
-            Some(vec!("R", "Q")),
+            Some(vec!("R", "Q", "B")),
             None,
             None,
             None,
             None,
+            cluster_query.as_ref(),
             None
         )
         .await
@@ -71,13 +130,15 @@ pub async fn running(
         .get_groups_cache()
         .await;
     let (table_entries, errors) = sort_build_parse(
+        &app.config,
         groups_cache,
-        vec!(
+        with_cluster_column(&app, vec!(
             TableStat::JobID,
             TableStat::JobOwner,
             TableStat::JobName(10),
             TableStat::JobProject,
             TableStat::Status,
+            TableStat::ArrayProgress,
             TableStat::StartTime,
             TableStat::Queue,
             TableStat::RsvdTime,
@@ -88,13 +149,37 @@ pub async fn running(
             TableStat::CpuEfficiency,
             TableStat::MemEfficiency,
             TableStat::More
-        ),
+        )),
 
         &mut jobs,
         &params,
-        username.clone()
+        username.clone(),
+        timezone
     );
-    let url_prefix = &app.url_prefix;
+    let url_prefix = &app.config.server.url_prefix;
+
+    // Show the usage summary and history of each selected cluster
+    let history_timestamp = history_query_to_timestamp(&history_query);
+    let mut cluster_statuses = Vec::new();
+    let mut status_histories = Vec::new();
+    for cluster in app.clusters.iter() {
+        if cluster_query.as_ref().is_some_and(|name| *name != cluster.name) {
+            continue;
+        }
+
+        cluster_statuses.push((cluster.name.clone(), *cluster.status.read().await));
+
+        let snapshots = app.db
+            .get_cluster_status_history(&cluster.name, history_timestamp)
+            .await
+            .map_err(|e| {
+                error!(%e, "Couldn't get cluster status history!");
+                (StatusCode::INTERNAL_SERVER_ERROR, "Couldn't get cluster status history!".to_string())
+            })?;
+        if !snapshots.is_empty() {
+            status_histories.push(StatusHistory::new(cluster.name.clone(), snapshots, timezone));
+        }
+    }
     
     // Build template
     let template = RunningPageTemplate {
@@ -102,15 +187,20 @@ pub async fn running(
         needs_login: false,
         title: String::from("Cluster Overview - Batch Job Monitor"),
         header: format!(
-            "Submitted Jobs Status - Metis Cluster - {}",
-            chrono::Local::now()
-                .format("%b %e, %Y at %l:%M%p")
+            "Submitted Jobs Status - {} - {}",
+            cluster_label(&app, cluster_query.as_ref()),
+            now_in_timezone(timezone)
         ),
         jobs,
         alert: errors,
         table_entries,
 
-        cluster_status: app.status.read().await.clone(),
+        cluster_statuses,
+        status_histories,
+        history_query,
+        clusters: app.cluster_names(),
+        cluster_query,
+        site: &app.config.site,
         url_prefix,
 
         toolkit: Toolkit,
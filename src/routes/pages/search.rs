@@ -1,5 +1,6 @@
 use super::super::AppState;
-use super::{try_render_template, TableEntry, TableStat, TableStatType, Toolkit, PageType, sort_build_parse};
+use crate::{config::SiteConfig, db::lib::AuditAction};
+use super::{display_timezone, parse_cluster_query, with_cluster_column, try_render_template, TableEntry, TableStat, TableStatType, Toolkit, PageType, sort_build_parse};
 
 use std::{collections::{BTreeMap, HashMap}, sync::Arc};
 
@@ -31,6 +32,9 @@ struct SearchPageTemplate<'a> {
     name_query: Option<String>,
     group_query: Option<String>,
     date_query: Option<String>,
+    clusters: Vec<String>,
+    cluster_query: Option<String>,
+    site: &'a SiteConfig,
     url_prefix: &'a str,
 
     toolkit: Toolkit,
@@ -51,20 +55,21 @@ pub async fn search(
             error!(%e, "Couldn't get username from session!");
             (StatusCode::UNAUTHORIZED, "Couldn't get username from session!".to_string())
         })?;
-    let date_query = params.get("date")
-        .and_then(|st| Some(st.to_owned()));
-    let any_filters = params.get("state").is_some() || 
-        params.get("queue").is_some() || 
-        params.get("user").is_some() || 
-        params.get("name").is_some() || 
-        params.get("date").is_some() ||
-        params.get("group").is_some();
+    let date_query = params.get("date").map(|st| st.to_owned());
+    let cluster_query = parse_cluster_query(&app, &params)?;
+    let timezone = display_timezone(&app, username.as_deref()).await;
+    let any_filters = params.contains_key("state") || 
+        params.contains_key("queue") || 
+        params.contains_key("user") || 
+        params.contains_key("name") || 
+        params.contains_key("date") ||
+        params.contains_key("group");
 
     // Convert our date query to a timestamp, using `month`
     //  by default. Options are `day`, `month`, `year`, `all` (10 years)
     let timestamp_filter = if let Some(ref date_query) = date_query {
         let date_query = date_query.to_lowercase();
-        let now = chrono::Local::now();
+        let now = chrono::Utc::now();
         let timestamp = match date_query.as_str() {
             "day" => now.timestamp() - 86400,
             "month" => now.timestamp() - 2592000,
@@ -75,20 +80,20 @@ pub async fn search(
 
         timestamp.to_string()
     } else { // Default to a month
-        (chrono::Local::now().timestamp() - 2592000).to_string()
+        (chrono::Utc::now().timestamp() - 2592000).to_string()
     };
 
     // Get all running jobs
-    let mut jobs = if let Some(_) = username {
+    let mut jobs = if username.is_some() {
         if any_filters {
             app.db
                 .get_all_jobs(
-                    params.get("state")
-                        .and_then(|st| Some(vec!(st.as_str()))),
+                    params.get("state").map(|st| vec!(st.as_str())),
                     params.get("queue"),
                     params.get("user"),
                     params.get("name"),
                     params.get("group"),
+                    cluster_query.as_ref(),
                     Some(&timestamp_filter)
                 )
                 .await
@@ -102,14 +107,22 @@ pub async fn search(
     } else {
         vec!()
     };
+    if let Some(username) = username.as_ref().filter(|_| any_filters) {
+        let filters = ["state", "queue", "user", "name", "group", "date", "cluster"].into_iter()
+            .filter_map(|filter| params.get(filter).map(|value| format!("{filter}={value}")))
+            .collect::<Vec<String>>()
+            .join(", ");
+        app.audit(username, AuditAction::Search, params.get("user").map(|st| st.as_str()), Some(&filters)).await;
+    }
 
     // Tweak data to be presentable and add tooltips for efficiencies
     let groups_cache = app.db
         .get_groups_cache()
         .await;
     let (table_entries, errors) = sort_build_parse(
+        &app.config,
         groups_cache,
-        vec!(
+        with_cluster_column(&app, vec!(
             TableStat::JobID,
             TableStat::JobOwner,
             TableStat::JobName(10),
@@ -130,12 +143,14 @@ pub async fn search(
             TableStat::CpuEfficiency,
             TableStat::MemEfficiency,
             TableStat::ExitStatus,
+            TableStat::ArrayProgress,
             TableStat::More,
-        ),
+        )),
 
         &mut jobs,
         &params,
-        username.clone()
+        username.clone(),
+        timezone
     );
 
     // Limit jobs to the first 200
@@ -144,7 +159,7 @@ pub async fn search(
     }
 
     // Build jobs and template
-    let url_prefix = &app.url_prefix;
+    let url_prefix = &app.config.server.url_prefix;
     let template = SearchPageTemplate {
         alert: if username.is_none() {
                 Some("You are not logged in!".to_string())
@@ -161,17 +176,20 @@ pub async fn search(
             },
         username,
         needs_login: true,
-        title: String::from("Search - CRCD Batchmon"),
+        title: format!("Search - {}", app.config.site.name),
         header: String::from("Search"),
         jobs,
         table_entries,
 
-        state_query: params.get("state").and_then(|st| Some(st.to_owned())),
-        queue_query: params.get("queue").and_then(|st| Some(st.to_owned())),
-        user_query: params.get("user").and_then(|st| Some(st.to_owned())),
-        name_query: params.get("name").and_then(|st| Some(st.to_owned())),
-        group_query: params.get("group").and_then(|st| Some(st.to_owned())),
+        state_query: params.get("state").map(|st| st.to_owned()),
+        queue_query: params.get("queue").map(|st| st.to_owned()),
+        user_query: params.get("user").map(|st| st.to_owned()),
+        name_query: params.get("name").map(|st| st.to_owned()),
+        group_query: params.get("group").map(|st| st.to_owned()),
         date_query,
+        clusters: app.cluster_names(),
+        cluster_query,
+        site: &app.config.site,
         url_prefix,
 
         toolkit:Toolkit,
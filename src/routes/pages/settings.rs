@@ -0,0 +1,69 @@
+use crate::{config::SiteConfig, routes::AppState};
+
+use super::try_render_template;
+
+use std::{collections::HashMap, sync::Arc};
+
+use anyhow::Result;
+use axum::{
+    extract::{Query, State},
+    http::StatusCode, response::Response
+};
+use tower_sessions::Session;
+use askama::Template;
+use tracing::{error, info};
+
+#[derive(Template, Debug)]
+#[template(path = "pages/settings.html")]
+struct SettingsPageTemplate<'a> {
+    title: String,
+    username: Option<String>,
+    saved: bool,
+    /// The user's chosen timezone, if they've chosen one
+    timezone: Option<String>,
+    timezones: Vec<String>,
+    site: &'a SiteConfig,
+    url_prefix: &'a str
+}
+#[tracing::instrument]
+pub async fn settings(
+    State(app): State<Arc<AppState>>,
+    Query(params): Query<HashMap<String, String>>,
+    session: Session,
+) -> Result<Response, (StatusCode, String)> {
+    info!("[ Got request to build settings page...]");
+
+    let username = session.get::<String>("username")
+        .await
+        .map_err(|e| {
+            error!(%e, "Couldn't get username from session!");
+            (StatusCode::UNAUTHORIZED, "Couldn't get username from session!".to_string())
+        })?;
+
+    let timezone = match username {
+        Some(ref username) => app.db
+            .get_user_timezone(username)
+            .await
+            .map_err(|e| {
+                error!(%e, "Couldn't get user timezone!");
+                (StatusCode::INTERNAL_SERVER_ERROR, "Couldn't get user timezone!".to_string())
+            })?,
+        None => None
+    };
+
+    let template = SettingsPageTemplate {
+        title: format!("Settings - {}", app.config.site.name),
+        username,
+        saved: params.get("saved").map(|st| st.parse::<bool>()
+                    .unwrap_or(false))
+            .unwrap_or(false),
+        timezone: timezone.map(|timezone| timezone.name().to_string()),
+        timezones: chrono_tz::TZ_VARIANTS.iter()
+            .map(|timezone| timezone.name().to_string())
+            .collect(),
+        site: &app.config.site,
+        url_prefix: &app.config.server.url_prefix
+    };
+
+    try_render_template(&template)
+}
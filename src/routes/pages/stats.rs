@@ -1,10 +1,13 @@
+use crate::parsing::jobs::{parse_job_id, short_job_id};
 use super::super::AppState;
-use super::{timestamp_field_to_date, try_render_template, TableEntry, TableStat, TableStatType, Toolkit, PageType, sort_build_parse};
+use crate::{config::SiteConfig, db::lib::AuditAction};
+use super::{display_timezone, is_admin, now_in_timezone, parse_cluster_query, timestamp_field_to_date, try_render_template, TableEntry, TableStat, TableStatType, Toolkit, PageType, sort_build_parse};
 
 use std::collections::HashMap;
 use std::{collections::BTreeMap, sync::Arc};
 
 use anyhow::Result;
+use chrono_tz::Tz;
 use axum::response::Response;
 use axum::{
     extract::{Query, State},
@@ -14,6 +17,39 @@ use tower_sessions::Session;
 use askama::Template;
 use tracing::{error, info, warn};
 
+/// A job paired with its recorded `PastStats` samples
+type JobWithStats = (
+    BTreeMap<String, String>,
+    Vec<BTreeMap<String, String>>
+);
+
+/// Adds a `datetime` label (in `timezone`) to each sample, and the rate
+///  at which CPU time was used since the previous one (in cores)
+fn add_sample_rates ( stats: &mut [BTreeMap<String, String>], timezone: Tz ) {
+    let field = |stat: &BTreeMap<String, String>, field: &str| -> f64 {
+        stat.get(field)
+            .and_then(|st| st.parse::<f64>().ok())
+            .unwrap_or(0f64)
+    };
+
+    let mut previous: Option<(f64, f64)> = None;
+    for stat in stats.iter_mut() {
+        let (cput, walltime) = (field(stat, "cput"), field(stat, "walltime"));
+        let cput_rate = match previous {
+            Some((prev_cput, prev_walltime)) if walltime > prev_walltime => {
+                (cput - prev_cput).max(0f64) / (walltime - prev_walltime)
+            },
+            _ => cput / walltime.max(1f64)
+        };
+        previous = Some((cput, walltime));
+
+        let mut datetime = stat.get("timestamp").cloned().unwrap_or_default();
+        timestamp_field_to_date(&mut datetime, timezone);
+        stat.insert(String::from("datetime"), datetime);
+        stat.insert(String::from("cput_rate"), format!("{cput_rate:.2}"));
+        stat.insert(String::from("walltime_hours"), format!("{:.2}", walltime / 3600f64));
+    }
+}
 
 #[derive(Template, Debug)]
 #[template(path = "pages/stats.html")]
@@ -23,12 +59,14 @@ struct StatsPageTemplate<'a> {
     header: String,
     alert: Option<String>,
 
-    job: Option<(
-        BTreeMap<String, String>,
-        Vec<BTreeMap<String, String>>
-    )>,
+    job: Option<JobWithStats>,
     jobs: Vec<BTreeMap<String, String>>,
     tables: Vec<(String, Vec<TableEntry>)>,
+    attributes: Vec<(String, String)>,
+    raw_days: u64,
+    clusters: Vec<String>,
+    cluster_query: Option<String>,
+    site: &'a SiteConfig,
     url_prefix: &'a str,
     
     toolkit: Toolkit,
@@ -50,41 +88,50 @@ pub async fn stats(
             error!(%e, "Couldn't get username from session!");
             (StatusCode::UNAUTHORIZED, "Couldn't get username from session!".to_string())
         })?;
-    let id_query = params.get("id")
-        .and_then(|st| Some(st.to_owned()));
+    let id_query = params.get("id").map(|st| st.to_owned());
+    let cluster = parse_cluster_query(&app, &params)?
+        .unwrap_or_else(|| app.primary_cluster().name.clone());
+    let timezone = display_timezone(&app, username.as_deref()).await;
 
     // Get all running jobs
-    let mut job: Option<(
-        BTreeMap<String, String>,
-        Vec<BTreeMap<String, String>>
-    )> = if let Some(_) = username {
+    let mut attributes = Vec::new();
+    let mut job: Option<JobWithStats> = if username.is_some() {
         if let Some(ref id) = id_query {
-            let id = id.parse::<i32>()
+            let id = parse_job_id(id)
                 .map_err(|e| {
                     error!(%e, "Failed to parse ID!");
                     (StatusCode::BAD_REQUEST, "Failed to parse ID!".to_string())
                 })?;
+            let id = id.key();
 
             let mut job = app.db
-                .get_job(id)
+                .get_job(&cluster, &id)
                 .await
                 .map_err(|e| {
                     error!(%e, "Couldn't get job!");
                     (StatusCode::INTERNAL_SERVER_ERROR, "Couldn't get job!".to_string())
                 })?;
-            let stats = app.db
-                .get_job_stats(id)
+            let mut stats = app.db
+                .get_job_stats(&cluster, &id)
                 .await
                 .map_err(|e| {
                     error!(%e, "Couldn't get job stats!");
                     (StatusCode::INTERNAL_SERVER_ERROR, "Couldn't get job stats!".to_string())
                 })?;
+            add_sample_rates(&mut stats, timezone);
+            attributes = app.db
+                .get_job_attributes(&cluster, &id)
+                .await
+                .map_err(|e| {
+                    error!(%e, "Couldn't get job attributes!");
+                    (StatusCode::INTERNAL_SERVER_ERROR, "Couldn't get job attributes!".to_string())
+                })?;
 
             if let Some(end_time_str_ref) = job.get_mut("start_time") {
-                timestamp_field_to_date(end_time_str_ref);
+                timestamp_field_to_date(end_time_str_ref, timezone);
             }
             if let Some(end_time_str_ref) = job.get_mut("end_time") {
-                timestamp_field_to_date(end_time_str_ref);
+                timestamp_field_to_date(end_time_str_ref, timezone);
             }
 
             Some((job, stats))
@@ -104,9 +151,7 @@ pub async fn stats(
     } else {
         "?".to_string()
     };
-    let current_timestamp = chrono::Local::now()
-        .format("%b %e, %Y at %l:%M%p")
-        .to_string();
+    let current_timestamp = now_in_timezone(timezone);
 
     let mut jobs = std::iter::once(job.clone())
         .flatten()
@@ -119,12 +164,14 @@ pub async fn stats(
     let tables = vec!(
             ("Metadata", vec!(
                 TableStat::JobID,
+                TableStat::Cluster,
                 TableStat::JobOwner,
                 TableStat::JobName(20),
                 TableStat::JobProject,
                 TableStat::Queue,
                 TableStat::Status,
                 TableStat::ExitStatus,
+                TableStat::ArrayProgress,
             )),
             ("Walltime", vec!(
                 TableStat::StartTime,
@@ -152,11 +199,13 @@ pub async fn stats(
         .into_iter()
         .map(|(title, stats)| {
             let (table_entries, errors) = sort_build_parse(
+                &app.config,
                 groups_cache.clone(),
                 stats,
                 &mut jobs,
                 &params,
-                username.clone()
+                username.clone(),
+                timezone
             );
 
             if let Some(errors) = errors {
@@ -172,7 +221,7 @@ pub async fn stats(
         })
         .collect::<Vec<_>>();
     if let Some(ref mut job) = job {
-        if let Some(modified_job) = jobs.get(0) {
+        if let Some(modified_job) = jobs.first() {
             job.0 = modified_job.clone();
         }
 
@@ -183,22 +232,36 @@ pub async fn stats(
                     "No owner found for job!".to_string()
                 )
             )?;
-    
+        if let (Some(username), Some(id)) = (&username, &id_query) {
+            app.audit(username, AuditAction::ViewJob, Some(owner), Some(&format!("{id} on {cluster}"))).await;
+        }
+
+        // Job environments can hold secrets, so only show
+        //  them to the job's owner and admins
+        let may_see_environment = username.as_ref()
+            .is_some_and(|username| username == owner || is_admin(&app, &groups_cache, username));
+        if !may_see_environment {
+            for (name, value) in attributes.iter_mut() {
+                if name == "Variable_List" {
+                    *value = String::from("(only visible to the job's owner)");
+                }
+            }
+        }
+
         job.0.insert(
             String::from("project"),
             groups_cache.get(owner)
                 .unwrap_or(&Vec::new())
-                .into_iter()
-                .next()
-                .and_then(|st| Some(st.to_owned()))
+                .iter()
+                .next().map(|st| st.to_owned())
                 .unwrap_or(String::from("None"))
         );
     }
     
     // Build template
     let template = StatsPageTemplate {
-        alert: if let Some(_) = username {
-            if let None = id_query {
+        alert: if username.is_some() {
+            if id_query.is_none() {
                 Some("No job ID provided!".to_string())
             } else {
                 all_errors
@@ -207,9 +270,10 @@ pub async fn stats(
             Some("You are not logged in!".to_string())
         },
         username,
-        title: String::from("Job Stats - CRCD Batchmon"),
+        title: format!("Job Stats - {}", app.config.site.name),
         header: if let Some(ref id) = id_query {
-            format!("Extended Job Stats - Job ID {id} ({status}) on {current_timestamp}")
+            let id = short_job_id(id);
+            format!("Extended Job Stats - Job ID {id} on {cluster} ({status}) on {current_timestamp}")
         } else {
             String::from("Job Stats")
         },
@@ -217,7 +281,12 @@ pub async fn stats(
         job,
         jobs: vec!(),
         tables,
-        url_prefix: &app.url_prefix,
+        attributes,
+        raw_days: app.config.retention.raw_days,
+        clusters: vec!(),
+        cluster_query: None,
+        site: &app.config.site,
+        url_prefix: &app.config.server.url_prefix,
 
         toolkit:Toolkit,
         page_type: PageType::Stats
@@ -0,0 +1,278 @@
+use super::super::AppState;
+use crate::{config::SiteConfig, db::lib::AuditAction};
+use super::{cluster_label, date_query_to_timestamp, display_timezone, duration_field_to_hours, parse_cluster_query, timestamp_field_to_month, with_cluster_column, sort_build_parse, try_render_template, Toolkit, TableEntry, TableStat, TableStatType, PageType};
+
+use std::{collections::{BTreeMap, HashMap}, sync::Arc};
+
+use anyhow::Result;
+use chrono_tz::Tz;
+use axum::{
+    extract::{Path, Query, State}, response::Response
+};
+use tower_sessions::Session;
+use axum::http::StatusCode;
+use askama::Template;
+use tracing::{error, info};
+
+
+/// The number of jobs listed as worst offenders
+const WORST_OFFENDERS: usize = 10;
+
+/// Median efficiencies of the jobs which ended in one month
+#[derive(Debug)]
+struct MonthlyEfficiency {
+    month: String,
+    jobs: usize,
+    cpu_efficiency: f32,
+    mem_efficiency: f32,
+    walltime_efficiency: f32
+}
+/// Aggregate usage and efficiency of a user's completed jobs
+#[derive(Debug)]
+struct ReportCard {
+    total_jobs: usize,
+    successful_jobs: usize,
+    success_rate: f32,
+    used_core_hours: f32,
+    rsvd_core_hours: f32,
+    wasted_core_hours: f32,
+    cpu_efficiency: f32,
+    mem_efficiency: f32,
+    walltime_efficiency: f32,
+    months: Vec<MonthlyEfficiency>
+}
+impl ReportCard {
+    /// Builds the report card, adding `used_core_hours`, `rsvd_core_hours`
+    ///  and `wasted_core_hours` fields to each job along the way, with
+    ///  the months in `timezone`
+    fn from_jobs ( jobs: &mut [BTreeMap<String, String>], timezone: Tz ) -> Self {
+        let field = |job: &BTreeMap<String, String>, field: &str| -> f32 {
+            job.get(field)
+                .and_then(|st| st.parse::<f32>().ok())
+                .unwrap_or(0f32)
+        };
+
+        let mut used_core_hours = 0f32;
+        let mut rsvd_core_hours = 0f32;
+        let mut by_month: BTreeMap<String, Vec<(f32, f32, f32)>> = BTreeMap::new();
+        for job in jobs.iter_mut() {
+            let used = duration_field_to_hours(job.get("used_cpu_time").map(|st| st.as_str()).unwrap_or(""));
+            let rsvd = field(job, "req_cpus")
+                * duration_field_to_hours(job.get("used_walltime").map(|st| st.as_str()).unwrap_or(""));
+            used_core_hours += used;
+            rsvd_core_hours += rsvd;
+
+            job.insert(String::from("used_core_hours"), format!("{used:.2}"));
+            job.insert(String::from("rsvd_core_hours"), format!("{rsvd:.2}"));
+            job.insert(String::from("wasted_core_hours"), format!("{:.2}", (rsvd - used).max(0f32)));
+
+            let month = timestamp_field_to_month(job.get("end_time").map(|st| st.as_str()).unwrap_or(""), timezone);
+            by_month.entry(month)
+                .or_default()
+                .push((
+                    field(job, "cpu_efficiency"),
+                    field(job, "mem_efficiency"),
+                    field(job, "walltime_efficiency")
+                ));
+        }
+
+        let successful_jobs = jobs.iter()
+            .filter(|job| job.get("exit_status").is_some_and(|st| st == "0"))
+            .count();
+        let medians = |efficiencies: &[(f32, f32, f32)]| -> (f32, f32, f32) {
+            (
+                median(efficiencies.iter().map(|eff| eff.0).collect()),
+                median(efficiencies.iter().map(|eff| eff.1).collect()),
+                median(efficiencies.iter().map(|eff| eff.2).collect())
+            )
+        };
+        let all_efficiencies = by_month.values()
+            .flatten()
+            .copied()
+            .collect::<Vec<_>>();
+        let (cpu_efficiency, mem_efficiency, walltime_efficiency) = medians(&all_efficiencies);
+
+        Self {
+            total_jobs: jobs.len(),
+            successful_jobs,
+            success_rate: successful_jobs as f32 * 100f32 / jobs.len().max(1) as f32,
+            used_core_hours,
+            rsvd_core_hours,
+            wasted_core_hours: (rsvd_core_hours - used_core_hours).max(0f32),
+            cpu_efficiency,
+            mem_efficiency,
+            walltime_efficiency,
+            months: by_month.iter()
+                .map(|(month, efficiencies)| {
+                    let (cpu_efficiency, mem_efficiency, walltime_efficiency) = medians(efficiencies);
+
+                    MonthlyEfficiency {
+                        month: month.clone(),
+                        jobs: efficiencies.len(),
+                        cpu_efficiency,
+                        mem_efficiency,
+                        walltime_efficiency
+                    }
+                })
+                .collect()
+        }
+    }
+}
+fn median ( mut values: Vec<f32> ) -> f32 {
+    if values.is_empty() {
+        return 0f32;
+    }
+
+    values.sort_by(|a, b| a.total_cmp(b));
+    let middle = values.len() / 2;
+    if values.len().is_multiple_of(2) {
+        (values[middle - 1] + values[middle]) / 2f32
+    } else {
+        values[middle]
+    }
+}
+
+#[derive(Template, Debug)]
+#[template(path = "pages/user.html")]
+struct UserPageTemplate<'a> {
+    username: Option<String>,
+    needs_login: bool,
+    title: String,
+    header: String,
+    alert: Option<String>,
+    jobs: Vec<BTreeMap<String, String>>,
+    table_entries: Vec<TableEntry>,
+
+    user_query: String,
+    date_query: String,
+    report: Option<ReportCard>,
+    clusters: Vec<String>,
+    cluster_query: Option<String>,
+    site: &'a SiteConfig,
+    url_prefix: &'a str,
+
+    page_type: PageType,
+    toolkit: Toolkit
+}
+#[tracing::instrument]
+pub async fn user(
+    State(app): State<Arc<AppState>>,
+    Path(user_query): Path<String>,
+    Query(params): Query<HashMap<String, String>>,
+    session: Session,
+) -> Result<Response, (StatusCode, String)> {
+    info!("[ Got request to build user page...]");
+
+    // Unpack username and query parameters
+    let username = session.get::<String>("username")
+        .await
+        .map_err(|e| {
+            error!(%e, "Couldn't get username from session!");
+            (StatusCode::UNAUTHORIZED, "Couldn't get username from session!".to_string())
+        })?;
+    let date_query = params.get("date")
+        .map(|st| st.to_lowercase())
+        .unwrap_or(String::from("year"));
+    let cluster_query = parse_cluster_query(&app, &params)?;
+    let adjusted_timestamp = date_query_to_timestamp(Some(&date_query));
+    let timezone = display_timezone(&app, username.as_deref()).await;
+
+    // Get the user's completed jobs, with arrays as their subjobs
+    let mut jobs = if username.is_some() {
+        app.db
+            .get_user_report_jobs(
+                &user_query,
+                adjusted_timestamp,
+                cluster_query.as_ref()
+            )
+            .await
+            .map_err(|e| {
+                error!(%e, "Couldn't get user jobs!");
+                (StatusCode::INTERNAL_SERVER_ERROR, "Couldn't get user jobs!".to_string())
+            })?
+    } else {
+        vec!()
+    };
+    if let Some(ref username) = username {
+        let detail = format!("Since {} on {}", date_query, cluster_label(&app, cluster_query.as_ref()));
+        app.audit(username, AuditAction::ViewUser, Some(&user_query), Some(&detail)).await;
+    }
+    let report = username.is_some()
+        .then(|| ReportCard::from_jobs(&mut jobs, timezone));
+
+    // Keep only the jobs which wasted the most core-hours, in
+    //  ascending order, since `sort_build_parse` reverses them
+    jobs.sort_by(|a, b| {
+        let wasted = |job: &BTreeMap<String, String>| job.get("wasted_core_hours")
+            .and_then(|st| st.parse::<f32>().ok())
+            .unwrap_or(0f32);
+        wasted(b).total_cmp(&wasted(a))
+    });
+    jobs.truncate(WORST_OFFENDERS);
+    jobs.reverse();
+
+    // Tweak data to be presentable and add tooltips for efficiencies
+    let groups_cache = app.db
+        .get_groups_cache()
+        .await;
+    let (table_entries, errors) = sort_build_parse(
+        &app.config,
+        groups_cache,
+        with_cluster_column(&app, vec!(
+            TableStat::JobID,
+            TableStat::JobName(20),
+            TableStat::Queue,
+            TableStat::RsvdCpus,
+            TableStat::EndTime,
+            TableStat::Custom {
+                name: String::from("Wasted Core-Hours"),
+                tooltip: String::from("<b>Wasted Core-Hours</b><br><br>Reserved core-hours (reserved CPUs multiplied by the elapsed walltime) which went unused"),
+                sort_by: Some(String::from("wasted_core_hours")),
+                value: String::from("wasted_core_hours"),
+                value_unit: None,
+                stat_type: TableStatType::Default
+            },
+            TableStat::ElapsedWalltimeColored,
+            TableStat::CpuEfficiency,
+            TableStat::MemEfficiency,
+            TableStat::ExitStatus,
+            TableStat::More
+        )),
+
+        &mut jobs,
+        &params,
+        username.clone(),
+        timezone
+    );
+    let url_prefix = &app.config.server.url_prefix;
+    let cluster_label = cluster_label(&app, cluster_query.as_ref());
+
+    // Build the template
+    let template = UserPageTemplate {
+        jobs,
+        alert: if username.is_some() {
+                errors
+            } else {
+                Some("You are not logged in!".to_string())
+            },
+        username,
+        needs_login: true,
+        title: format!("{user_query} - {}", app.config.site.name),
+        header: format!("Report Card for '{user_query}' on {cluster_label}"),
+
+        table_entries,
+
+        user_query,
+        date_query,
+        report,
+        clusters: app.cluster_names(),
+        cluster_query,
+        site: &app.config.site,
+        url_prefix,
+
+        toolkit: Toolkit,
+        page_type: PageType::User
+    };
+
+    try_render_template(&template)
+}
@@ -0,0 +1,77 @@
+use std::{sync::{atomic::{AtomicU64, Ordering}, LazyLock}, time::Instant};
+
+use axum::{
+    extract::{MatchedPath, Request}, http::HeaderValue, middleware::Next, response::Response
+};
+use tower_sessions::Session;
+use tracing::{field::Empty, info, info_span, Instrument};
+
+/// Carries each request's ID, which is kept from the request if
+///  a proxy set it, and always returned with the response
+const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Distinguishes IDs made by this run of Hawkeye from those of past runs
+static ID_PREFIX: LazyLock<String> = LazyLock::new(|| format!("{:x}", chrono::Utc::now().timestamp()));
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+/// A new ID for a request or daemon run, unique across restarts
+pub fn new_trace_id ( ) -> String {
+    format!("{}-{:x}", *ID_PREFIX, NEXT_ID.fetch_add(1, Ordering::Relaxed))
+}
+
+async fn session_username ( session: Option<&Session> ) -> Option<String> {
+    session?.get::<String>("username")
+        .await
+        .ok()
+        .flatten()
+}
+
+/// Runs each request in a span with its ID, method and route (so
+///  that everything it logs can be tied back to it), and logs its
+///  status, latency and user once it's answered
+pub async fn trace_requests (
+    request: Request,
+    next: Next
+) -> Response {
+    let request_id = request.headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|id| {
+            !id.is_empty() && id.len() <= 64
+                && id.chars().all(|c| c.is_ascii_alphanumeric() || "-_.".contains(c))
+        })
+        .map(|id| id.to_string())
+        .unwrap_or_else(new_trace_id);
+    let method = request.method().clone();
+    let route = request.extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_else(|| request.uri().path().to_string());
+    let session = request.extensions()
+        .get::<Session>()
+        .cloned();
+
+    let span = info_span!("request", %request_id, %method, %route, user = Empty);
+    let user_before = session_username(session.as_ref()).await;
+    let start = Instant::now();
+
+    let mut response = next.run(request)
+        .instrument(span.clone())
+        .await;
+
+    // Logging in sets the user, and logging out clears it
+    let latency_ms = start.elapsed().as_secs_f64() * 1000f64;
+    if let Some(user) = session_username(session.as_ref()).await.or(user_before) {
+        span.record("user", user);
+    }
+    let status = response.status().as_u16();
+    span.in_scope(|| {
+        info!(status, latency_ms, "[ {method} {route} answered {status} in {latency_ms:.1}ms ]");
+    });
+
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+
+    response
+}